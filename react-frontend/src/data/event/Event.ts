@@ -1,11 +1,9 @@
-import EventType from "./EventType";
-
 export default interface Event {
     "id"?: string,
-    "type": EventType,
+    "type": number,
     "name": string,
     "start": number,
     "end"?: number | null,
     "description": string,
-    "notification"?: number | null
-}
\ No newline at end of file
+    "notifications"?: Array<number>
+}
@@ -1,3 +1,12 @@
-type EventType = 'homework' | 'exam' | 'holidays' | 'other';
+export default interface EventType {
+    "id"?: number,
+    "name": string,
+    "colour": string,
+    "reminders"?: Array<number>,
+    "builtIn"?: boolean
+}
 
-export default EventType;
\ No newline at end of file
+export const HOMEWORK = 1;
+export const EXAM = 2;
+export const HOLIDAYS = 3;
+export const OTHER = 4;
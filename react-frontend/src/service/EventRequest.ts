@@ -1,4 +1,5 @@
 import Event from "../data/event/Event";
+import EventType from "../data/event/EventType";
 import Axios from './AxiosInstance'
 
 export default class EventRequest {
@@ -15,7 +16,7 @@ export default class EventRequest {
             start: event.start,
             end: event.end,
             description: event.description,
-            notification: event.notification,
+            notifications: event.notifications,
         }).catch((err) => {
             throw new Error(err.response.data)
         })
@@ -28,6 +29,13 @@ export default class EventRequest {
         return response.data;
     }
 
+    public async getEventTypes(classId: string): Promise<Array<EventType>> {
+        const response = await this.axios.axios.get<Array<EventType>>(`/classes/${classId}/event-types`).catch((err) => {
+            throw new Error(err.response.data)
+        });
+        return response.data;
+    }
+
     public async deleteEvent(classId: string, eventId: string) {
         await this.axios.axios.delete(`/classes/${classId}/events/${eventId}`).catch((err) => {
             throw new Error(err.response.data)
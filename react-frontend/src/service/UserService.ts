@@ -3,6 +3,7 @@ import Class from "../data/class/Class";
 import Member from "../data/user/Member";
 import MemberRole from "../data/user/MemberRole";
 import Event from "../data/event/Event";
+import EventType from "../data/event/EventType";
 import TimeTable from "../data/timetable/TimeTable";
 import TimeTableDay from "../data/timetable/TimetableDay";
 import TimetableRequest from "./TimetableRequest";
@@ -169,6 +170,10 @@ export default class UserService {
         return await this.eventRequest.createEvent(classID, event).then(() => window.location.reload())
     }
 
+    public async getEventTypes(classId: string): Promise<Array<EventType>> {
+        return await this.eventRequest.getEventTypes(classId)
+    }
+
     public async deleteEvent(classId: string, eventId: string): Promise<void> {
         return await this.eventRequest.deleteEvent(classId, eventId);
     }
@@ -1,4 +1,4 @@
-import React, {useContext} from 'react';
+import React, {useContext, useEffect, useState} from 'react';
 import Alert from 'react-bootstrap/Alert';
 import Button from 'react-bootstrap/Button';
 import Form from 'react-bootstrap/Form';
@@ -17,14 +17,15 @@ import {useFormik} from "formik";
 import {UserServiceContext} from "../../../Router";
 import {CurrentClass} from "../ClassView";
 import * as Yup from "yup";
-import EventType from "../../../../data/event/EventType";
+import EventType, {OTHER} from "../../../../data/event/EventType";
+import {formatType} from "../calendar/Calendar";
 
 type submitValues = {
     eventName: string,
     startDate: number,
     endDate?: number,
     description: string,
-    eventType: EventType,
+    eventType: number,
     notification: number | null
 }
 
@@ -39,14 +40,18 @@ const validationSchema = Yup.object().shape({
         .notRequired(),
     description: Yup.string()
         .notRequired(),
-    eventType: Yup.string()
-        .oneOf(['homework', 'holidays', 'exam', 'other'])
+    eventType: Yup.number()
         .required('Der Typ muss ausgewählt sein'),
 })
 
 const NewEvent = () => {
     const userService = useContext(UserServiceContext);
     const currentClass = useContext(CurrentClass);
+    const [eventTypes, setEventTypes] = useState<Array<EventType>>([]);
+    useEffect(() => {
+        userService.getEventTypes(currentClass!.id).then(setEventTypes);
+    }, [currentClass, userService])
+
     const onSubmit = ({eventName, startDate, endDate, description, eventType, notification}: submitValues) => {
         userService.createEvent(currentClass!.id, {
             name: eventName,
@@ -54,7 +59,7 @@ const NewEvent = () => {
             end: (endDate) ? endDate * 1000 : undefined,
             description: description,
             type: eventType,
-            notification: notification ? notification * 1000 : null
+            notifications: notification ? [notification * 1000] : []
         })
     }
 
@@ -64,7 +69,7 @@ const NewEvent = () => {
             startDate: 0,
             endDate: 0,
             description: '',
-            eventType: 'other',
+            eventType: OTHER,
             notification: null
         },
         onSubmit: onSubmit,
@@ -136,13 +141,16 @@ const NewEvent = () => {
                 </Row>
                 <br/>
                 <Row className={'text-center'}>
-                    <Dropdown onSelect={(value) => formik.setFieldValue('eventType', value)}>
-                        <Dropdown.Toggle>{getFormatted(formik.values.eventType)}</Dropdown.Toggle>
+                    <Dropdown onSelect={(value) => formik.setFieldValue('eventType', Number(value))}>
+                        <Dropdown.Toggle>{formatType(formik.values.eventType, eventTypes)}</Dropdown.Toggle>
                         <Dropdown.Menu>
-                            <Dropdown.Item eventKey={'homework'}>Hausaufgabe</Dropdown.Item>
-                            <Dropdown.Item eventKey={'exam'}>Prüfung</Dropdown.Item>
-                            <Dropdown.Item eventKey={'holidays'}>Ferien</Dropdown.Item>
-                            <Dropdown.Item eventKey={'other'} default>Anderes</Dropdown.Item>
+                            {
+                                eventTypes.map((eventType) => (
+                                    <Dropdown.Item key={eventType.id} eventKey={String(eventType.id)}>
+                                        {formatType(eventType.id!, eventTypes)}
+                                    </Dropdown.Item>
+                                ))
+                            }
                         </Dropdown.Menu>
                     </Dropdown>
                 </Row>
@@ -158,13 +166,4 @@ const NewEvent = () => {
     );
 };
 
-const getFormatted = (type: string): string => types[type];
-
-const types: { [type: string]: string } = {
-    'homework': 'Hausaufgabe',
-    'holidays': 'Ferien',
-    'exam': 'Prüfung',
-    'other': 'Anderes',
-}
-
 export default NewEvent;
\ No newline at end of file
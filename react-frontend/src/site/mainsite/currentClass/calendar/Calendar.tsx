@@ -7,7 +7,7 @@ import interactionPlugin from "@fullcalendar/interaction";
 import {UserServiceContext} from "../../../Router";
 import Event from "../../../../data/event/Event";
 import {Container} from "react-bootstrap";
-import EventType from "../../../../data/event/EventType";
+import EventType, {EXAM, HOLIDAYS, HOMEWORK, OTHER} from "../../../../data/event/EventType";
 import EventPopup from "./EventPopup";
 import bootstrapPlugin from '@fullcalendar/bootstrap';
 
@@ -17,9 +17,11 @@ const Calendar = () => {
     const currentClass = useContext(CurrentClass);
     const userService = useContext(UserServiceContext)
     const [events, setEvents] = useState<Array<Event>>([]);
+    const [eventTypes, setEventTypes] = useState<Array<EventType>>([]);
     const [selectedEvent, setSelectedEvent] = useState<Event>();
     useEffect(() => {
         userService.getCalendar(currentClass!.id).then(setEvents);
+        userService.getEventTypes(currentClass!.id).then(setEventTypes);
     }, [currentClass, userService])
 
     const handleEventClick = (event: EventClickArg) => {
@@ -38,7 +40,7 @@ const Calendar = () => {
             {
                 selectedEvent && (
                     <>
-                        <EventPopup event={selectedEvent} onClose={() => setSelectedEvent(undefined)}/>
+                        <EventPopup event={selectedEvent} eventTypes={eventTypes} onClose={() => setSelectedEvent(undefined)}/>
                     </>
                 )
             }
@@ -63,9 +65,9 @@ const Calendar = () => {
                         title: val.name,
                         start: val.start,
                         end: (val.end) ? val.end : undefined,
-                        allDay: val.type === 'holidays',
-                        backgroundColor: getColorOfEvent(val.type),
-                        borderColor: getColorOfEvent(val.type),
+                        allDay: val.type === HOLIDAYS,
+                        backgroundColor: getColorOfEvent(val.type, eventTypes),
+                        borderColor: getColorOfEvent(val.type, eventTypes),
                     }))}
                     editable={true}
                     selectable={true}
@@ -98,25 +100,23 @@ const renderEventContent = (eventContent: EventContentArg) => {
     )
 }
 
-const color = {
-    'homework': '#b1b3fa',
-    'exam': '#eed850',
-    'holidays': '#05c61c',
-    'other': '#a5a5a5'
-}
+const defaultColor = '#a5a5a5'
 
-const getColorOfEvent = (event: EventType) => color[event]
+const getColorOfEvent = (type: number, eventTypes: Array<EventType>) =>
+    eventTypes.find((eventType) => eventType.id === type)?.colour || defaultColor
 
-const formatType = (eventType: EventType): string => {
-    switch (eventType) {
-        case 'homework':
+const formatType = (type: number, eventTypes: Array<EventType>): string => {
+    switch (type) {
+        case HOMEWORK:
             return 'Hausaufgabe'
-        case "exam":
+        case EXAM:
             return 'Prüfung'
-        case "holidays":
+        case HOLIDAYS:
             return 'Ferien'
-        case "other":
+        case OTHER:
             return 'Andere'
+        default:
+            return eventTypes.find((eventType) => eventType.id === type)?.name || 'Andere'
     }
 }
 
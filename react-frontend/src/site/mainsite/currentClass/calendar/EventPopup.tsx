@@ -3,11 +3,11 @@ import Button from 'react-bootstrap/Button';
 import Modal from 'react-bootstrap/Modal';
 import Event from "../../../../data/event/Event";
 import {formatType} from "./Calendar";
-import EventType from "../../../../data/event/EventType";
+import EventType, {HOLIDAYS} from "../../../../data/event/EventType";
 import {UserServiceContext} from "../../../Router";
 import {CurrentClass} from "../ClassView";
 
-const EventPopup = ({event, onClose}: { event: Event, onClose: () => void }) => {
+const EventPopup = ({event, eventTypes, onClose}: { event: Event, eventTypes: Array<EventType>, onClose: () => void }) => {
     const userService = useContext(UserServiceContext);
     const currentClass = useContext(CurrentClass);
 
@@ -38,7 +38,7 @@ const EventPopup = ({event, onClose}: { event: Event, onClose: () => void }) =>
                 {
                     event.description && <p>Beschreibung: {event.description}</p>
                 }
-                <p>Typ: {formatType(event.type)}</p>
+                <p>Typ: {formatType(event.type, eventTypes)}</p>
             </Modal.Body>
             <Modal.Footer>
                 <Button onClick={handleClose}>Schliessen</Button>
@@ -51,12 +51,12 @@ const EventPopup = ({event, onClose}: { event: Event, onClose: () => void }) =>
 };
 
 
-const formatDate = (timestamp: number, type: EventType): string => {
+const formatDate = (timestamp: number, type: number): string => {
     const date = new Date(timestamp);
     const minute = '0' + date.getMinutes();
     const hours = '0' + date.getHours();
 
-    return `${date.getDate()}.${date.getMonth()}.${date.getFullYear()} ` + ((type !== 'holidays') ? `${hours.substr(-2)}:${minute.substr(-2)}` : '')
+    return `${date.getDate()}.${date.getMonth()}.${date.getFullYear()} ` + ((type !== HOLIDAYS) ? `${hours.substr(-2)}:${minute.substr(-2)}` : '')
 }
 
 export default EventPopup;
\ No newline at end of file
@@ -3,12 +3,16 @@ use serenity::prelude::*;
 
 use crate::commands::format_datetime;
 use crate::error::{BotError, BotResult};
-use crate::functions::{format_date, limit_length};
+use crate::functions::{class_timezone, find_event_type, format_date, limit_length};
 use crate::requests::CorsClient;
 use chrono::Utc;
+use chrono_tz::Tz;
 use serenity::builder::CreateEmbed;
 use tracing::{debug, warn};
 
+/// The maximum amount of events shown at once
+const MAX_EVENTS: usize = 10;
+
 pub async fn handle_event_command(
     ctx: &Context,
     interaction: &Interaction,
@@ -36,8 +40,8 @@ async fn show_all_events(ctx: &Context, interaction: &Interaction) -> BotResult<
 }
 
 async fn show_next_events(ctx: &Context, interaction: &Interaction) -> BotResult<()> {
-    let current_time = Utc::now().timestamp_millis();
-    let events = get_events(ctx, interaction.guild_id, None, Some(current_time)).await?;
+    let current_time = Utc::now().into();
+    let events = get_next_events(ctx, interaction.guild_id, current_time).await?;
 
     debug!(len = %events.len());
     send_events(ctx, interaction, events.as_slice()).await
@@ -54,10 +58,16 @@ async fn show_filter_events(
         .ok_or(BotError::Other("event show filter has no option"))?;
 
     if let Some(serde_json::Value::String(typ)) = &typ.value {
+        let types = get_event_types(ctx, interaction.guild_id).await?;
+        let typ = match find_event_type(&types, typ) {
+            Some(typ) => typ.id,
+            None => return send_unknown_type(ctx, interaction, &types).await,
+        };
+
         let events = get_events(ctx, interaction.guild_id, None, None)
             .await?
             .into_iter()
-            .filter(|event| event.r#type.as_str() == typ)
+            .filter(|event| event.r#type == typ)
             .collect::<Vec<_>>();
 
         let events = events;
@@ -79,15 +89,7 @@ async fn show_search_events(
         .ok_or(BotError::Other("event show search has no option"))?;
 
     if let Some(serde_json::Value::String(query)) = &typ.value {
-        let query = query.to_lowercase();
-        let events = get_events(ctx, interaction.guild_id, None, None)
-            .await?
-            .into_iter()
-            .filter(|event| {
-                event.name.to_lowercase().contains(&query)
-                    || event.description.to_lowercase().contains(&query)
-            })
-            .collect::<Vec<_>>();
+        let events = search_events(ctx, interaction.guild_id, query).await?;
 
         send_events(ctx, interaction, events.as_slice()).await
     } else {
@@ -98,8 +100,8 @@ async fn show_search_events(
 async fn get_events(
     ctx: &Context,
     guild_id: Option<GuildId>,
-    before: Option<i64>,
-    after: Option<i64>,
+    before: Option<dto::Timestamp>,
+    after: Option<dto::Timestamp>,
 ) -> BotResult<Vec<dto::Event>> {
     let guild_id = guild_id.ok_or(BotError::Other("Guild id not found"))?;
 
@@ -109,18 +111,95 @@ async fn get_events(
     Ok(client.get_events(guild_id.0, before, after).await?)
 }
 
-async fn send_events(
+async fn get_next_events(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    after: dto::Timestamp,
+) -> BotResult<Vec<dto::Event>> {
+    let guild_id = guild_id.ok_or(BotError::Other("Guild id not found"))?;
+
+    let map = ctx.data.read().await;
+    let client = map.get::<CorsClient>().unwrap();
+
+    client
+        .get_next_events(guild_id.0, after, MAX_EVENTS as i64)
+        .await
+}
+
+async fn search_events(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    query: &str,
+) -> BotResult<Vec<dto::Event>> {
+    let guild_id = guild_id.ok_or(BotError::Other("Guild id not found"))?;
+
+    let map = ctx.data.read().await;
+    let client = map.get::<CorsClient>().unwrap();
+
+    client.search_events(guild_id.0, query).await
+}
+
+/// The timezone of the class of the guild
+async fn get_timezone(ctx: &Context, guild_id: Option<GuildId>) -> BotResult<Tz> {
+    let guild_id = guild_id.ok_or(BotError::Other("Guild id not found"))?;
+
+    let map = ctx.data.read().await;
+    let client = map.get::<CorsClient>().unwrap();
+
+    Ok(class_timezone(
+        &client.get_class(guild_id.0).await?.timezone,
+    ))
+}
+
+async fn get_event_types(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+) -> BotResult<Vec<dto::EventType>> {
+    let guild_id = guild_id.ok_or(BotError::Other("Guild id not found"))?;
+
+    let map = ctx.data.read().await;
+    let client = map.get::<CorsClient>().unwrap();
+
+    client.get_event_types(guild_id.0).await
+}
+
+async fn send_unknown_type(
     ctx: &Context,
     interaction: &Interaction,
-    events: &[dto::Event],
+    types: &[dto::EventType],
 ) -> BotResult<()> {
-    let mut events = events
+    let names = types
         .iter()
-        .take(10)
-        .cloned() // todo oh
-        .collect::<Vec<_>>();
+        .map(|typ| typ.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    Ok(interaction
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|message| {
+                    message
+                        .create_embed(|embed| {
+                            embed
+                                .title("Unbekannter Typ")
+                                .field("Verfügbare Typen", names, false)
+                        })
+                        .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                })
+        })
+        .await?)
+}
 
+async fn send_events(
+    ctx: &Context,
+    interaction: &Interaction,
+    events: &[dto::Event],
+) -> BotResult<()> {
+    let tz = get_timezone(ctx, interaction.guild_id).await?;
+    let mut events = events.to_vec();
     events.sort_unstable_by(|e1, e2| e1.start.cmp(&e2.start));
+    events.truncate(MAX_EVENTS);
 
     Ok(interaction
         .create_interaction_response(&ctx.http, |response| {
@@ -128,14 +207,18 @@ async fn send_events(
                 .kind(InteractionResponseType::ChannelMessageWithSource)
                 .interaction_response_data(|message| {
                     message
-                        .create_embed(|embed| event_embed(embed, events.as_slice()))
+                        .create_embed(|embed| event_embed(embed, events.as_slice(), tz))
                         .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
                 })
         })
         .await?)
 }
 
-fn event_embed<'a>(embed: &'a mut CreateEmbed, events: &[dto::Event]) -> &'a mut CreateEmbed {
+fn event_embed<'a>(
+    embed: &'a mut CreateEmbed,
+    events: &[dto::Event],
+    tz: Tz,
+) -> &'a mut CreateEmbed {
     const MAX_DESCRIPTION_LENGTH: usize = 100;
 
     let mut fields = events
@@ -144,22 +227,27 @@ fn event_embed<'a>(embed: &'a mut CreateEmbed, events: &[dto::Event]) -> &'a mut
             let description = limit_length(&event.description, MAX_DESCRIPTION_LENGTH);
 
             let end_value = if let Some(end) = event.end {
-                format!(" - {}", format_datetime(end))
+                format!(" - {}", format_datetime(end, tz))
             } else {
                 "".to_string()
             };
 
-            let notification = if let Some(time) = event.notification {
-                format!("\n\n> Benachrichtigung um {}", format_datetime(time))
+            let notification = event
+                .notifications
+                .iter()
+                .map(|time| format!("\n> Benachrichtigung um {}", format_datetime(*time, tz)))
+                .collect::<String>();
+            let notification = if notification.is_empty() {
+                notification
             } else {
-                "".to_string()
+                format!("\n{}", notification)
             };
 
             (
-                format!("{} | {}", format_date(event.start), event.name),
+                format!("{} | {}", format_date(event.start, tz), event.name),
                 format!(
                     "{}{} \n\n {}{}",
-                    format_datetime(event.start),
+                    format_datetime(event.start, tz),
                     end_value,
                     description,
                     notification
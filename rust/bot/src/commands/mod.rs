@@ -1,16 +1,17 @@
 use chrono::format::{DelayedFormat, StrftimeItems};
 use chrono::Utc;
+use chrono_tz::Tz;
 use serenity::builder::CreateEmbed;
 use serenity::model::prelude::*;
 use serenity::prelude::*;
 use tracing::debug;
 
-use dto::Lesson;
+use dto::{EffectiveHoliday, Event, Lesson, Timestamp};
 pub use setup::setup_slash_commands;
 
 use crate::error::{BotError, BotResult};
 use crate::functions;
-use crate::functions::{absolute_time_as_weekday, from_utc_timestamp, from_utc_to_cest};
+use crate::functions::{absolute_time_as_weekday, from_utc_timestamp, from_utc_to_local};
 use crate::requests::CorsClient;
 
 mod events;
@@ -42,15 +43,40 @@ async fn wie_lange_noch(ctx: &Context, interaction: &Interaction) -> BotResult<(
     let map = ctx.data.read().await;
     let client = map.get::<CorsClient>().unwrap();
 
-    let timetable = client.get_timetable(guild_id.0).await?;
-    let found = timetable.is_some();
+    let now = Utc::now();
+    let days = client
+        .get_effective_timetable(guild_id.0, now.into(), 1)
+        .await?;
+    let found = days.is_some();
+    let tz = functions::class_timezone(&client.get_class(guild_id.0).await?.timezone);
+
+    let today = days.as_ref().and_then(|days| days.first());
+    let holiday = today.and_then(|today| today.holiday.as_ref());
+    let (lesson, next) = match today {
+        Some(today) => {
+            let (diff, _) = functions::absolute_time_as_weekday(now, tz);
+            functions::wie_lange_noch(&today.lessons, diff)
+        }
+        None => (None, None),
+    };
 
-    let (lesson, next) = if let Some(ref timetable) = timetable {
-        let (diff, weekday) = functions::absolute_time_as_weekday(chrono::Utc::now());
-        functions::wie_lange_noch(timetable, diff, weekday)
+    // the homework that is due today
+    let events = if lesson.is_some() || next.is_some() {
+        let after = (now - chrono::Duration::days(1)).into();
+        let before = (now + chrono::Duration::days(1)).into();
+        client
+            .get_events(guild_id.0, Some(before), Some(after))
+            .await?
     } else {
-        (None, None)
+        vec![]
     };
+    let today = from_utc_to_local(now, tz).naive_local().date();
+    let homework = |lesson: Option<&Lesson>| {
+        lesson
+            .map(|lesson| functions::due_homework(&events, &lesson.subject, today, tz))
+            .unwrap_or_default()
+    };
+    let (lesson_homework, next_homework) = (homework(lesson), homework(next));
 
     Ok(interaction
         .create_interaction_response(&ctx.http, |response| {
@@ -58,7 +84,16 @@ async fn wie_lange_noch(ctx: &Context, interaction: &Interaction) -> BotResult<(
                 .kind(InteractionResponseType::ChannelMessageWithSource)
                 .interaction_response_data(|message| {
                     message
-                        .create_embed(|embed| wie_lange_noch_embed(embed, found, lesson, next))
+                        .create_embed(|embed| {
+                            wie_lange_noch_embed(
+                                embed,
+                                found,
+                                holiday,
+                                tz,
+                                (lesson, &lesson_homework),
+                                (next, &next_homework),
+                            )
+                        })
                         .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
                 })
         })
@@ -86,13 +121,22 @@ async fn info(ctx: &Context, interaction: &Interaction) -> BotResult<()> {
         .await?)
 }
 
+/// The lessons come with the homework that is due at them
 fn wie_lange_noch_embed<'a>(
     embed: &'a mut CreateEmbed,
     found_timetable: bool,
-    lesson: Option<&Lesson>,
-    next: Option<&Lesson>,
+    holiday: Option<&EffectiveHoliday>,
+    tz: Tz,
+    (lesson, lesson_homework): (Option<&Lesson>, &[&Event]),
+    (next, next_homework): (Option<&Lesson>, &[&Event]),
 ) -> &'a mut CreateEmbed {
-    let now = absolute_time_as_weekday(Utc::now()).0;
+    let now = absolute_time_as_weekday(Utc::now(), tz).0;
+
+    if let Some(holiday) = holiday {
+        return embed
+            .title("Ferien")
+            .description(functions::holiday_text(holiday));
+    }
 
     match (found_timetable, lesson, next) {
         (false, _, _) => embed.title("Stundenplan nicht gefunden").field(
@@ -105,18 +149,22 @@ fn wie_lange_noch_embed<'a>(
             let time_to_next = format_time(next.start - now);
             embed.title("Keine Lektion").field(
                 format!("Nächste Lektion: {}", next.subject),
-                format!("Start in: {}h", time_to_next),
+                format!(
+                    "Start in: {}h{}",
+                    time_to_next,
+                    format_homework(next_homework)
+                ),
                 false,
             )
         }
         (_, Some(lesson), next) => {
-            let (current, _) = absolute_time_as_weekday(Utc::now());
+            let (current, _) = absolute_time_as_weekday(Utc::now(), tz);
             let remaining = lesson.end - current;
             embed
                 .title(format!("Aktuelle Lektion: {}", lesson.subject))
                 .field(
                     format!("Noch {}h", format_time(remaining),),
-                    &lesson.description,
+                    format!("{}{}", lesson.description, format_homework(lesson_homework)),
                     false,
                 );
             if let Some(next) = next {
@@ -124,7 +172,7 @@ fn wie_lange_noch_embed<'a>(
 
                 embed.field(
                     format!("Nächste Lektion: {}", &next.subject),
-                    format!("in {}h", time_to_next),
+                    format!("in {}h{}", time_to_next, format_homework(next_homework)),
                     false,
                 );
             }
@@ -133,8 +181,16 @@ fn wie_lange_noch_embed<'a>(
     }
 }
 
+/// A line for every homework, used below a lesson
+pub(crate) fn format_homework(homework: &[&Event]) -> String {
+    homework
+        .iter()
+        .map(|event| format!("\n📚 {}", event.name))
+        .collect()
+}
+
 fn format_time(time: i64) -> DelayedFormat<StrftimeItems<'static>> {
-    from_utc_timestamp(time).format("%H:%M:%S")
+    from_utc_timestamp(Timestamp(time)).format("%H:%M:%S")
 }
 
 fn info_embed(
@@ -174,8 +230,10 @@ fn info_embed(
         .footer(|f| f.text("Bot Version 0.0"))
 }
 
+/// The date and time in the timezone of the class, with the abbreviation of the timezone (CET or CEST)
 pub fn format_datetime(
-    time: i64,
+    time: Timestamp,
+    tz: Tz,
 ) -> chrono::format::DelayedFormat<chrono::format::StrftimeItems<'static>> {
-    from_utc_to_cest(from_utc_timestamp(time)).format("%d.%m.%Y %H:%M CEST")
+    from_utc_to_local(from_utc_timestamp(time), tz).format("%d.%m.%Y %H:%M %Z")
 }
@@ -48,12 +48,8 @@ fn create_commands(commands: &mut CreateApplicationCommands) -> &mut CreateAppli
                         .create_sub_option(|option| {
                             option
                                 .name("typ")
-                                .description("Der Typ nach dem gefiltert werden soll")
+                                .description("Der Typ nach dem gefiltert werden soll, zB. exam")
                                 .kind(ApplicationCommandOptionType::String)
-                                .add_string_choice("Hausaufgabe", "homework")
-                                .add_string_choice("Prüfung", "exam")
-                                .add_string_choice("Ferien", "holidays")
-                                .add_string_choice("Andere", "other")
                                 .required(true)
                         })
                 })
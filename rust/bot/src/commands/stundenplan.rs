@@ -1,7 +1,12 @@
+use crate::commands::format_homework;
 use crate::error::{BotError, BotResult};
-use crate::functions::format_day_time;
+use crate::functions::{
+    class_timezone, cycle_week_name, due_homework, format_day_time, holiday_text,
+};
 use crate::requests::CorsClient;
-use dto::Timetable;
+use chrono::{Datelike, Utc};
+use chrono_tz::Tz;
+use dto::{EffectiveDay, Event};
 use serenity::client::Context;
 use serenity::model::prelude::*;
 
@@ -12,20 +17,31 @@ pub async fn handle_timetable(ctx: &Context, interaction: &Interaction) -> BotRe
         .guild_id
         .ok_or(BotError::Other("Guild id not found"))?;
 
-    let timetable = {
+    let (days, events, tz) = {
         let map = ctx.data.read().await;
         let client = map.get::<CorsClient>().unwrap();
-        client.get_timetable(guild_id.0).await?
+        let tz = class_timezone(&client.get_class(guild_id.0).await?.timezone);
+        let days = client
+            .get_effective_timetable(guild_id.0, Utc::now().into(), 7)
+            .await?;
+        // the homework that is due this week
+        let week_start = (Utc::now() - chrono::Duration::days(1)).into();
+        let week_end = (Utc::now() + chrono::Duration::days(7)).into();
+        let events = client
+            .get_events(guild_id.0, Some(week_end), Some(week_start))
+            .await?;
+        (days, events, tz)
     };
 
-    let result = if let Some(timetable) = timetable {
-        if timetable.iter().all(|vec| vec.is_empty()) {
-            not_found()
-        } else {
-            show_timetable(timetable)
+    let result = match days {
+        Some(days)
+            if days
+                .iter()
+                .any(|day| !day.lessons.is_empty() || day.holiday.is_some()) =>
+        {
+            show_timetable(days, &events, tz)
         }
-    } else {
-        not_found()
+        _ => not_found(),
     };
 
     Ok(interaction
@@ -52,7 +68,9 @@ fn not_found() -> Embed {
     )
 }
 
-fn show_timetable(timetable: Timetable) -> Embed {
+/// The lessons of the next seven days, with the changes of the timetable applied.
+/// The homework that is due at a lesson is shown next to it, holidays are shown once instead of their days
+fn show_timetable(days: Vec<EffectiveDay>, events: &[Event], tz: Tz) -> Embed {
     const DAY_NAMES: [&str; 7] = [
         "Montag",
         "Dienstag",
@@ -64,21 +82,51 @@ fn show_timetable(timetable: Timetable) -> Embed {
     ];
 
     let title = "Stundenplan".to_string();
-    let fields = timetable
+    let mut previous_holiday = None;
+    let fields = days
         .iter()
-        .zip(DAY_NAMES.iter())
-        .filter(|(day, _)| !day.is_empty())
-        .map(|(day, name)| {
+        .filter(|day| !day.lessons.is_empty() || day.holiday.is_some())
+        .filter(|day| {
+            let holiday = day.holiday.as_ref().map(|holiday| holiday.event);
+            let repeated = holiday.is_some() && holiday == previous_holiday;
+            previous_holiday = holiday;
+            !repeated
+        })
+        .map(|day| {
+            if let Some(holiday) = &day.holiday {
+                return ("Ferien".to_string(), holiday_text(holiday), false);
+            }
+            let date = day.date.to_naive().date();
+            let name = DAY_NAMES[date.weekday().num_days_from_monday() as usize];
+            let name = match day.week {
+                Some(week) => format!("{} ({})", name, cycle_week_name(week)),
+                None => name.to_string(),
+            };
             (
-                name.to_string(),
-                day.iter()
-                    .map(|lesson| {
-                        format!(
-                            "`{}-{}` {}",
+                name,
+                day.lessons
+                    .iter()
+                    .map(|effective| {
+                        let lesson = &effective.lesson;
+                        let homework =
+                            format_homework(&due_homework(events, &lesson.subject, date, tz));
+                        let room = if lesson.room.is_empty() {
+                            String::new()
+                        } else {
+                            format!(" ({})", lesson.room)
+                        };
+                        let line = format!(
+                            "`{}-{}` {}{}",
                             format_day_time(lesson.start),
                             format_day_time(lesson.end),
-                            lesson.subject
-                        )
+                            lesson.subject,
+                            room,
+                        );
+                        if effective.cancelled {
+                            format!("~~{}~~ fällt aus{}", line, homework)
+                        } else {
+                            format!("{}{}", line, homework)
+                        }
                     })
                     .collect::<Vec<_>>()
                     .join("\n"),
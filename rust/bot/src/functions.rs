@@ -2,49 +2,101 @@
 //! This makes parts of this bot easily testable
 
 use chrono::format::{DelayedFormat, StrftimeItems};
-use chrono::{DateTime, Datelike, FixedOffset, NaiveDateTime, TimeZone, Utc, Weekday};
-use dto::Timetable;
+use chrono::{DateTime, Datelike, NaiveDate, Timelike, Utc, Weekday};
+use chrono_tz::Tz;
+use dto::{EffectiveHoliday, EffectiveLesson, Event, Timestamp};
 
-pub fn from_utc_timestamp(ms: i64) -> DateTime<Utc> {
-    Utc.from_utc_datetime(&NaiveDateTime::from_timestamp(ms / 1000, 0))
+pub fn from_utc_timestamp(time: Timestamp) -> DateTime<Utc> {
+    time.to_utc()
 }
 
-pub fn from_utc_to_cest(utc: DateTime<Utc>) -> DateTime<FixedOffset> {
-    chrono::FixedOffset::east(2 * 3600).from_utc_datetime(&utc.naive_utc())
+/// The timezone of the class, classes with an unknown timezone use the default one
+pub fn class_timezone(name: &str) -> Tz {
+    name.parse().unwrap_or(chrono_tz::Europe::Zurich)
 }
 
-pub fn format_date(ms: i64) -> DelayedFormat<StrftimeItems<'static>> {
-    from_utc_to_cest(from_utc_timestamp(ms)).format("%d.%m")
+/// The local time in the timezone of the class, daylight saving time included
+pub fn from_utc_to_local(utc: DateTime<Utc>, tz: Tz) -> DateTime<Tz> {
+    utc.with_timezone(&tz)
+}
+
+pub fn format_date(time: Timestamp, tz: Tz) -> DelayedFormat<StrftimeItems<'static>> {
+    from_utc_to_local(from_utc_timestamp(time), tz).format("%d.%m")
 }
 
 pub fn format_day_time(ms: i64) -> DelayedFormat<StrftimeItems<'static>> {
-    NaiveDateTime::from_timestamp(ms / 1000, 0).format("%H:%M")
+    Timestamp(ms).to_naive().format("%H:%M")
 }
 
-pub fn absolute_time_as_weekday(now: chrono::DateTime<Utc>) -> (i64, Weekday) {
-    let day_0 = now.date().and_hms(0, 0, 0);
-    let diff = (now - day_0).num_milliseconds();
-    let weekday = now.weekday();
+/// The local time of the day in milliseconds and the local weekday, lessons are in local time
+pub fn absolute_time_as_weekday(now: chrono::DateTime<Utc>, tz: Tz) -> (i64, Weekday) {
+    let local = from_utc_to_local(now, tz).naive_local();
+    let diff =
+        local.num_seconds_from_midnight() as i64 * 1000 + local.nanosecond() as i64 / 1_000_000;
+    let weekday = local.weekday();
 
     (diff, weekday)
 }
 
+/// The current and the next lesson of the day, cancelled lessons are skipped
 pub fn wie_lange_noch(
-    table: &Timetable,
+    day: &[EffectiveLesson],
     time: i64,
-    day_of_week: Weekday,
 ) -> (Option<&dto::Lesson>, Option<&dto::Lesson>) {
-    let day_table = &table[day_of_week as usize]; // Will never panic
-    let cur_lesson = day_table
+    let mut day_table = day
         .iter()
+        .filter(|lesson| !lesson.cancelled)
+        .map(|lesson| &lesson.lesson);
+    let cur_lesson = day_table
+        .clone()
         .find(|lesson| lesson.start < time && lesson.end > time);
 
-    let next = day_table.iter().find(|lesson| {
+    let next = day_table.find(|lesson| {
         (cur_lesson.map(|ls| ls.start) < Some(lesson.start)) || (lesson.start > time)
     });
     (cur_lesson, next)
 }
 
+/// The name of the holidays and their last day, eg. `Herbstferien bis 17.10.2021`
+pub fn holiday_text(holiday: &EffectiveHoliday) -> String {
+    format!(
+        "{} bis {}",
+        holiday.name,
+        holiday.until.to_naive().format("%d.%m.%Y")
+    )
+}
+
+/// The name of a week of the timetable cycle, A for the first one
+pub fn cycle_week_name(week: u8) -> char {
+    (b'A' + week) as char
+}
+
+/// The homework of the subject that is due on the local `date`
+pub fn due_homework<'a>(
+    events: &'a [Event],
+    subject: &str,
+    date: NaiveDate,
+    tz: Tz,
+) -> Vec<&'a Event> {
+    events
+        .iter()
+        .filter(|event| event.r#type == dto::EventType::HOMEWORK)
+        .filter(|event| event.subject.as_deref() == Some(subject))
+        .filter(|event| {
+            from_utc_to_local(from_utc_timestamp(event.start), tz)
+                .naive_local()
+                .date()
+                == date
+        })
+        .collect()
+}
+
+/// Finds an event type by its name, ignoring the case
+pub fn find_event_type<'a>(types: &'a [dto::EventType], name: &str) -> Option<&'a dto::EventType> {
+    let name = name.trim().to_lowercase();
+    types.iter().find(|typ| typ.name.to_lowercase() == name)
+}
+
 pub fn limit_length(string: &str, len: usize) -> String {
     let mut result = string
         .char_indices()
@@ -60,13 +112,17 @@ pub fn limit_length(string: &str, len: usize) -> String {
 #[cfg(test)]
 mod test {
     use chrono::*;
-    use dto::{Lesson, Timetable};
+    use dto::{EffectiveLesson, Lesson};
 
     fn monday_10am() -> DateTime<Utc> {
         Utc.datetime_from_str("2021-07-26 10:00:00", "%Y-%m-%d %H:%M:%S")
             .unwrap()
     }
 
+    fn utc(time: &str) -> DateTime<Utc> {
+        Utc.from_utc_datetime(&NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").unwrap())
+    }
+
     fn monday_1s() -> DateTime<Utc> {
         Utc.datetime_from_str("2021-07-26 00:00:01", "%Y-%m-%d %H:%M:%S")
             .unwrap()
@@ -76,46 +132,101 @@ mod test {
     fn weekday_monday() {
         let day = monday_10am();
 
-        let (timestamp, weekday) = super::absolute_time_as_weekday(day);
+        let (timestamp, weekday) = super::absolute_time_as_weekday(day, chrono_tz::UTC);
         assert_eq!(weekday, Weekday::Mon);
         let ten_am = 10 * 60 * 60 * 1000;
         assert_eq!(timestamp, ten_am);
     }
 
+    #[test]
+    fn weekday_local_time() {
+        let zurich = chrono_tz::Europe::Zurich;
+        let hour = 60 * 60 * 1000;
+
+        // 08:00 CEST in summer, 08:00 CET in winter
+        let summer = utc("2021-07-26 06:00:00");
+        assert_eq!(
+            super::absolute_time_as_weekday(summer, zurich),
+            (8 * hour, Weekday::Mon)
+        );
+        let winter = utc("2021-11-29 07:00:00");
+        assert_eq!(
+            super::absolute_time_as_weekday(winter, zurich),
+            (8 * hour, Weekday::Mon)
+        );
+
+        // sunday 23:30 UTC is already monday in zurich
+        let sunday = utc("2021-11-28 23:30:00");
+        assert_eq!(
+            super::absolute_time_as_weekday(sunday, zurich),
+            (30 * 60 * 1000, Weekday::Mon)
+        );
+    }
+
+    #[test]
+    fn local_dates() {
+        let zurich = super::class_timezone("Europe/Zurich");
+        let summer = dto::Timestamp(1627257600000); // 2021-07-26 00:00 UTC
+        let winter = dto::Timestamp(1638144000000); // 2021-11-29 00:00 UTC
+
+        assert_eq!(super::format_date(summer, zurich).to_string(), "26.07");
+        assert_eq!(
+            crate::commands::format_datetime(summer, zurich).to_string(),
+            "26.07.2021 02:00 CEST"
+        );
+        assert_eq!(
+            crate::commands::format_datetime(winter, zurich).to_string(),
+            "29.11.2021 01:00 CET"
+        );
+        assert_eq!(
+            crate::commands::format_datetime(winter, super::class_timezone("Asia/Tokyo"))
+                .to_string(),
+            "29.11.2021 09:00 JST"
+        );
+        // unknown timezones fall back to the default one
+        assert_eq!(super::class_timezone("Mars/Olympus"), zurich);
+    }
+
+    fn lesson(subject: &str, start: i64, end: i64) -> Lesson {
+        Lesson {
+            id: None,
+            subject: subject.to_string(),
+            subject_id: None,
+            description: "none".to_string(),
+            start,
+            end,
+            room: String::new(),
+            teacher: String::new(),
+            week: None,
+        }
+    }
+
+    fn effective(lesson: &Lesson, cancelled: bool) -> EffectiveLesson {
+        EffectiveLesson {
+            lesson: lesson.clone(),
+            cancelled,
+            exception: None,
+        }
+    }
+
     #[test]
     fn wielangenoch_no_lesson() {
-        let timetable: Timetable = [vec![], vec![], vec![], vec![], vec![], vec![], vec![]];
-        let (timestamp, weekday) = super::absolute_time_as_weekday(monday_10am());
+        let (timestamp, _) = super::absolute_time_as_weekday(monday_10am(), chrono_tz::UTC);
 
-        let (lesson, _) = super::wie_lange_noch(&timetable, timestamp, weekday);
+        let (lesson, _) = super::wie_lange_noch(&[], timestamp);
         assert_eq!(None, lesson);
     }
 
     #[test]
     fn wielangenoch_in_lesson() {
-        let target_lesson = Lesson {
-            subject: "schaffen".to_string(),
-            description: "none".to_string(),
-            start: 500,
-            end: 1500,
-        };
-        let next_lesson = Lesson {
-            subject: "schaffen".to_string(),
-            description: "none".to_string(),
-            start: 1600,
-            end: 2000,
-        };
-        let timetable: Timetable = [
-            vec![target_lesson.clone(), next_lesson.clone()],
-            vec![],
-            vec![],
-            vec![],
-            vec![],
-            vec![],
-            vec![],
+        let target_lesson = lesson("schaffen", 500, 1500);
+        let next_lesson = lesson("schaffen", 1600, 2000);
+        let day = vec![
+            effective(&target_lesson, false),
+            effective(&next_lesson, false),
         ];
-        let (timestamp, weekday) = super::absolute_time_as_weekday(monday_1s());
-        let (lesson, next) = super::wie_lange_noch(&timetable, timestamp, weekday);
+        let (timestamp, weekday) = super::absolute_time_as_weekday(monday_1s(), chrono_tz::UTC);
+        let (lesson, next) = super::wie_lange_noch(&day, timestamp);
 
         assert_eq!(timestamp, 1000);
         assert_eq!(weekday, Weekday::Mon);
@@ -125,27 +236,126 @@ mod test {
 
     #[test]
     fn wielangenoch_next_lesson() {
-        let target_lesson = Lesson {
-            subject: "schaffen".to_string(),
-            description: "none".to_string(),
-            start: 1500,
-            end: 2000,
-        };
-        let timetable: Timetable = [
-            vec![target_lesson.clone()],
-            vec![],
-            vec![],
-            vec![],
-            vec![],
-            vec![],
-            vec![],
-        ];
-        let (timestamp, weekday) = super::absolute_time_as_weekday(monday_1s());
-        let (lesson, next) = super::wie_lange_noch(&timetable, timestamp, weekday);
+        let target_lesson = lesson("schaffen", 1500, 2000);
+        let day = vec![effective(&target_lesson, false)];
+        let (timestamp, weekday) = super::absolute_time_as_weekday(monday_1s(), chrono_tz::UTC);
+        let (lesson, next) = super::wie_lange_noch(&day, timestamp);
 
         assert_eq!(timestamp, 1000);
         assert_eq!(weekday, Weekday::Mon);
         assert_eq!(None, lesson);
         assert_eq!(Some(&target_lesson), next);
     }
+
+    #[test]
+    fn wielangenoch_cancelled_lesson() {
+        let cancelled = lesson("cancelled", 500, 1500);
+        let next_lesson = lesson("next", 1600, 2000);
+        let day = vec![
+            effective(&cancelled, true),
+            effective(&lesson("later cancelled", 1550, 1600), true),
+            effective(&next_lesson, false),
+        ];
+        let (timestamp, _) = super::absolute_time_as_weekday(monday_1s(), chrono_tz::UTC);
+        let (lesson, next) = super::wie_lange_noch(&day, timestamp);
+
+        assert_eq!(None, lesson);
+        assert_eq!(Some(&next_lesson), next);
+    }
+
+    #[test]
+    fn cycle_week_names() {
+        assert_eq!(super::cycle_week_name(0), 'A');
+        assert_eq!(super::cycle_week_name(1), 'B');
+    }
+
+    #[test]
+    fn holiday_end() {
+        let holiday = dto::EffectiveHoliday {
+            event: uuid::Uuid::nil(),
+            name: "Herbstferien".to_string(),
+            until: utc("2021-10-17 00:00:00").into(),
+        };
+        assert_eq!(super::holiday_text(&holiday), "Herbstferien bis 17.10.2021");
+    }
+
+    #[test]
+    fn homework_of_lesson() {
+        let homework = |name: &str, subject: Option<&str>, start: &str| dto::Event {
+            id: uuid::Uuid::new_v4(),
+            r#type: dto::EventType::HOMEWORK,
+            name: name.to_string(),
+            start: utc(start).into(),
+            end: None,
+            description: "".to_string(),
+            subject: subject.map(str::to_string),
+            subject_id: None,
+            notifications: vec![],
+            recurrence: None,
+            occurrence: None,
+            done: false,
+            completions: None,
+            warnings: vec![],
+        };
+        let events = vec![
+            homework("Aufgaben S. 12", Some("Mathe"), "2021-07-26 06:00:00"),
+            homework("Aufsatz", Some("Deutsch"), "2021-07-26 06:00:00"),
+            homework("Aufgaben S. 14", Some("Mathe"), "2021-07-27 06:00:00"),
+            homework("Vokabeln", None, "2021-07-26 06:00:00"),
+            // 01:00 CEST on tuesday
+            homework("Übungsblatt", Some("Mathe"), "2021-07-26 23:00:00"),
+            dto::Event {
+                r#type: dto::EventType::EXAM,
+                ..homework("Prüfung", Some("Mathe"), "2021-07-26 06:00:00")
+            },
+        ];
+
+        let due = super::due_homework(
+            &events,
+            "Mathe",
+            NaiveDate::from_ymd_opt(2021, 7, 26).unwrap(),
+            chrono_tz::Europe::Zurich,
+        );
+        assert_eq!(
+            due.iter().map(|event| &event.name).collect::<Vec<_>>(),
+            vec!["Aufgaben S. 12"]
+        );
+        let due = super::due_homework(
+            &events,
+            "Mathe",
+            NaiveDate::from_ymd_opt(2021, 7, 27).unwrap(),
+            chrono_tz::Europe::Zurich,
+        );
+        assert_eq!(due.len(), 2);
+    }
+
+    #[test]
+    fn event_type_by_name() {
+        let types = vec![
+            dto::EventType {
+                id: dto::EventType::EXAM,
+                name: "exam".to_string(),
+                colour: "#e53935".to_string(),
+                reminders: vec![],
+                built_in: true,
+            },
+            dto::EventType {
+                id: 5,
+                name: "Vortrag".to_string(),
+                colour: "#ff8800".to_string(),
+                reminders: vec![],
+                built_in: false,
+            },
+        ];
+
+        assert_eq!(
+            super::find_event_type(&types, "vortrag").map(|t| t.id),
+            Some(5)
+        );
+        assert_eq!(
+            super::find_event_type(&types, " Exam").map(|t| t.id),
+            Some(dto::EventType::EXAM)
+        );
+        assert_eq!(super::find_event_type(&types, "excursion"), None);
+    }
 }
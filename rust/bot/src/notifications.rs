@@ -1,8 +1,8 @@
 use crate::commands::format_datetime;
 use crate::error::{BotError, BotResult};
-use crate::functions::limit_length;
+use crate::functions::{class_timezone, limit_length};
 use crate::requests::CorsClient;
-use dto::Notification;
+use dto::{Notification, Timestamp};
 use serenity::builder::CreateEmbed;
 use serenity::model::id::RoleId;
 use serenity::model::prelude::{ChannelId, Mentionable};
@@ -28,20 +28,20 @@ pub async fn start_timer(arc: Arc<CacheAndHttp>, client: Arc<CorsClient>) {
 
 async fn send_notifications(http: &CacheAndHttp, client: &CorsClient) -> BotResult<()> {
     let last = match std::fs::read_to_string(LAST_NOTIFICATION_PATH) {
-        Ok(str) => match str.parse::<i64>() {
-            Ok(n) => chrono::NaiveDateTime::from_timestamp(n / 1000, 0),
+        Ok(str) => match str.trim().parse::<Timestamp>() {
+            Ok(last) => last,
             Err(_) => {
                 warn!("Invalid notification file");
-                chrono::Utc::now().naive_utc()
+                chrono::Utc::now().into()
             }
         },
         Err(_) => {
             warn!("Could not find notification file");
-            chrono::Utc::now().naive_utc()
+            chrono::Utc::now().into()
         }
     };
 
-    let notifications = client.get_notifications(last.timestamp_millis()).await?;
+    let notifications = client.get_notifications(last).await?;
 
     std::fs::write(LAST_NOTIFICATION_PATH, notifications.time.to_string())
         .map_err(|_| BotError::Other("could not write notification file"))?;
@@ -116,7 +116,10 @@ fn notification_embed<'a>(embed: &'a mut CreateEmbed, notif: &Notification) -> &
     embed
         .title(format!("Benachrichtigung für {}", notif.event.name))
         .field(
-            format!("Start: {}", format_datetime(notif.event.start)),
+            format!(
+                "Start: {}",
+                format_datetime(notif.event.start, class_timezone(&notif.timezone))
+            ),
             &description,
             false,
         )
@@ -5,7 +5,10 @@ use serenity::model::id::UserId;
 use tracing::debug;
 use uuid::Uuid;
 
-use dto::{Class, Event, GetEventQueryParams, NotificationRes, Timetable};
+use dto::{
+    Class, EffectiveDay, EffectiveTimetableQueryParams, Event, EventType, GetEventQueryParams,
+    NotificationRes, Page, SortOrder, Timestamp,
+};
 
 use crate::error::BotResult;
 
@@ -33,7 +36,7 @@ impl CorsClient {
         }
     }
 
-    pub async fn get_notifications(&self, old_timestamp: i64) -> BotResult<NotificationRes> {
+    pub async fn get_notifications(&self, old_timestamp: Timestamp) -> BotResult<NotificationRes> {
         debug!(after = %old_timestamp, "Getting notifications");
 
         let res = self
@@ -53,13 +56,17 @@ impl CorsClient {
     pub async fn get_events(
         &self,
         guild_id: u64,
-        before: Option<i64>,
-        after: Option<i64>,
+        before: Option<Timestamp>,
+        after: Option<Timestamp>,
     ) -> BotResult<Vec<Event>> {
         let class_id = self.get_class(guild_id).await?.id;
 
-        let params = serde_url_params::to_string(&GetEventQueryParams { before, after })
-            .expect("Query params be valid");
+        let params = serde_url_params::to_string(&GetEventQueryParams {
+            before,
+            after,
+            ..Default::default()
+        })
+        .expect("Query params be valid");
 
         let res = self
             .client
@@ -77,20 +84,109 @@ impl CorsClient {
         Ok(events)
     }
 
-    pub async fn get_timetable(&self, guild_id: u64) -> BotResult<Option<Timetable>> {
+    /// The first `limit` events after `after`, sorted by their start
+    pub async fn get_next_events(
+        &self,
+        guild_id: u64,
+        after: Timestamp,
+        limit: i64,
+    ) -> BotResult<Vec<Event>> {
+        let class_id = self.get_class(guild_id).await?.id;
+
+        let params = serde_url_params::to_string(&GetEventQueryParams {
+            after: Some(after),
+            limit: Some(limit),
+            sort: Some(SortOrder::Asc),
+            ..Default::default()
+        })
+        .expect("Query params be valid");
+
+        let res = self
+            .client
+            .get(format!(
+                "{}/classes/{}/events?{}",
+                *BASE_URL, class_id, params
+            ))
+            .send()
+            .await?;
+
+        debug!(status = %res.status(), "Get next events status");
+
+        let page: Page<Event> = res.json().await?;
+        Ok(page.items)
+    }
+
+    /// Full-text search over the events of the class, best matches first
+    pub async fn search_events(&self, guild_id: u64, query: &str) -> BotResult<Vec<Event>> {
         let class_id = self.get_class(guild_id).await?.id;
 
+        let params = serde_url_params::to_string(&GetEventQueryParams {
+            q: Some(query.to_string()),
+            ..Default::default()
+        })
+        .expect("Query params be valid");
+
         let res = self
             .client
-            .get(format!("{}/classes/{}/timetable", *BASE_URL, class_id))
+            .get(format!(
+                "{}/classes/{}/events?{}",
+                *BASE_URL, class_id, params
+            ))
+            .send()
+            .await?;
+
+        debug!(status = %res.status(), "Search events status");
+
+        let events = res.json().await?;
+        Ok(events)
+    }
+
+    /// The built-in event types and the ones of the class
+    pub async fn get_event_types(&self, guild_id: u64) -> BotResult<Vec<EventType>> {
+        let class_id = self.get_class(guild_id).await?.id;
+
+        let res = self
+            .client
+            .get(format!("{}/classes/{}/event-types", *BASE_URL, class_id))
+            .send()
+            .await?;
+
+        debug!(status = %res.status(), "Get event types status");
+
+        let types = res.json().await?;
+        Ok(types)
+    }
+
+    /// The lessons of `days` days starting at the local date of `date`, with the timetable
+    /// exceptions applied
+    pub async fn get_effective_timetable(
+        &self,
+        guild_id: u64,
+        date: Timestamp,
+        days: u8,
+    ) -> BotResult<Option<Vec<EffectiveDay>>> {
+        let class_id = self.get_class(guild_id).await?.id;
+
+        let params = serde_url_params::to_string(&EffectiveTimetableQueryParams {
+            date: Some(date),
+            days: Some(days),
+        })
+        .expect("Query params be valid");
+
+        let res = self
+            .client
+            .get(format!(
+                "{}/classes/{}/timetable/effective?{}",
+                *BASE_URL, class_id, params
+            ))
             .send()
             .await?;
 
         if res.status() == 404 {
             Ok(None)
         } else {
-            let timetable = res.json().await?;
-            Ok(Some(timetable))
+            let days = res.json().await?;
+            Ok(Some(days))
         }
     }
 
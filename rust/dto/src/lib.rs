@@ -1,59 +1,389 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
 
-/// A UTC Unix timestamp in seconds
-type Timestamp = i64;
+/// A UTC Unix timestamp in milliseconds
+///
+/// All conversions from and to chrono go through this type, they keep the milliseconds
+///
+/// ```
+/// # use dto::Timestamp;
+/// use chrono::NaiveDateTime;
+/// let time = NaiveDateTime::parse_from_str("2021-08-26 07:30:15.250", "%Y-%m-%d %H:%M:%S%.3f").unwrap();
+/// let timestamp = Timestamp::from(time);
+/// assert_eq!(timestamp, Timestamp(1629963015250));
+/// assert_eq!(timestamp.to_naive(), time);
+/// assert_eq!(serde_json::to_string(&timestamp).unwrap(), "1629963015250");
+/// ```
+#[derive(
+    Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Timestamp(pub i64);
+
+impl Timestamp {
+    pub fn millis(self) -> i64 {
+        self.0
+    }
+
+    /// The time in naive UTC. Timestamps outside of the range of chrono are clamped to it
+    pub fn to_naive(self) -> NaiveDateTime {
+        NaiveDateTime::from_timestamp_millis(self.0).unwrap_or(if self.0 < 0 {
+            NaiveDateTime::MIN
+        } else {
+            NaiveDateTime::MAX
+        })
+    }
+
+    pub fn to_utc(self) -> DateTime<Utc> {
+        Utc.from_utc_datetime(&self.to_naive())
+    }
+}
+
+impl From<NaiveDateTime> for Timestamp {
+    fn from(time: NaiveDateTime) -> Self {
+        Timestamp(time.timestamp_millis())
+    }
+}
+
+impl From<DateTime<Utc>> for Timestamp {
+    fn from(time: DateTime<Utc>) -> Self {
+        Timestamp(time.timestamp_millis())
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Timestamp)
+    }
+}
 
-/// A UTC seconds after 00:00
+/// Milliseconds after 00:00
 type DayTimestamp = i64;
 
 /// A Unique User Id
 type Uuid = uuid::Uuid;
 
+/// The id of an event type, see [`EventType`]
+type EventTypeId = i32;
+
 /// A discord Snowflake id
 type Snowflake = String;
 
 /// A class event
+///
+/// ```
+/// # use dto::{Event, EventType};
+/// let json = r#"{"type":"exam","name":"Algebra","start":0,"description":""}"#;
+/// let event: Event = serde_json::from_str(json).unwrap();
+/// assert_eq!(event.r#type, EventType::EXAM);
+/// let json = serde_json::to_string(&event).unwrap();
+/// assert!(json.contains(r#""type":2"#));
+/// assert!(serde_json::from_str::<Event>(&json).is_ok());
+///
+/// let json = r#"{"type":1,"name":"Lesen","start":0,"description":"","notification":1000}"#;
+/// let event: Event = serde_json::from_str(json).unwrap();
+/// assert_eq!(event.notifications, vec![dto::Timestamp(1000)]);
+/// let json = r#"{"type":1,"name":"Lesen","start":0,"description":"","notification":null}"#;
+/// assert!(serde_json::from_str::<Event>(json).unwrap().notifications.is_empty());
+/// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Event {
     #[serde(default)]
     pub id: Uuid,
-    pub r#type: EventType,
+    #[serde(deserialize_with = "deserialize_event_type")]
+    pub r#type: EventTypeId,
     pub name: String,
     pub start: Timestamp,
     #[serde(default)]
     pub end: Option<Timestamp>,
     pub description: String,
+    /// The name of the subject, only used to find the subject in requests without a `subject_id`
+    #[serde(default)]
+    pub subject: Option<String>,
+    /// The subject of the class
+    #[serde(default)]
+    pub subject_id: Option<Uuid>,
+    /// The times when a reminder for the event is sent.
+    /// Older clients send a single, possibly null, `notification` instead
+    #[serde(
+        default,
+        alias = "notification",
+        deserialize_with = "deserialize_notifications"
+    )]
+    pub notifications: Vec<Timestamp>,
+    /// Set if the event repeats, every occurrence of the series carries the rule
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
+    /// The original start of this occurrence, if the event is part of a recurring series
+    /// Used to edit or delete a single occurrence
+    #[serde(default)]
+    pub occurrence: Option<Timestamp>,
+    /// Whether the requesting member has marked the event as done
     #[serde(default)]
-    pub notification: Option<Timestamp>,
+    pub done: bool,
+    /// The amount of members that have marked the event as done, only set for admins
+    #[serde(default)]
+    pub completions: Option<i64>,
+    /// The weeks that have too many exams with this event, only set when creating or editing it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<WorkloadWarning>,
 }
 
-/// The type of a class event
+/// The id of the type, or the name of a built-in type for older clients
+fn deserialize_event_type<'de, D>(deserializer: D) -> Result<EventTypeId, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum IdOrName {
+        Id(EventTypeId),
+        Name(String),
+    }
+
+    match IdOrName::deserialize(deserializer)? {
+        IdOrName::Id(id) => Ok(id),
+        IdOrName::Name(name) => EventType::built_in_id(&name)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown event type `{}`", name))),
+    }
+}
+
+/// A list of notifications, or a single one that may be null
+fn deserialize_notifications<'de, D>(deserializer: D) -> Result<Vec<Timestamp>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ListOrSingle {
+        List(Vec<Timestamp>),
+        Single(Option<Timestamp>),
+    }
+
+    Ok(match ListOrSingle::deserialize(deserializer)? {
+        ListOrSingle::List(notifications) => notifications,
+        ListOrSingle::Single(notification) => notification.into_iter().collect(),
+    })
+}
+
+/// An event together with its class, returned when searching the events of all classes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassEvent {
+    pub class: Uuid,
+    pub event: Event,
+}
+
+/// A change of an event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventRevision {
+    pub id: i32,
+    pub event: Uuid,
+    /// The user that made the change, `None` if the user has been deleted
+    pub actor: Option<Uuid>,
+    pub kind: RevisionKind,
+    pub time: Timestamp,
+    /// The changed fields of the event
+    pub changes: BTreeMap<String, FieldChange>,
+    /// The event after the change, `None` if it has been deleted
+    pub snapshot: Option<Event>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RevisionKind {
+    Create,
+    Update,
+    Delete,
+    Revert,
+    Restore,
+}
+
+impl RevisionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RevisionKind::Create => "create",
+            RevisionKind::Update => "update",
+            RevisionKind::Delete => "delete",
+            RevisionKind::Revert => "revert",
+            RevisionKind::Restore => "restore",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "create" => RevisionKind::Create,
+            "update" => RevisionKind::Update,
+            "delete" => RevisionKind::Delete,
+            "revert" => RevisionKind::Revert,
+            "restore" => RevisionKind::Restore,
+            _ => return None,
+        })
+    }
+}
+
+/// The value of a field of an event before and after a change, `null` if the event didn't exist
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub old: serde_json::Value,
+    pub new: serde_json::Value,
+}
+
+/// The recurrence rule of a class event, modeled after the iCalendar `RRULE`
+///
+/// At most one of `until` and `count` should be set, if none is set, the event repeats forever
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    /// Repeat every `interval` days/weeks/months/years
+    #[serde(default = "Recurrence::default_interval")]
+    pub interval: i32,
+    /// The last possible start of an occurrence (inclusive)
+    #[serde(default)]
+    pub until: Option<Timestamp>,
+    /// The amount of occurrences, including the excluded ones
+    #[serde(default)]
+    pub count: Option<i32>,
+    /// The starts of occurrences that have been excluded from the series
+    #[serde(default)]
+    pub exceptions: Vec<Timestamp>,
+}
+
+impl Recurrence {
+    fn default_interval() -> i32 {
+        1
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.interval > 0 && self.count.is_none_or(|count| count > 0)
+    }
+}
+
+/// How often a recurring event repeats
 ///
 /// ```
-/// use dto::EventType;
+/// use dto::Frequency;
 ///
-/// let homework = EventType::Homework;
-/// assert_eq!("homework", homework.as_str())
+/// let weekly = Frequency::Weekly;
+/// assert_eq!(Some(weekly), Frequency::from_name(weekly.as_str()))
 /// ```
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub enum EventType {
-    Homework = 1,
-    Exam = 2,
-    Holidays = 3,
-    Other = 4,
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
 }
 
-impl EventType {
+impl Frequency {
     pub fn as_str(&self) -> &'static str {
         match self {
-            EventType::Homework => "homework",
-            EventType::Exam => "exam",
-            EventType::Holidays => "holidays",
-            EventType::Other => "other",
+            Frequency::Daily => "daily",
+            Frequency::Weekly => "weekly",
+            Frequency::Monthly => "monthly",
+            Frequency::Yearly => "yearly",
         }
     }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "daily" => Frequency::Daily,
+            "weekly" => Frequency::Weekly,
+            "monthly" => Frequency::Monthly,
+            "yearly" => Frequency::Yearly,
+            _ => return None,
+        })
+    }
+}
+
+/// The type of a class event
+///
+/// The built-in types exist in every class and can't be changed, classes can add their own types
+///
+/// ```
+/// use dto::EventType;
+///
+/// let presentation = EventType {
+///     id: 0,
+///     name: "presentation".to_string(),
+///     colour: "#ff8800".to_string(),
+///     reminders: vec![24 * 60 * 60 * 1000],
+///     built_in: false,
+/// };
+/// assert!(presentation.is_valid());
+///
+/// let late = EventType {
+///     reminders: vec![-60 * 60 * 1000],
+///     ..presentation
+/// };
+/// assert!(!late.is_valid());
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventType {
+    #[serde(default)]
+    pub id: EventTypeId,
+    pub name: String,
+    /// A hex colour, eg. `#ff8800`
+    pub colour: String,
+    /// The notifications a new event of this type gets by default, in milliseconds before its start
+    #[serde(default)]
+    pub reminders: Vec<i64>,
+    #[serde(default)]
+    pub built_in: bool,
+}
+
+impl EventType {
+    pub const HOMEWORK: EventTypeId = 1;
+    pub const EXAM: EventTypeId = 2;
+    pub const HOLIDAYS: EventTypeId = 3;
+    pub const OTHER: EventTypeId = 4;
+
+    /// Reminders are sent at most a year before the event
+    pub const MAX_REMINDER: i64 = 365 * 24 * 60 * 60 * 1000;
+
+    /// The id of a built-in type by the name it was sent as before classes had their own types
+    ///
+    /// ```
+    /// # use dto::EventType;
+    /// assert_eq!(EventType::built_in_id("exam"), Some(EventType::EXAM));
+    /// assert_eq!(EventType::built_in_id("Prüfung"), None);
+    /// ```
+    pub fn built_in_id(name: &str) -> Option<EventTypeId> {
+        match name {
+            "homework" => Some(Self::HOMEWORK),
+            "exam" => Some(Self::EXAM),
+            "holidays" => Some(Self::HOLIDAYS),
+            "other" => Some(Self::OTHER),
+            _ => None,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        let name_length = self.name.trim().chars().count();
+        let colour = self.colour.strip_prefix('#').unwrap_or("");
+
+        (1..=50).contains(&name_length)
+            && colour.len() == 6
+            && colour.chars().all(|char| char.is_ascii_hexdigit())
+            && self
+                .reminders
+                .iter()
+                .all(|reminder| (0..=Self::MAX_REMINDER).contains(reminder))
+    }
 }
 
 /// A Class
@@ -69,6 +399,57 @@ pub struct Class {
     pub description: String,
     #[serde(default)]
     pub discord_id: Option<String>,
+    /// The maximum amount of exams in a week
+    #[serde(default = "Class::default_exam_limit")]
+    pub exam_limit: i32,
+    /// Whether exams that exceed the limit are rejected instead of only warned about
+    #[serde(default)]
+    pub strict_exam_limit: bool,
+    /// The IANA name of the timezone of the class, eg. `Europe/Zurich`
+    #[serde(default = "Class::default_timezone")]
+    pub timezone: String,
+}
+
+impl Class {
+    pub fn default_exam_limit() -> i32 {
+        3
+    }
+
+    pub fn default_timezone() -> String {
+        "Europe/Zurich".to_string()
+    }
+}
+
+/// A week that has more exams than the class allows
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct WorkloadWarning {
+    /// The start of the week, monday 00:00
+    pub week: Timestamp,
+    pub exams: usize,
+    pub limit: i32,
+}
+
+/// The amount of events of each type in a week
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct WeekWorkload {
+    /// The start of the week, monday 00:00
+    pub week: Timestamp,
+    pub types: Vec<EventTypeCount>,
+    /// Whether the week has more exams than the class allows
+    pub overloaded: bool,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct EventTypeCount {
+    pub r#type: EventTypeId,
+    pub count: usize,
+}
+
+/// Query of GET /classes/{uuid}/workload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkloadQueryParams {
+    pub before: Option<Timestamp>,
+    pub after: Option<Timestamp>,
 }
 
 /// A User
@@ -143,12 +524,401 @@ pub type Timetable = [TimeTableDay; 7];
 pub type TimeTableDay = Vec<Lesson>;
 
 /// A lesson in a timetable
+///
+/// ```
+/// # use dto::Lesson;
+/// let json = r#"{"subject_id":"4c9e3d8e-7a7f-4f0b-9a55-0c6f3d1b2e10","description":"","start":0,"end":1}"#;
+/// let lesson: Lesson = serde_json::from_str(json).unwrap();
+/// assert_eq!(lesson.subject, "");
+/// assert!(lesson.subject_id.is_some());
+/// ```
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Lesson {
+    /// Set by the server, lessons without an id are created when the timetable is replaced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    /// The name of the subject, the subject is created if the class doesn't have it yet.
+    /// Ignored in requests with a `subject_id`
+    #[serde(default)]
     pub subject: String,
+    /// The subject of the class, always set by the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject_id: Option<Uuid>,
     pub description: String,
     pub start: DayTimestamp,
     pub end: DayTimestamp,
+    #[serde(default)]
+    pub room: String,
+    #[serde(default)]
+    pub teacher: String,
+    /// The week of the [`TimetableCycle`] the lesson is in, `None` if it is in every week
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub week: Option<u8>,
+}
+
+impl Lesson {
+    /// The length of a day, lessons have to start and end within it
+    pub const DAY: DayTimestamp = 24 * 60 * 60 * 1000;
+
+    pub fn is_in_week(&self, week: u8) -> bool {
+        self.week.is_none() || self.week == Some(week)
+    }
+
+    /// Checks the lesson on its own, [`validate_timetable`] also checks for overlapping lessons
+    pub fn validate(&self) -> Result<(), LessonErrorKind> {
+        if self.subject.trim().is_empty() {
+            Err(LessonErrorKind::MissingSubject)
+        } else if self.start < 0 || self.end > Self::DAY {
+            Err(LessonErrorKind::OutsideOfDay)
+        } else if self.end <= self.start {
+            Err(LessonErrorKind::EndBeforeStart)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Lessons in different weeks of the cycle never overlap
+    ///
+    /// ```
+    /// # use dto::Lesson;
+    /// let lesson = |start, week| Lesson {
+    ///     id: None,
+    ///     subject: "Mathe".to_string(),
+    ///     subject_id: None,
+    ///     description: "".to_string(),
+    ///     start,
+    ///     end: start + 2_700_000,
+    ///     room: "".to_string(),
+    ///     teacher: "".to_string(),
+    ///     week,
+    /// };
+    /// assert!(lesson(0, None).overlaps(&lesson(1_000_000, Some(1))));
+    /// assert!(!lesson(0, Some(0)).overlaps(&lesson(1_000_000, Some(1))));
+    /// assert!(!lesson(0, None).overlaps(&lesson(2_700_000, None)));
+    /// ```
+    pub fn overlaps(&self, other: &Lesson) -> bool {
+        let same_week = self.week.is_none() || other.week.is_none() || self.week == other.week;
+        same_week && self.start < other.end && other.start < self.end
+    }
+}
+
+/// A problem with a lesson of a timetable
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct LessonError {
+    /// The day in the timetable, `0` is monday
+    pub weekday: u8,
+    /// The index of the lesson in its day
+    pub index: usize,
+    pub error: LessonErrorKind,
+    /// The index of the lesson it overlaps with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub with: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LessonErrorKind {
+    /// The end is not after the start
+    EndBeforeStart,
+    /// The lesson starts before 00:00 or ends after 24:00
+    OutsideOfDay,
+    /// The lesson overlaps with an earlier lesson of the day
+    Overlapping,
+    /// The subject is empty
+    MissingSubject,
+}
+
+/// Body of `400 invalid-timetable`
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TimetableErrors {
+    pub error: String,
+    pub lessons: Vec<LessonError>,
+}
+
+/// All problems with the lessons of the timetable, the server rejects timetables that have any.
+/// The lessons of a day don't have to be sorted.
+///
+/// ```
+/// # use dto::{validate_timetable, Lesson, LessonErrorKind, Timetable};
+/// let lesson = |start, end| Lesson {
+///     id: None,
+///     subject: "Mathe".to_string(),
+///     subject_id: None,
+///     description: "".to_string(),
+///     start,
+///     end,
+///     room: "".to_string(),
+///     teacher: "".to_string(),
+///     week: None,
+/// };
+/// let mut timetable = Timetable::default();
+/// timetable[0] = vec![lesson(28_800_000, 31_500_000), lesson(30_000_000, 33_000_000)];
+/// timetable[2] = vec![lesson(36_000_000, 33_000_000)];
+///
+/// let errors = validate_timetable(&timetable);
+/// assert_eq!(errors.len(), 2);
+/// assert_eq!((errors[0].weekday, errors[0].index), (0, 1));
+/// assert_eq!(errors[0].error, LessonErrorKind::Overlapping);
+/// assert_eq!(errors[0].with, Some(0));
+/// assert_eq!((errors[1].weekday, errors[1].error), (2, LessonErrorKind::EndBeforeStart));
+/// ```
+pub fn validate_timetable(timetable: &Timetable) -> Vec<LessonError> {
+    let mut errors = Vec::new();
+    for (weekday, day) in timetable.iter().enumerate() {
+        for (index, lesson) in day.iter().enumerate() {
+            let error = |error, with| LessonError {
+                weekday: weekday as u8,
+                index,
+                error,
+                with,
+            };
+            if let Err(kind) = lesson.validate() {
+                errors.push(error(kind, None));
+                continue;
+            }
+            // the lesson that starts later is the overlapping one
+            let earlier = day.iter().enumerate().find(|&(other_index, other)| {
+                other_index != index
+                    && other.validate().is_ok()
+                    && (other.start, other_index) < (lesson.start, index)
+                    && lesson.overlaps(other)
+            });
+            if let Some((other_index, _)) = earlier {
+                errors.push(error(LessonErrorKind::Overlapping, Some(other_index)));
+            }
+        }
+    }
+    errors
+}
+
+/// A single lesson together with its day in the timetable, `0` is monday
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TimetableLesson {
+    pub weekday: u8,
+    /// The [`TimetableVersion`] of the lesson, the one that is valid today if it is not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<Uuid>,
+    #[serde(flatten)]
+    pub lesson: Lesson,
+}
+
+/// A subject of a class, lessons and events refer to it by its id.
+/// Requests without an id can refer to it by its name instead, ignoring the case.
+/// The teacher and room are used for lessons that don't have their own.
+///
+/// ```
+/// # use dto::Subject;
+/// let subject: Subject = serde_json::from_str(r#"{"name":"Mathematik","short":"M"}"#).unwrap();
+/// assert_eq!(subject.id, None);
+/// assert_eq!(subject.colour, "");
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Subject {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    pub name: String,
+    #[serde(default)]
+    pub short: String,
+    /// A hex colour like `#1e90ff`, or empty
+    #[serde(default)]
+    pub colour: String,
+    #[serde(default)]
+    pub teacher: String,
+    #[serde(default)]
+    pub room: String,
+}
+
+impl Subject {
+    /// Whether the colour is empty or like `#1e90ff`
+    pub fn has_valid_colour(&self) -> bool {
+        self.colour.is_empty()
+            || (self.colour.len() == 7
+                && self.colour.starts_with('#')
+                && self.colour[1..].chars().all(|c| c.is_ascii_hexdigit()))
+    }
+}
+
+/// A class can have several timetables, each one is valid between two dates.
+/// The dates are the timestamps of 00:00 UTC, both are included and `None` is unbounded
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TimetableVersion {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    pub valid_from: Option<Timestamp>,
+    pub valid_until: Option<Timestamp>,
+}
+
+/// The file format of POST /classes/{uuid}/timetable/import
+///
+/// ```
+/// # use dto::TimetableImportFormat;
+/// let format: TimetableImportFormat = serde_json::from_str(r#""untis""#).unwrap();
+/// assert_eq!(format, TimetableImportFormat::Untis);
+/// assert_eq!(TimetableImportFormat::default(), TimetableImportFormat::Csv);
+/// ```
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TimetableImportFormat {
+    /// The columns weekday, start, end, subject, room, description and teacher
+    #[default]
+    Csv,
+    /// The CSV export of a timetable in WebUntis
+    Untis,
+}
+
+/// Query of POST /classes/{uuid}/timetable/import
+/// With `preview`, the import is only checked and nothing is saved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimetableImportQueryParams {
+    #[serde(default)]
+    pub format: TimetableImportFormat,
+    #[serde(default)]
+    pub preview: bool,
+    /// The [`TimetableVersion`] whose lessons are replaced, the one that is valid today if it is not set
+    pub version: Option<Uuid>,
+}
+
+/// Response of POST /classes/{uuid}/timetable/import
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimetableImportRes {
+    pub timetable: Timetable,
+    /// The rows that could not be imported, they are skipped
+    pub errors: Vec<TimetableImportError>,
+    /// The problems with the lessons of `timetable`, only in a preview.
+    /// Without preview, the import fails with `invalid-timetable` instead
+    pub lesson_errors: Vec<LessonError>,
+}
+
+/// A row of an imported timetable that could not be imported
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TimetableImportError {
+    /// The line of the row in the file, starting at 1
+    pub row: usize,
+    pub error: String,
+}
+
+/// A timetable can alternate between several weeks, for example A and B weeks.
+/// Request and response of /classes/{uuid}/timetable/cycle
+///
+/// ```
+/// # use dto::{Timestamp, TimetableCycle};
+/// use chrono::NaiveDate;
+/// let cycle = TimetableCycle {
+///     weeks: 2,
+///     anchor: NaiveDate::from_ymd_opt(2021, 8, 25).unwrap().and_hms_opt(0, 0, 0).unwrap().into(),
+/// };
+/// assert_eq!(cycle.week_of(NaiveDate::from_ymd_opt(2021, 8, 23).unwrap()), 0);
+/// assert_eq!(cycle.week_of(NaiveDate::from_ymd_opt(2021, 9, 3).unwrap()), 1);
+/// assert_eq!(cycle.week_of(NaiveDate::from_ymd_opt(2021, 8, 20).unwrap()), 1);
+/// ```
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TimetableCycle {
+    /// The amount of weeks in the cycle, `1` if every week is the same
+    pub weeks: u8,
+    /// A date in the first week of the cycle, as the timestamp of 00:00 UTC
+    pub anchor: Timestamp,
+}
+
+impl TimetableCycle {
+    pub const MAX_WEEKS: u8 = 4;
+
+    /// The week of the cycle that the date is in. Weeks start on monday
+    pub fn week_of(&self, date: NaiveDate) -> u8 {
+        let monday =
+            |date: NaiveDate| date - Duration::days(date.weekday().num_days_from_monday() as i64);
+        let weeks = (monday(date) - monday(self.anchor.to_naive().date())).num_weeks();
+        weeks.rem_euclid(self.weeks.max(1) as i64) as u8
+    }
+
+    /// The timetable with only the lessons of the week of the cycle that the date is in
+    pub fn timetable_of_week(&self, timetable: &Timetable, date: NaiveDate) -> Timetable {
+        let week = self.week_of(date);
+        let mut week_table = timetable.clone();
+        for day in week_table.iter_mut() {
+            day.retain(|lesson| lesson.is_in_week(week));
+        }
+        week_table
+    }
+}
+
+/// Query of GET /classes/{uuid}/timetable
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimetableQueryParams {
+    /// Only the lessons of the week of the cycle that this date is in
+    pub date: Option<Timestamp>,
+}
+
+/// A change to the timetable on a single date.
+/// Changes a lesson of the timetable, or adds an extra lesson if `lesson` is `None`
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TimetableException {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    /// The date, as the timestamp of 00:00 UTC
+    pub date: Timestamp,
+    /// The id of the lesson that is changed
+    pub lesson: Option<Uuid>,
+    #[serde(default)]
+    pub cancelled: bool,
+    /// The fields that are set replace the ones of the lesson
+    pub subject: Option<String>,
+    pub room: Option<String>,
+    pub teacher: Option<String>,
+    pub start: Option<DayTimestamp>,
+    pub end: Option<DayTimestamp>,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// Query of GET /classes/{uuid}/timetable/exceptions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimetableExceptionQueryParams {
+    pub before: Option<Timestamp>,
+    pub after: Option<Timestamp>,
+}
+
+/// A lesson on a date, with the exception of that date applied
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct EffectiveLesson {
+    #[serde(flatten)]
+    pub lesson: Lesson,
+    pub cancelled: bool,
+    /// The exception that changed or added the lesson
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exception: Option<Uuid>,
+}
+
+/// The lessons on a date, sorted by their start
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct EffectiveDay {
+    /// The date, as the timestamp of 00:00 UTC
+    pub date: Timestamp,
+    /// The week of the timetable cycle, `None` if every week is the same
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub week: Option<u8>,
+    /// Empty on holidays
+    pub lessons: Vec<EffectiveLesson>,
+    /// The holidays the date is in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub holiday: Option<EffectiveHoliday>,
+}
+
+/// A holidays event that covers a date of the effective timetable
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct EffectiveHoliday {
+    pub event: Uuid,
+    pub name: String,
+    /// The last day of the holidays, as the timestamp of 00:00 UTC
+    pub until: Timestamp,
+}
+
+/// Query of GET /classes/{uuid}/timetable/effective
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EffectiveTimetableQueryParams {
+    /// The first date, in the timezone of the class. Today if not set
+    pub date: Option<Timestamp>,
+    /// The amount of days, `1` if not set
+    pub days: Option<u8>,
 }
 
 impl PartialOrd for Lesson {
@@ -202,10 +972,315 @@ pub struct SingleSnowflake {
     pub snowflake: Snowflake,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GetEventQueryParams {
-    pub before: Option<i64>,
-    pub after: Option<i64>,
+    pub before: Option<Timestamp>,
+    pub after: Option<Timestamp>,
+    /// Only return the events the requesting member has (not) marked as done
+    pub done: Option<bool>,
+    /// Full-text search over the names and descriptions
+    pub q: Option<String>,
+    /// If set, a `Page` with at most `limit` events is returned instead of all events
+    pub limit: Option<i64>,
+    /// The `next` cursor of the previous page
+    pub cursor: Option<String>,
+    /// The order of the events by their start, ascending by default
+    pub sort: Option<SortOrder>,
+    /// Only return the events of the subject, as it is written in the timetable
+    pub subject: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// A page of a listing, `next` is the cursor for the next page, or `None` on the last page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next: Option<String>,
+}
+
+/// A deleted event, member or class
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Trashed<T> {
+    pub deleted_at: Timestamp,
+    pub item: T,
+}
+
+/// The deleted events and members of a class
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trash {
+    pub events: Vec<Trashed<Event>>,
+    pub members: Vec<Trashed<Member>>,
+}
+
+/// The position in a listing of events, sorted by `(start, id)`
+///
+/// ```
+/// # use dto::EventCursor;
+/// let cursor = EventCursor {
+///     start: dto::Timestamp(1628000000000),
+///     id: uuid::Uuid::nil(),
+/// };
+/// assert_eq!(cursor.to_string().parse(), Ok(cursor));
+/// ```
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct EventCursor {
+    pub start: Timestamp,
+    pub id: Uuid,
+}
+
+impl fmt::Display for EventCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}_{}", self.start, self.id)
+    }
+}
+
+impl FromStr for EventCursor {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, id) = s.split_once('_').ok_or(())?;
+        Ok(EventCursor {
+            start: start.parse().map_err(|_| ())?,
+            id: id.parse().map_err(|_| ())?,
+        })
+    }
+}
+
+/// Query of PUT/DELETE /classes/{uuid}/events/{uuid}
+/// If `occurrence` is set, only that occurrence of a recurring event is affected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OccurrenceQueryParams {
+    pub occurrence: Option<Timestamp>,
+}
+
+/// Query of POST /classes/{uuid}/events/import
+/// With `preview`, the import is only checked and nothing is saved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportQueryParams {
+    #[serde(default)]
+    pub preview: bool,
+}
+
+/// Response of POST /classes/{uuid}/events/import
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventImportRes {
+    pub created: usize,
+    pub updated: usize,
+    /// The events that could not be imported, they are skipped
+    pub errors: Vec<EventImportError>,
+}
+
+/// An event of an imported calendar that could not be imported
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventImportError {
+    pub uid: Option<String>,
+    pub error: String,
+}
+
+/// Query of POST /classes/{uuid}/events/batch
+/// With `bestEffort`, the successful operations are saved even if others fail
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchQueryParams {
+    #[serde(default)]
+    pub best_effort: bool,
+}
+
+/// An operation of POST /classes/{uuid}/events/batch
+/// ```
+/// # use dto::BatchOperation;
+/// let op: BatchOperation =
+///     serde_json::from_str(r#"{"op": "delete", "id": "5f3c2d1e-0a4b-4c8d-9e7f-1a2b3c4d5e6f"}"#).unwrap();
+/// assert!(matches!(op, BatchOperation::Delete { .. }));
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum BatchOperation {
+    Create {
+        event: Event,
+    },
+    /// The `id` of the event is the event that is replaced
+    Update {
+        event: Event,
+    },
+    Delete {
+        id: Uuid,
+    },
+}
+
+/// The result of an operation of a batch, in the same order as the operations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum BatchResult {
+    Created {
+        event: Event,
+    },
+    Updated {
+        event: Event,
+    },
+    Deleted {
+        id: Uuid,
+    },
+    Failed {
+        error: String,
+    },
+    /// The operation was not run because an earlier operation failed
+    Skipped,
+}
+
+/// Response of POST /classes/{uuid}/events/batch
+/// If `committed` is false, nothing has been saved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchRes {
+    pub committed: bool,
+    pub results: Vec<BatchResult>,
+}
+
+/// The grade of the requesting member for an exam, grades are private to the member
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Grade {
+    #[serde(default)]
+    pub event: Uuid,
+    #[serde(default)]
+    pub class: Uuid,
+    pub grade: f64,
+    #[serde(default = "Grade::default_weight")]
+    pub weight: f64,
+    #[serde(default)]
+    pub scale: GradeScale,
+    /// The name of the exam, ignored in requests
+    #[serde(default)]
+    pub name: String,
+    /// The subject of the exam, ignored in requests
+    #[serde(default)]
+    pub subject: Option<String>,
+    /// The start of the exam, ignored in requests
+    #[serde(default)]
+    pub start: Timestamp,
+}
+
+impl Grade {
+    pub fn default_weight() -> f64 {
+        1.0
+    }
+}
+
+/// A grading scale, a grade is validated and averaged on its scale
+///
+/// ```
+/// use dto::GradeScale;
+///
+/// assert!(GradeScale::Swiss.is_valid(5.5));
+/// assert!(!GradeScale::Swiss.is_valid(0.5));
+/// assert!(GradeScale::Swiss.is_passing(4.0));
+/// assert!(!GradeScale::Swiss.is_passing(3.75));
+/// assert!(GradeScale::German.is_passing(4.0));
+/// assert!(!GradeScale::German.is_passing(4.5));
+/// ```
+#[derive(
+    Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum GradeScale {
+    /// 1 to 6, 6 is the best grade, 4 is passing
+    #[default]
+    Swiss,
+    /// 1 to 6, 1 is the best grade, 4 is passing
+    German,
+}
+
+impl GradeScale {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GradeScale::Swiss => "swiss",
+            GradeScale::German => "german",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "swiss" => GradeScale::Swiss,
+            "german" => GradeScale::German,
+            _ => return None,
+        })
+    }
+
+    /// The best and the worst grade
+    pub fn range(&self) -> (f64, f64) {
+        match self {
+            GradeScale::Swiss => (6.0, 1.0),
+            GradeScale::German => (1.0, 6.0),
+        }
+    }
+
+    /// The worst grade that is still passing
+    pub fn passing(&self) -> f64 {
+        match self {
+            GradeScale::Swiss | GradeScale::German => 4.0,
+        }
+    }
+
+    pub fn is_valid(&self, grade: f64) -> bool {
+        let (best, worst) = self.range();
+        grade.is_finite() && grade >= best.min(worst) && grade <= best.max(worst)
+    }
+
+    pub fn is_passing(&self, grade: f64) -> bool {
+        let (best, worst) = self.range();
+        if best > worst {
+            grade >= self.passing()
+        } else {
+            grade <= self.passing()
+        }
+    }
+}
+
+/// The weighted average of the grades of a subject on a scale
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubjectGrades {
+    /// `None` for exams without a subject
+    pub subject: Option<String>,
+    pub scale: GradeScale,
+    pub average: f64,
+    pub passing: bool,
+    /// The sum of the weights of the grades
+    pub weight: f64,
+    pub count: usize,
+}
+
+/// The averages of the grades of the requesting member in a class
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GradeSummary {
+    pub class: Uuid,
+    pub subjects: Vec<SubjectGrades>,
+}
+
+/// Query of GET /users/me/grades and GET /users/me/grades/summary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GradeQueryParams {
+    /// Only the grades of the class
+    pub class: Option<Uuid>,
+}
+
+/// A token to subscribe to the calendar of a class, since calendar apps can't send a Bearer token
+/// # IMPORTANT: never log the token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedToken {
+    pub token: Uuid,
+}
+
+/// Query of /classes/{uuid}/calendar.ics
+/// # IMPORTANT: never log the token
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CalendarQueryParams {
+    pub token: Uuid,
 }
 
 /// # IMPORTANT: never log the password
@@ -217,9 +1292,15 @@ pub struct ChangePasswordReq {
 }
 
 /// A single notification that should be sent out by the bot
+/// An event with several reminders has a separate notification for every one of them
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Notification {
+    /// The reminder of the event that is due
+    pub time: Timestamp,
     pub event: Event,
+    /// The timezone of the class of the event
+    #[serde(default = "Class::default_timezone")]
+    pub timezone: String,
     pub guild: Snowflake,
     pub channel: Snowflake,
     pub role_ping: Option<Snowflake>,
@@ -235,7 +1316,7 @@ pub struct NotificationRes {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NotificationQueryParams {
-    pub since: i64,
+    pub since: Timestamp,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
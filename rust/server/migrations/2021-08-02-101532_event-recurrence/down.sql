@@ -0,0 +1,9 @@
+ALTER TABLE events
+    DROP CONSTRAINT event_series_fk,
+    DROP CONSTRAINT event_rec_frequency_check,
+    DROP COLUMN series,
+    DROP COLUMN rec_exceptions,
+    DROP COLUMN rec_count,
+    DROP COLUMN rec_until,
+    DROP COLUMN rec_interval,
+    DROP COLUMN rec_frequency;
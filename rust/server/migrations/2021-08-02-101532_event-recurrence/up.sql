@@ -0,0 +1,13 @@
+ALTER TABLE events
+    ADD COLUMN rec_frequency  VARCHAR(10) NULL,
+    ADD COLUMN rec_interval   INT         NOT NULL DEFAULT 1,
+    ADD COLUMN rec_until      TIMESTAMP   NULL,
+    ADD COLUMN rec_count      INT         NULL,
+    ADD COLUMN rec_exceptions TIMESTAMP[] NOT NULL DEFAULT '{}',
+    ADD COLUMN series         UUID        NULL,
+    ADD CONSTRAINT event_rec_frequency_check
+        CHECK (rec_frequency IN ('daily', 'weekly', 'monthly', 'yearly')),
+    ADD CONSTRAINT event_series_fk
+        FOREIGN KEY (series)
+            REFERENCES events (id)
+            ON DELETE CASCADE;
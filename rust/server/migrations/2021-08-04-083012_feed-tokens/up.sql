@@ -0,0 +1,12 @@
+CREATE TABLE feed_tokens
+(
+    token   UUID PRIMARY KEY,
+    "user"  UUID NOT NULL,
+    class   UUID NOT NULL,
+    CONSTRAINT feed_token_member_fk
+        FOREIGN KEY ("user", class)
+            REFERENCES members ("user", class)
+            ON DELETE CASCADE,
+    CONSTRAINT unique_feed_token_member
+        UNIQUE ("user", class)
+);
@@ -0,0 +1,4 @@
+ALTER TABLE events
+    ADD COLUMN import_uid VARCHAR NULL,
+    ADD CONSTRAINT unique_event_import_uid
+        UNIQUE (class, import_uid);
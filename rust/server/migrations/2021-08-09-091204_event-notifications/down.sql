@@ -0,0 +1,8 @@
+ALTER TABLE events
+    ADD COLUMN notification TIMESTAMP NULL;
+
+-- only the earliest notification of an event can be kept
+UPDATE events
+SET notification = (SELECT min(time) FROM event_notifications WHERE event = events.id);
+
+DROP TABLE event_notifications;
@@ -0,0 +1,18 @@
+CREATE TABLE event_notifications
+(
+    event UUID      NOT NULL,
+    time  TIMESTAMP NOT NULL,
+    PRIMARY KEY (event, time),
+    CONSTRAINT event_notification_event_fk
+        FOREIGN KEY (event)
+            REFERENCES events (id)
+            ON DELETE CASCADE
+);
+
+INSERT INTO event_notifications (event, time)
+SELECT id, notification
+FROM events
+WHERE notification IS NOT NULL;
+
+ALTER TABLE events
+    DROP COLUMN notification;
@@ -0,0 +1,13 @@
+UPDATE events
+SET e_type = 4
+WHERE e_type IN (SELECT id FROM event_types WHERE class IS NOT NULL);
+
+DELETE
+FROM event_types
+WHERE class IS NOT NULL;
+
+ALTER TABLE event_types
+    DROP COLUMN class,
+    DROP COLUMN colour,
+    DROP COLUMN reminders,
+    ALTER COLUMN display TYPE VARCHAR(10);
@@ -0,0 +1,21 @@
+-- event types without a class are the built-in types, they exist in every class
+ALTER TABLE event_types
+    ALTER COLUMN display TYPE VARCHAR(50),
+    ADD COLUMN class     UUID       NULL,
+    ADD COLUMN colour    VARCHAR(7) NOT NULL DEFAULT '#808080',
+    -- the default notifications of new events, in seconds before the start
+    ADD COLUMN reminders INT[]      NOT NULL DEFAULT '{}',
+    ADD CONSTRAINT event_type_class_fk
+        FOREIGN KEY (class)
+            REFERENCES classes (id)
+            ON DELETE CASCADE,
+    ADD CONSTRAINT unique_event_type_name
+        UNIQUE (class, display);
+
+UPDATE event_types
+SET colour = CASE display
+                 WHEN 'homework' THEN '#1e88e5'
+                 WHEN 'exam' THEN '#e53935'
+                 WHEN 'holidays' THEN '#43a047'
+                 ELSE '#808080'
+    END;
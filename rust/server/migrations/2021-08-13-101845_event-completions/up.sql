@@ -0,0 +1,16 @@
+CREATE TABLE event_completions
+(
+    event   UUID      NOT NULL,
+    "user"  UUID      NOT NULL,
+    class   UUID      NOT NULL,
+    done_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (event, "user"),
+    CONSTRAINT event_completion_event_fk
+        FOREIGN KEY (event)
+            REFERENCES events (id)
+            ON DELETE CASCADE,
+    CONSTRAINT event_completion_member_fk
+        FOREIGN KEY ("user", class)
+            REFERENCES members ("user", class)
+            ON DELETE CASCADE
+);
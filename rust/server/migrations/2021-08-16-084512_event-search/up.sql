@@ -0,0 +1 @@
+CREATE INDEX events_search_idx ON events USING GIN (to_tsvector('german', name || ' ' || description));
@@ -0,0 +1,16 @@
+-- events are not referenced, the history of deleted events is kept
+CREATE TABLE event_revisions
+(
+    id       SERIAL PRIMARY KEY,
+    event    UUID        NOT NULL,
+    class    UUID        NOT NULL REFERENCES classes ON DELETE CASCADE,
+    actor    UUID        NULL REFERENCES users ON DELETE SET NULL,
+    kind     VARCHAR(10) NOT NULL,
+    time     TIMESTAMP   NOT NULL,
+    -- JSON, the changed fields with their old and new value
+    changes  TEXT        NOT NULL,
+    -- JSON, the event after the change, NULL if it was deleted
+    snapshot TEXT        NULL
+);
+
+CREATE INDEX event_revisions_event_idx ON event_revisions (event);
@@ -0,0 +1,12 @@
+DELETE FROM events WHERE deleted_at IS NOT NULL;
+DELETE FROM members WHERE deleted_at IS NOT NULL;
+DELETE FROM classes WHERE deleted_at IS NOT NULL;
+
+ALTER TABLE events
+    DROP COLUMN deleted_at;
+
+ALTER TABLE members
+    DROP COLUMN deleted_at;
+
+ALTER TABLE classes
+    DROP COLUMN deleted_at;
@@ -0,0 +1,9 @@
+-- deleted rows are kept in the trash until they are purged
+ALTER TABLE classes
+    ADD COLUMN deleted_at TIMESTAMP NULL;
+
+ALTER TABLE members
+    ADD COLUMN deleted_at TIMESTAMP NULL;
+
+ALTER TABLE events
+    ADD COLUMN deleted_at TIMESTAMP NULL;
@@ -0,0 +1,4 @@
+DROP INDEX events_subject_idx;
+
+ALTER TABLE events
+    DROP COLUMN subject;
@@ -0,0 +1,5 @@
+-- the subject of a lesson in the timetable of the class
+ALTER TABLE events
+    ADD COLUMN subject VARCHAR NULL;
+
+CREATE INDEX events_subject_idx ON events (class, subject);
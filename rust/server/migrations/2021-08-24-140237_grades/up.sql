@@ -0,0 +1,19 @@
+-- grades are private, they are only ever shown to the member themselves
+CREATE TABLE grades
+(
+    "user" UUID             NOT NULL,
+    event  UUID             NOT NULL,
+    class  UUID             NOT NULL,
+    grade  DOUBLE PRECISION NOT NULL,
+    weight DOUBLE PRECISION NOT NULL DEFAULT 1,
+    scale  VARCHAR(10)      NOT NULL DEFAULT 'swiss',
+    PRIMARY KEY ("user", event),
+    CONSTRAINT grade_event_fk
+        FOREIGN KEY (event)
+            REFERENCES events (id)
+            ON DELETE CASCADE,
+    CONSTRAINT grade_member_fk
+        FOREIGN KEY ("user", class)
+            REFERENCES members ("user", class)
+            ON DELETE CASCADE
+);
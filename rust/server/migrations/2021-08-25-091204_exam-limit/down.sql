@@ -0,0 +1,3 @@
+ALTER TABLE classes
+    DROP COLUMN exam_limit,
+    DROP COLUMN strict_exam_limit;
@@ -0,0 +1,4 @@
+-- the maximum amount of exams in a week, strict classes reject exams that exceed it
+ALTER TABLE classes
+    ADD COLUMN exam_limit        INTEGER NOT NULL DEFAULT 3,
+    ADD COLUMN strict_exam_limit BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+-- the IANA timezone of the class, lessons and dates are shown in it
+ALTER TABLE classes
+    ADD COLUMN timezone VARCHAR NOT NULL DEFAULT 'Europe/Zurich';
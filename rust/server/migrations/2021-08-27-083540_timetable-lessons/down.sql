@@ -0,0 +1,16 @@
+ALTER TABLE timetables
+    ADD COLUMN timetable TEXT NOT NULL DEFAULT '[[],[],[],[],[],[],[]]';
+
+UPDATE timetables
+SET timetable = (SELECT json_agg(
+                                COALESCE((SELECT json_agg(json_build_object(
+                                        'subject', subject,
+                                        'description', description,
+                                        'start', start,
+                                        'end', "end") ORDER BY start)
+                                          FROM lessons
+                                          WHERE lessons.class = timetables.class
+                                            AND lessons.weekday = day), '[]'::JSON) ORDER BY day)
+                 FROM generate_series(0, 6) AS day)::TEXT;
+
+DROP TABLE lessons;
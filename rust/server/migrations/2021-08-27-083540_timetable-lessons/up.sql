@@ -0,0 +1,33 @@
+-- the lessons of a timetable are stored as rows, so they have stable ids and can be queried
+CREATE TABLE lessons
+(
+    id          UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    class       UUID    NOT NULL,
+    weekday     INTEGER NOT NULL CHECK (weekday BETWEEN 0 AND 6),
+    start       BIGINT  NOT NULL,
+    "end"       BIGINT  NOT NULL,
+    subject     VARCHAR NOT NULL,
+    room        VARCHAR NOT NULL DEFAULT '',
+    teacher     VARCHAR NOT NULL DEFAULT '',
+    description VARCHAR NOT NULL DEFAULT '',
+    CONSTRAINT lesson_timetable_fk
+        FOREIGN KEY (class)
+            REFERENCES timetables (class)
+            ON DELETE CASCADE
+);
+
+CREATE INDEX lessons_class_idx ON lessons (class, weekday, start);
+
+INSERT INTO lessons (class, weekday, start, "end", subject, description)
+SELECT timetables.class,
+       day.index - 1,
+       (lesson ->> 'start')::BIGINT,
+       (lesson ->> 'end')::BIGINT,
+       lesson ->> 'subject',
+       COALESCE(lesson ->> 'description', '')
+FROM timetables,
+     json_array_elements(timetables.timetable::JSON) WITH ORDINALITY AS day (lessons, index),
+     json_array_elements(day.lessons) AS lesson;
+
+ALTER TABLE timetables
+    DROP COLUMN timetable;
@@ -0,0 +1,6 @@
+ALTER TABLE lessons
+    DROP COLUMN cycle_week;
+
+ALTER TABLE timetables
+    DROP COLUMN cycle_weeks,
+    DROP COLUMN cycle_anchor;
@@ -0,0 +1,8 @@
+-- timetables can alternate between several weeks, the anchor is a date in the first week
+ALTER TABLE timetables
+    ADD COLUMN cycle_weeks  INTEGER NOT NULL DEFAULT 1 CHECK (cycle_weeks >= 1),
+    ADD COLUMN cycle_anchor DATE    NOT NULL DEFAULT CURRENT_DATE;
+
+-- NULL if the lesson is in every week of the cycle
+ALTER TABLE lessons
+    ADD COLUMN cycle_week INTEGER NULL CHECK (cycle_week >= 0);
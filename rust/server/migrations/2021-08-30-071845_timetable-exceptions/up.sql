@@ -0,0 +1,25 @@
+-- changes a lesson on a single date, or adds an extra lesson if there is no lesson
+CREATE TABLE timetable_exceptions
+(
+    id          UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    class       UUID    NOT NULL,
+    date        DATE    NOT NULL,
+    lesson      UUID    NULL,
+    cancelled   BOOLEAN NOT NULL DEFAULT FALSE,
+    subject     VARCHAR NULL,
+    room        VARCHAR NULL,
+    teacher     VARCHAR NULL,
+    start       BIGINT  NULL,
+    "end"       BIGINT  NULL,
+    description VARCHAR NOT NULL DEFAULT '',
+    CONSTRAINT exception_timetable_fk
+        FOREIGN KEY (class)
+            REFERENCES timetables (class)
+            ON DELETE CASCADE,
+    CONSTRAINT exception_lesson_fk
+        FOREIGN KEY (lesson)
+            REFERENCES lessons (id)
+            ON DELETE CASCADE
+);
+
+CREATE INDEX timetable_exceptions_date_idx ON timetable_exceptions (class, date);
@@ -0,0 +1,14 @@
+-- only the lessons of the version that is valid today are kept
+DELETE
+FROM lessons
+WHERE version NOT IN (SELECT DISTINCT ON (class) id
+                      FROM timetable_versions
+                      ORDER BY class,
+                               (COALESCE(valid_from, '-infinity') <= CURRENT_DATE AND
+                                COALESCE(valid_until, 'infinity') >= CURRENT_DATE) DESC,
+                               valid_from DESC NULLS LAST);
+
+ALTER TABLE lessons
+    DROP COLUMN version;
+
+DROP TABLE timetable_versions;
@@ -0,0 +1,35 @@
+-- a class can have several timetables, each one is valid between two dates. NULL is unbounded
+CREATE TABLE timetable_versions
+(
+    id          UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    class       UUID NOT NULL,
+    valid_from  DATE NULL,
+    valid_until DATE NULL,
+    CONSTRAINT version_timetable_fk
+        FOREIGN KEY (class)
+            REFERENCES timetables (class)
+            ON DELETE CASCADE,
+    CONSTRAINT valid_range
+        CHECK (valid_from IS NULL OR valid_until IS NULL OR valid_from <= valid_until)
+);
+
+CREATE INDEX timetable_versions_class_idx ON timetable_versions (class);
+
+INSERT INTO timetable_versions (class)
+SELECT class
+FROM timetables;
+
+ALTER TABLE lessons
+    ADD COLUMN version UUID NULL;
+
+UPDATE lessons
+SET version = timetable_versions.id
+FROM timetable_versions
+WHERE timetable_versions.class = lessons.class;
+
+ALTER TABLE lessons
+    ALTER COLUMN version SET NOT NULL,
+    ADD CONSTRAINT lesson_version_fk
+        FOREIGN KEY (version)
+            REFERENCES timetable_versions (id)
+            ON DELETE CASCADE;
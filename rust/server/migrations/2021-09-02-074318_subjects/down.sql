@@ -0,0 +1,14 @@
+DROP INDEX events_subject_id_idx;
+
+ALTER TABLE events
+    DROP CONSTRAINT event_subject_id,
+    DROP CONSTRAINT event_subject_fk,
+    DROP COLUMN subject_id;
+
+DROP INDEX lessons_subject_idx;
+
+ALTER TABLE lessons
+    DROP CONSTRAINT lesson_subject_fk,
+    DROP COLUMN subject_id;
+
+DROP TABLE subjects;
@@ -0,0 +1,94 @@
+-- the subjects of a class, lessons and events reference them instead of spelling them out
+CREATE TABLE subjects
+(
+    id      UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    class   UUID        NOT NULL,
+    name    VARCHAR     NOT NULL,
+    short   VARCHAR(10) NOT NULL DEFAULT '',
+    colour  VARCHAR(7)  NOT NULL DEFAULT '',
+    teacher VARCHAR     NOT NULL DEFAULT '',
+    room    VARCHAR     NOT NULL DEFAULT '',
+    CONSTRAINT subject_class_fk
+        FOREIGN KEY (class)
+            REFERENCES classes (id)
+            ON DELETE CASCADE,
+    -- the name is copied into lessons and events, renaming a subject renames them too
+    CONSTRAINT subject_id_name_key
+        UNIQUE (id, name)
+);
+
+CREATE UNIQUE INDEX subjects_class_name_idx ON subjects (class, lower(name));
+
+-- events without a subject don't get one, lessons need one and get a placeholder
+UPDATE events
+SET subject = NULL
+WHERE trim(subject) = '';
+
+UPDATE lessons
+SET subject = 'Unbekannt'
+WHERE trim(subject) = '';
+
+-- "Mathe", "mathe " and "MATHE" are the same subject, the most used spelling wins
+INSERT INTO subjects (class, name)
+SELECT class, mode() WITHIN GROUP (ORDER BY name)
+FROM (SELECT class, trim(subject) AS name
+      FROM lessons
+      WHERE trim(subject) <> ''
+      UNION ALL
+      SELECT class, trim(subject)
+      FROM events
+      WHERE trim(subject) <> '') AS names
+GROUP BY class, lower(name);
+
+UPDATE subjects
+SET teacher = coalesce((SELECT mode() WITHIN GROUP (ORDER BY teacher)
+                        FROM lessons
+                        WHERE lessons.class = subjects.class
+                          AND lower(trim(lessons.subject)) = lower(subjects.name)
+                          AND teacher <> ''), ''),
+    room    = coalesce((SELECT mode() WITHIN GROUP (ORDER BY room)
+                        FROM lessons
+                        WHERE lessons.class = subjects.class
+                          AND lower(trim(lessons.subject)) = lower(subjects.name)
+                          AND room <> ''), '');
+
+ALTER TABLE lessons
+    ADD COLUMN subject_id UUID NULL;
+
+UPDATE lessons
+SET subject_id = subjects.id,
+    subject    = subjects.name
+FROM subjects
+WHERE subjects.class = lessons.class
+  AND lower(subjects.name) = lower(trim(lessons.subject));
+
+ALTER TABLE lessons
+    ALTER COLUMN subject_id SET NOT NULL,
+    ADD CONSTRAINT lesson_subject_fk
+        FOREIGN KEY (subject_id, subject)
+            REFERENCES subjects (id, name)
+            ON UPDATE CASCADE;
+
+CREATE INDEX lessons_subject_idx ON lessons (subject_id);
+
+ALTER TABLE events
+    ADD COLUMN subject_id UUID NULL;
+
+UPDATE events
+SET subject_id = subjects.id,
+    subject    = subjects.name
+FROM subjects
+WHERE subjects.class = events.class
+  AND lower(subjects.name) = lower(trim(events.subject));
+
+-- deleting a subject removes it from its events
+ALTER TABLE events
+    ADD CONSTRAINT event_subject_fk
+        FOREIGN KEY (subject_id, subject)
+            REFERENCES subjects (id, name)
+            ON UPDATE CASCADE
+            ON DELETE SET NULL,
+    ADD CONSTRAINT event_subject_id
+        CHECK ((subject_id IS NULL) = (subject IS NULL));
+
+CREATE INDEX events_subject_id_idx ON events (subject_id);
@@ -2,11 +2,12 @@ use crate::actions::Pool;
 use crate::diesel::{QueryDsl, RunQueryDsl};
 use crate::error::{ServiceErr, ServiceResult};
 use crate::models::{
-    Class, Guild, Member, MemberRole, NewClass, NewGuild, NewMember, Timetable, User,
+    Class, FeedToken, Guild, Member, MemberRole, NewClass, NewGuild, NewMember, User,
 };
 use crate::schema::classes::dsl::*;
 use diesel::{
-    delete, insert_into, update, BoolExpressionMethods, ExpressionMethods, SaveChangesDsl,
+    delete, insert_into, update, BoolExpressionMethods, Connection, ExpressionMethods,
+    SaveChangesDsl,
 };
 use uuid::Uuid;
 
@@ -19,13 +20,16 @@ pub fn insert_class(db: &Pool, new_class: NewClass) -> ServiceResult<Class> {
 pub type ClassMemberData = (Class, Vec<(Member, User)>);
 
 pub fn get_class(db: &Pool, class_id: Uuid) -> ServiceResult<Option<ClassMemberData>> {
-    use crate::schema::members::dsl::{display_name, members, role};
+    use crate::schema::members::dsl::{
+        deleted_at as member_deleted_at, display_name, members, role,
+    };
     use crate::schema::users::dsl::users;
     let conn = db.get()?;
 
     let vec = classes
         .inner_join(members.inner_join(users))
         .filter(id.eq(class_id).and(role.ne(MemberRole::PENDING)))
+        .filter(deleted_at.is_null().and(member_deleted_at.is_null()))
         .order_by((role, display_name))
         .load(&conn)?;
 
@@ -33,7 +37,9 @@ pub fn get_class(db: &Pool, class_id: Uuid) -> ServiceResult<Option<ClassMemberD
 }
 
 pub fn get_classes_by_user(db: &Pool, user_id: Uuid) -> ServiceResult<Vec<Class>> {
-    use crate::schema::members::dsl::{members, role, user as member_user};
+    use crate::schema::members::dsl::{
+        deleted_at as member_deleted_at, members, role, user as member_user,
+    };
     let conn = db.get()?;
 
     let class_vec: Vec<(Class, Member)> = classes
@@ -44,28 +50,31 @@ pub fn get_classes_by_user(db: &Pool, user_id: Uuid) -> ServiceResult<Vec<Class>
                 .and(role.ne(MemberRole::BANNED))
                 .and(role.ne(MemberRole::PENDING)),
         )
+        .filter(deleted_at.is_null().and(member_deleted_at.is_null()))
         .load(&conn)?;
 
     Ok(class_vec.into_iter().map(|(class, _)| class).collect())
 }
 
 pub fn get_pending_members(db: &Pool, class_id: Uuid) -> ServiceResult<Vec<Member>> {
-    use crate::schema::members::dsl::{class, members, role};
+    use crate::schema::members::dsl::{class, deleted_at as member_deleted_at, members, role};
     let conn = db.get()?;
 
     Ok(members
         .filter(class.eq(class_id).and(role.eq(MemberRole::PENDING)))
+        .filter(member_deleted_at.is_null())
         .load(&conn)?)
 }
 
 pub fn get_banned_members(db: &Pool, class_id: Uuid) -> ServiceResult<Vec<(Member, User)>> {
-    use crate::schema::members::dsl::{class, members, role};
+    use crate::schema::members::dsl::{class, deleted_at as member_deleted_at, members, role};
     use crate::schema::users::dsl::users;
     let conn = db.get()?;
 
     Ok(members
         .inner_join(users)
         .filter(class.eq(class_id).and(role.eq(MemberRole::BANNED)))
+        .filter(member_deleted_at.is_null())
         .load(&conn)?)
 }
 
@@ -75,11 +84,21 @@ pub fn update_member(db: &Pool, member: NewMember) -> ServiceResult<Member> {
     Ok(member.save_changes(&*conn)?)
 }
 
+/// A deleted member is replaced by the new member
 pub fn create_member(db: &Pool, member: NewMember) -> ServiceResult<Member> {
-    use crate::schema::members::dsl::members;
+    use crate::schema::members::dsl::{
+        class, deleted_at as member_deleted_at, members, user as member_user,
+    };
     let conn = db.get()?;
 
-    Ok(insert_into(members).values(&member).get_result(&conn)?)
+    conn.transaction(|| {
+        delete(members)
+            .filter(member_user.eq(member.user).and(class.eq(member.class)))
+            .filter(member_deleted_at.is_not_null())
+            .execute(&conn)?;
+
+        Ok(insert_into(members).values(&member).get_result(&conn)?)
+    })
 }
 
 pub fn update_class(db: &Pool, new_class: NewClass) -> ServiceResult<Class> {
@@ -90,6 +109,9 @@ pub fn update_class(db: &Pool, new_class: NewClass) -> ServiceResult<Class> {
         .set((
             name.eq(new_class.name),
             description.eq(new_class.description),
+            exam_limit.eq(new_class.exam_limit),
+            strict_exam_limit.eq(new_class.strict_exam_limit),
+            timezone.eq(new_class.timezone),
         ))
         .get_result(&conn)?)
 }
@@ -107,70 +129,108 @@ pub fn get_class_by_discord(db: &Pool, class_id: &str) -> ServiceResult<Class> {
     let conn = db.get()?;
 
     classes
-        .filter(discord_id.eq(class_id))
+        .filter(discord_id.eq(class_id).and(deleted_at.is_null()))
         .load(&conn)?
         .into_iter()
         .next()
         .ok_or(ServiceErr::NotFound)
 }
 
+/// Members of deleted classes are not found
 pub fn get_member(db: &Pool, user_id: Uuid, class_id: Uuid) -> ServiceResult<(Member, User)> {
-    use crate::schema::members::dsl::{class, members, user};
+    use crate::schema::members::dsl::{class, deleted_at as member_deleted_at, members, user};
     use crate::schema::users::dsl::users;
     let conn = db.get()?;
 
     Ok(members
         .inner_join(users)
         .filter(class.eq(class_id).and(user.eq(user_id)))
+        .filter(member_deleted_at.is_null())
+        .filter(class.eq_any(classes.select(id).filter(deleted_at.is_null())))
         .get_result(&conn)?)
 }
 
+/// Moves the member to the trash
 pub fn delete_member(db: &Pool, user_id: Uuid, class_id: Uuid) -> ServiceResult<usize> {
-    use crate::schema::members::dsl::{class, members, user};
+    use crate::schema::members::dsl::{class, deleted_at as member_deleted_at, members, user};
     let conn = db.get()?;
 
-    Ok(delete(members.filter(class.eq(class_id).and(user.eq(user_id)))).execute(&conn)?)
+    Ok(
+        update(members.filter(class.eq(class_id).and(user.eq(user_id))))
+            .filter(member_deleted_at.is_null())
+            .set(member_deleted_at.eq(chrono::Utc::now().naive_utc()))
+            .execute(&conn)?,
+    )
 }
 
+/// Moves the class to the trash, together with everything in it
 pub fn delete_class(db: &Pool, class_id: Uuid) -> ServiceResult<usize> {
     let conn = db.get()?;
 
-    Ok(delete(classes).filter(id.eq(class_id)).execute(&conn)?)
+    Ok(update(classes)
+        .filter(id.eq(class_id).and(deleted_at.is_null()))
+        .set(deleted_at.eq(chrono::Utc::now().naive_utc()))
+        .execute(&conn)?)
 }
 
-pub fn get_timetable(db: &Pool, class_id: Uuid) -> ServiceResult<Timetable> {
-    use crate::schema::timetables::dsl::*;
+/// The deleted members of the class
+pub fn get_deleted_members(db: &Pool, class_id: Uuid) -> ServiceResult<Vec<(Member, User)>> {
+    use crate::schema::members::dsl::{class, deleted_at as member_deleted_at, members};
+    use crate::schema::users::dsl::users;
     let conn = db.get()?;
 
-    Ok(timetables.find(class_id).get_result(&conn)?)
+    Ok(members
+        .inner_join(users)
+        .filter(class.eq(class_id).and(member_deleted_at.is_not_null()))
+        .order(member_deleted_at.desc())
+        .load(&conn)?)
 }
 
-pub fn create_timetable(db: &Pool, class_id: Uuid) -> ServiceResult<Timetable> {
-    use crate::schema::timetables::dsl::*;
+pub fn restore_member(db: &Pool, user_id: Uuid, class_id: Uuid) -> ServiceResult<Member> {
+    use crate::schema::members::dsl::{class, deleted_at as member_deleted_at, members, user};
     let conn = db.get()?;
 
-    Ok(insert_into(timetables)
-        .values(class.eq(class_id))
-        .get_result(&conn)?)
+    Ok(
+        update(members.filter(class.eq(class_id).and(user.eq(user_id))))
+            .filter(member_deleted_at.is_not_null())
+            .set(member_deleted_at.eq(None::<chrono::NaiveDateTime>))
+            .get_result(&conn)?,
+    )
 }
 
-pub fn update_timetable(db: &Pool, new_timetable: Timetable) -> ServiceResult<Timetable> {
-    use crate::schema::timetables::dsl::*;
+/// The deleted classes that the user owns
+pub fn get_deleted_classes(db: &Pool, owner_id: Uuid) -> ServiceResult<Vec<Class>> {
     let conn = db.get()?;
 
-    Ok(update(timetables)
-        .filter(class.eq(new_timetable.class))
-        .set(timetable.eq(new_timetable.timetable))
+    Ok(classes
+        .filter(owner.eq(owner_id).and(deleted_at.is_not_null()))
+        .order(deleted_at.desc())
+        .load(&conn)?)
+}
+
+pub fn restore_class(db: &Pool, owner_id: Uuid, class_id: Uuid) -> ServiceResult<Class> {
+    let conn = db.get()?;
+
+    Ok(update(classes)
+        .filter(id.eq(class_id).and(owner.eq(owner_id)))
+        .filter(deleted_at.is_not_null())
+        .set(deleted_at.eq(None::<chrono::NaiveDateTime>))
         .get_result(&conn)?)
 }
 
-pub fn delete_timetable(db: &Pool, class_id: Uuid) -> ServiceResult<usize> {
-    use crate::schema::timetables::dsl::*;
+/// Deletes everything that has been in the trash since before `before` for good
+pub fn purge_trash(db: &Pool, before: chrono::NaiveDateTime) -> ServiceResult<usize> {
+    use crate::schema::events::dsl::{deleted_at as event_deleted_at, events};
+    use crate::schema::members::dsl::{deleted_at as member_deleted_at, members};
     let conn = db.get()?;
 
-    Ok(delete(timetables)
-        .filter(class.eq(class_id))
-        .execute(&conn)?)
+    conn.transaction(|| {
+        let purged_events = delete(events.filter(event_deleted_at.lt(before))).execute(&conn)?;
+        let purged_members = delete(members.filter(member_deleted_at.lt(before))).execute(&conn)?;
+        let purged_classes = delete(classes.filter(deleted_at.lt(before))).execute(&conn)?;
+
+        Ok(purged_events + purged_members + purged_classes)
+    })
 }
 
 pub fn insert_guild(db: &Pool, guild: NewGuild) -> ServiceResult<Guild> {
@@ -191,6 +251,39 @@ pub fn get_guild_settings(db: &Pool, guild_id: &str) -> ServiceResult<Guild> {
     Ok(guilds.find(guild_id).get_result(&conn)?)
 }
 
+/// Creates a new calendar feed token for the member, an old token is replaced
+pub fn create_feed_token(db: &Pool, user_id: Uuid, class_id: Uuid) -> ServiceResult<FeedToken> {
+    use crate::schema::feed_tokens::dsl::{class, feed_tokens, token, user};
+    let conn = db.get()?;
+
+    let new_token = FeedToken {
+        token: Uuid::new_v4(),
+        user: user_id,
+        class: class_id,
+    };
+
+    Ok(insert_into(feed_tokens)
+        .values(&new_token)
+        .on_conflict((user, class))
+        .do_update()
+        .set(token.eq(new_token.token))
+        .get_result(&conn)?)
+}
+
+pub fn get_feed_token(db: &Pool, feed_token: Uuid) -> ServiceResult<FeedToken> {
+    use crate::schema::feed_tokens::dsl::feed_tokens;
+    let conn = db.get()?;
+
+    Ok(feed_tokens.find(feed_token).get_result(&conn)?)
+}
+
+pub fn delete_feed_token(db: &Pool, user_id: Uuid, class_id: Uuid) -> ServiceResult<usize> {
+    use crate::schema::feed_tokens::dsl::{class, feed_tokens, user};
+    let conn = db.get()?;
+
+    Ok(delete(feed_tokens.filter(class.eq(class_id).and(user.eq(user_id)))).execute(&conn)?)
+}
+
 pub fn map_class_join_members(vec: Vec<(Class, (Member, User))>) -> Option<ClassMemberData> {
     match vec
         .into_iter()
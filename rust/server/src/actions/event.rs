@@ -1,51 +1,108 @@
 use crate::actions::Pool;
-use crate::diesel::{QueryDsl, RunQueryDsl};
-use crate::models::{Class, Event, Guild, NewEvent};
+use crate::diesel::{BelongingToDsl, GroupedBy, QueryDsl, RunQueryDsl};
+use crate::history;
+use crate::models::conversion::IntoDto;
+use crate::models::{
+    Class, Event, EventCompletion, EventNotification, EventRevision, EventType, Guild, NewEvent,
+    NewEventRevision, NewEventType,
+};
+use crate::recurrence;
+use crate::workload;
 
-use crate::error::ServiceResult;
+use crate::error::{ServiceErr, ServiceResult};
 use crate::schema::events::dsl::*;
+use chrono_tz::Tz;
 use diesel::sql_types::{Nullable, Timestamp, VarChar};
 use diesel::{
-    delete, insert_into, BoolExpressionMethods, ExpressionMethods, JoinOnDsl, SaveChangesDsl,
+    delete, insert_into, update, BoolExpressionMethods, Connection, ExpressionMethods, JoinOnDsl,
+    OptionalExtension, PgConnection, SaveChangesDsl,
 };
+use dto::{RevisionKind, WorkloadWarning};
+use std::collections::{BTreeSet, HashMap};
 use uuid::Uuid;
 
-pub fn get_events_by_class(db: &Pool, class_id: Uuid) -> ServiceResult<Vec<Event>> {
+// Recurring events are always loaded and then expanded into their occurrences in the window
+
+pub type EventWithNotifications = (Event, Vec<EventNotification>);
+
+pub fn get_events_by_class(
+    db: &Pool,
+    class_id: Uuid,
+) -> ServiceResult<Vec<EventWithNotifications>> {
+    let conn = db.get()?;
+
+    let vec: Vec<Event> = events
+        .filter(class.eq(class_id).and(deleted_at.is_null()))
+        .load(&conn)?;
+
+    Ok(recurrence::expand(
+        with_notifications(&conn, vec)?,
+        class_timezone(&conn, class_id)?,
+        None,
+        None,
+    ))
+}
+
+/// Loads the events without expanding the recurring ones
+pub fn get_series_by_class(
+    db: &Pool,
+    class_id: Uuid,
+) -> ServiceResult<Vec<EventWithNotifications>> {
     let conn = db.get()?;
 
-    let vec: Vec<Event> = events.filter(class.eq(class_id)).load(&conn)?;
+    let vec: Vec<Event> = events
+        .filter(class.eq(class_id).and(deleted_at.is_null()))
+        .load(&conn)?;
 
-    Ok(vec)
+    with_notifications(&conn, vec)
 }
 
 pub fn get_events_by_class_filtered_after(
     db: &Pool,
     class_id: Uuid,
     after: chrono::NaiveDateTime,
-) -> ServiceResult<Vec<Event>> {
+) -> ServiceResult<Vec<EventWithNotifications>> {
     let conn = db.get()?;
 
     sql_function!(fn coalesce(a: Nullable<Timestamp>, b: Timestamp) -> Timestamp);
 
     let vec: Vec<Event> = events
-        .filter(class.eq(class_id).and(coalesce(end, start).gt(after)))
+        .filter(deleted_at.is_null())
+        .filter(
+            class.eq(class_id).and(
+                coalesce(end, start)
+                    .gt(after)
+                    .or(rec_frequency.is_not_null()),
+            ),
+        )
         .load(&conn)?;
 
-    Ok(vec)
+    Ok(recurrence::expand(
+        with_notifications(&conn, vec)?,
+        class_timezone(&conn, class_id)?,
+        Some(after),
+        None,
+    ))
 }
 
 pub fn get_events_by_class_filtered_before(
     db: &Pool,
     class_id: Uuid,
     before: chrono::NaiveDateTime,
-) -> ServiceResult<Vec<Event>> {
+) -> ServiceResult<Vec<EventWithNotifications>> {
     let conn = db.get()?;
 
     let vec: Vec<Event> = events
         .filter(class.eq(class_id).and(start.lt(before)))
+        .filter(deleted_at.is_null())
         .load(&conn)?;
 
-    Ok(vec)
+    Ok(recurrence::expand(
+        with_notifications(&conn, vec)?,
+        class_timezone(&conn, class_id)?,
+        None,
+        Some(before),
+    ))
 }
 
 pub fn get_events_by_class_filtered_both(
@@ -53,50 +110,927 @@ pub fn get_events_by_class_filtered_both(
     class_id: Uuid,
     before: chrono::NaiveDateTime,
     after: chrono::NaiveDateTime,
-) -> ServiceResult<Vec<Event>> {
+) -> ServiceResult<Vec<EventWithNotifications>> {
     let conn = db.get()?;
 
+    events_between(&conn, class_id, before, after)
+}
+
+fn events_between(
+    conn: &PgConnection,
+    class_id: Uuid,
+    before: chrono::NaiveDateTime,
+    after: chrono::NaiveDateTime,
+) -> ServiceResult<Vec<EventWithNotifications>> {
     sql_function!(fn coalesce(a: Nullable<Timestamp>, b: Timestamp) -> Timestamp);
 
     let vec: Vec<Event> = events
+        .filter(deleted_at.is_null())
         .filter(
-            class
-                .eq(class_id)
-                .and(start.lt(before).and(coalesce(end, start).gt(after))),
+            class.eq(class_id).and(
+                start.lt(before).and(
+                    coalesce(end, start)
+                        .gt(after)
+                        .or(rec_frequency.is_not_null()),
+                ),
+            ),
         )
+        .load(conn)?;
+
+    Ok(recurrence::expand(
+        with_notifications(conn, vec)?,
+        class_timezone(conn, class_id)?,
+        Some(after),
+        Some(before),
+    ))
+}
+
+/// The last `(start, id)` of the previous page
+pub type EventCursor = (chrono::NaiveDateTime, Uuid);
+
+/// Which events of a class are on a page of `get_events_page`
+pub struct EventPageQuery<'a> {
+    pub after: Option<chrono::NaiveDateTime>,
+    pub before: Option<chrono::NaiveDateTime>,
+    pub search: Option<&'a str>,
+    pub subject: Option<&'a str>,
+    /// The member and whether the events have been marked as done by them
+    pub done: Option<(Uuid, bool)>,
+    pub cursor: Option<EventCursor>,
+    pub descending: bool,
+    pub limit: i64,
+}
+
+/// A page of the events of a class, sorted by `(start, id)`, with the cursor of the next page.
+/// Single events are paged in the database, recurring events are expanded and merged in.
+pub fn get_events_page(
+    db: &Pool,
+    class_id: Uuid,
+    query: EventPageQuery,
+) -> ServiceResult<(Vec<EventWithNotifications>, Option<EventCursor>)> {
+    use crate::schema::event_completions::dsl::{event as cevent, event_completions, user};
+    use diesel::dsl::sql;
+    use diesel::sql_types::{Bool, Text};
+
+    let conn = db.get()?;
+
+    sql_function!(fn coalesce(a: Nullable<Timestamp>, b: Timestamp) -> Timestamp);
+
+    let filtered = || {
+        let mut filtered = events
+            .filter(class.eq(class_id).and(deleted_at.is_null()))
+            .into_boxed();
+
+        if let Some(after) = query.after {
+            filtered = filtered.filter(
+                coalesce(end, start)
+                    .gt(after)
+                    .or(rec_frequency.is_not_null()),
+            );
+        }
+        if let Some(before) = query.before {
+            filtered = filtered.filter(start.lt(before));
+        }
+        if let Some(search) = query.search {
+            filtered = filtered.filter(
+                sql::<Bool>(&format!(
+                    "{} @@ websearch_to_tsquery('german', ",
+                    SEARCH_DOCUMENT
+                ))
+                .bind::<Text, _>(search.to_string())
+                .sql(")"),
+            );
+        }
+        if let Some(event_subject) = query.subject {
+            filtered = filtered.filter(subject.eq(event_subject));
+        }
+        match query.done {
+            Some((user_id, true)) => {
+                filtered = filtered
+                    .filter(id.eq_any(event_completions.filter(user.eq(user_id)).select(cevent)))
+            }
+            Some((user_id, false)) => {
+                filtered = filtered
+                    .filter(id.ne_all(event_completions.filter(user.eq(user_id)).select(cevent)))
+            }
+            None => {}
+        }
+        filtered
+    };
+
+    let mut singles = filtered().filter(rec_frequency.is_null());
+    if let Some((cursor_start, cursor_id)) = query.cursor {
+        singles = if query.descending {
+            singles.filter(
+                start
+                    .lt(cursor_start)
+                    .or(start.eq(cursor_start).and(id.lt(cursor_id))),
+            )
+        } else {
+            singles.filter(
+                start
+                    .gt(cursor_start)
+                    .or(start.eq(cursor_start).and(id.gt(cursor_id))),
+            )
+        };
+    }
+    let singles: Vec<Event> = if query.descending {
+        singles.order((start.desc(), id.desc()))
+    } else {
+        singles.order((start.asc(), id.asc()))
+    }
+    .limit(query.limit + 1)
+    .load(&conn)?;
+
+    // occurrences that start before the cursor are not needed
+    let second = chrono::Duration::seconds(1);
+    let (after, before) = match query.cursor {
+        Some((cursor_start, _)) if query.descending => (
+            query.after,
+            Some(query.before.map_or(cursor_start + second, |before| {
+                before.min(cursor_start + second)
+            })),
+        ),
+        Some((cursor_start, _)) => (
+            Some(query.after.map_or(cursor_start - second, |after| {
+                after.max(cursor_start - second)
+            })),
+            query.before,
+        ),
+        None => (query.after, query.before),
+    };
+    let recurring: Vec<Event> = filtered().filter(rec_frequency.is_not_null()).load(&conn)?;
+    let occurrences = recurrence::expand(
+        with_notifications(&conn, recurring)?,
+        class_timezone(&conn, class_id)?,
+        after,
+        before,
+    )
+    .into_iter()
+    .filter(|(occurrence, _)| {
+        query.cursor.is_none_or(|cursor| {
+            let key = (occurrence.start, occurrence.id);
+            if query.descending {
+                key < cursor
+            } else {
+                key > cursor
+            }
+        })
+    });
+
+    let mut page = with_notifications(&conn, singles)?
+        .into_iter()
+        .chain(occurrences)
+        .collect::<Vec<_>>();
+    page.sort_by_key(|(event, _)| (event.start, event.id));
+    if query.descending {
+        page.reverse();
+    }
+
+    let limit = query.limit as usize;
+    let next = if page.len() > limit {
+        page.truncate(limit);
+        page.last().map(|(event, _)| (event.start, event.id))
+    } else {
+        None
+    };
+
+    Ok((page, next))
+}
+
+/// The document that is searched, the search index in the database uses the same expression
+const SEARCH_DOCUMENT: &str = "to_tsvector('german', name || ' ' || description)";
+
+/// Full-text search over the names and descriptions of the events of the classes, best matches first.
+/// `after` and `before` work like in the other getters.
+pub fn search_events(
+    db: &Pool,
+    class_ids: &[Uuid],
+    query: &str,
+    after: Option<chrono::NaiveDateTime>,
+    before: Option<chrono::NaiveDateTime>,
+) -> ServiceResult<Vec<EventWithNotifications>> {
+    use diesel::dsl::sql;
+    use diesel::sql_types::{Bool, Float, Text};
+
+    let conn = db.get()?;
+
+    sql_function!(fn coalesce(a: Nullable<Timestamp>, b: Timestamp) -> Timestamp);
+
+    let mut search = events
+        .filter(class.eq_any(class_ids).and(deleted_at.is_null()))
+        .filter(
+            sql::<Bool>(&format!(
+                "{} @@ websearch_to_tsquery('german', ",
+                SEARCH_DOCUMENT
+            ))
+            .bind::<Text, _>(query)
+            .sql(")"),
+        )
+        .order(
+            sql::<Float>(&format!(
+                "ts_rank({}, websearch_to_tsquery('german', ",
+                SEARCH_DOCUMENT
+            ))
+            .bind::<Text, _>(query)
+            .sql("))")
+            .desc(),
+        )
+        .into_boxed();
+
+    if let Some(after) = after {
+        search = search.filter(
+            coalesce(end, start)
+                .gt(after)
+                .or(rec_frequency.is_not_null()),
+        );
+    }
+    if let Some(before) = before {
+        search = search.filter(start.lt(before));
+    }
+
+    let vec: Vec<Event> = search.load(&conn)?;
+
+    let mut timezones = HashMap::new();
+    for class_id in class_ids {
+        timezones.insert(*class_id, class_timezone(&conn, *class_id)?);
+    }
+    Ok(with_notifications(&conn, vec)?
+        .into_iter()
+        .flat_map(|event| {
+            let timezone = timezones[&event.0.class];
+            recurrence::expand(vec![event], timezone, after, before)
+        })
+        .collect())
+}
+
+pub fn get_event_by_id(
+    db: &Pool,
+    class_id: Uuid,
+    event_id: Uuid,
+) -> ServiceResult<EventWithNotifications> {
+    let conn = db.get()?;
+
+    let event: Event = events
+        .find(event_id)
+        .filter(class.eq(class_id).and(deleted_at.is_null()))
+        .get_result(&conn)?;
+    let notifications = EventNotification::belonging_to(&event).load(&conn)?;
+
+    Ok((event, notifications))
+}
+
+/// The class of the event, for requests that don't name it
+pub fn get_event_class(db: &Pool, event_id: Uuid) -> ServiceResult<Uuid> {
+    let conn = db.get()?;
+
+    Ok(events
+        .find(event_id)
+        .filter(deleted_at.is_null())
+        .select(class)
+        .get_result(&conn)?)
+}
+
+/// Replaces the event, the old notifications are replaced too.
+/// Fails with `NotFound` if the event is not one of the class of `new_event`
+pub fn update_event(
+    db: &Pool,
+    actor: Uuid,
+    new_event: NewEvent,
+    notifications: &[chrono::NaiveDateTime],
+) -> ServiceResult<EventWithNotifications> {
+    let conn = db.get()?;
+
+    conn.transaction(|| update_event_in(&conn, actor, new_event, notifications))
+}
+
+fn update_event_in(
+    conn: &PgConnection,
+    actor: Uuid,
+    new_event: NewEvent,
+    notifications: &[chrono::NaiveDateTime],
+) -> ServiceResult<EventWithNotifications> {
+    let old = get_event_for_update(conn, new_event.id)?;
+    if old.0.class != new_event.class {
+        return Err(ServiceErr::NotFound);
+    }
+    let event: Event = new_event.save_changes(conn)?;
+    check_exam_limit(conn, &event)?;
+    let notifications = replace_notifications(conn, event.id, notifications)?;
+    let new = (event, notifications);
+    record_revision(conn, actor, RevisionKind::Update, Some(&old), Some(&new))?;
+    Ok(new)
+}
+
+pub fn insert_event(
+    db: &Pool,
+    actor: Uuid,
+    new_event: NewEvent,
+    notifications: &[chrono::NaiveDateTime],
+) -> ServiceResult<EventWithNotifications> {
+    let conn = db.get()?;
+
+    conn.transaction(|| insert_event_in(&conn, actor, new_event, notifications))
+}
+
+fn insert_event_in(
+    conn: &PgConnection,
+    actor: Uuid,
+    new_event: NewEvent,
+    notifications: &[chrono::NaiveDateTime],
+) -> ServiceResult<EventWithNotifications> {
+    let event: Event = insert_into(events).values(&new_event).get_result(conn)?;
+    check_exam_limit(conn, &event)?;
+    let notifications = replace_notifications(conn, event.id, notifications)?;
+    let new = (event, notifications);
+    record_revision(conn, actor, RevisionKind::Create, None, Some(&new))?;
+    Ok(new)
+}
+
+/// The weeks of the saved exam in which its class has more exams than its limit
+pub fn exam_warnings(db: &Pool, exam: &Event) -> ServiceResult<Vec<WorkloadWarning>> {
+    use crate::schema::classes::dsl::classes;
+    let conn = db.get()?;
+
+    let exam_class = classes.find(exam.class).get_result::<Class>(&conn)?;
+    exam_warnings_in(&conn, exam, &exam_class)
+}
+
+/// Fails with `too-many-exams` if the saved exam exceeds the strict limit of its class.
+/// The class is locked before the exams are counted, so that concurrent writes can't both stay
+/// within the limit.
+fn check_exam_limit(conn: &PgConnection, exam: &Event) -> ServiceResult<()> {
+    use crate::schema::classes::dsl::classes;
+
+    if exam.e_type != dto::EventType::EXAM {
+        return Ok(());
+    }
+    let exam_class = classes
+        .find(exam.class)
+        .for_update()
+        .get_result::<Class>(conn)?;
+    if exam_class.strict_exam_limit && !exam_warnings_in(conn, exam, &exam_class)?.is_empty() {
+        return Err(ServiceErr::BadRequest("too-many-exams"));
+    }
+    Ok(())
+}
+
+fn exam_warnings_in(
+    conn: &PgConnection,
+    exam: &Event,
+    exam_class: &Class,
+) -> ServiceResult<Vec<WorkloadWarning>> {
+    if exam.e_type != dto::EventType::EXAM {
+        return Ok(vec![]);
+    }
+
+    let timezone = exam_class.tz()?;
+    let weeks = recurrence::expand(vec![(exam.clone(), vec![])], timezone, None, None)
+        .iter()
+        .map(|(occurrence, _)| workload::week_of(occurrence.start, timezone))
+        .collect::<BTreeSet<_>>();
+    let (first, last) = match (weeks.iter().next(), weeks.iter().next_back()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return Ok(vec![]),
+    };
+
+    let week_events = events_between(
+        conn,
+        exam.class,
+        workload::week_start(last + chrono::Duration::weeks(1), timezone),
+        workload::week_start(first, timezone),
+    )?
+    .into_iter()
+    .map(|(event, _)| event)
+    .collect::<Vec<_>>();
+
+    Ok(workload::exam_warnings(
+        &week_events,
+        &weeks,
+        timezone,
+        exam_class.exam_limit,
+    ))
+}
+
+/// Recurring events are expanded in the timezone of their class
+fn class_timezone(conn: &PgConnection, class_id: Uuid) -> ServiceResult<Tz> {
+    use crate::schema::classes::dsl::classes;
+
+    classes.find(class_id).get_result::<Class>(conn)?.tz()
+}
+
+/// Loads the notifications of the events, in the same order as the events
+fn with_notifications(
+    conn: &PgConnection,
+    vec: Vec<Event>,
+) -> ServiceResult<Vec<EventWithNotifications>> {
+    let notifications = EventNotification::belonging_to(&vec)
+        .load::<EventNotification>(conn)?
+        .grouped_by(&vec);
+
+    Ok(vec.into_iter().zip(notifications).collect())
+}
+
+fn replace_notifications(
+    conn: &PgConnection,
+    event_id: Uuid,
+    times: &[chrono::NaiveDateTime],
+) -> ServiceResult<Vec<EventNotification>> {
+    use crate::schema::event_notifications::dsl::{event, event_notifications};
+
+    delete(event_notifications)
+        .filter(event.eq(event_id))
+        .execute(conn)?;
+
+    let mut notifications = times
+        .iter()
+        .map(|time| EventNotification {
+            event: event_id,
+            time: *time,
+        })
+        .collect::<Vec<_>>();
+    notifications.sort_unstable_by_key(|notif| notif.time);
+    notifications.dedup();
+
+    Ok(insert_into(event_notifications)
+        .values(&notifications)
+        .get_results(conn)?)
+}
+
+/// Moves the event to the trash, if it is recurring, the whole series is deleted.
+/// Events of other classes are not deleted
+pub fn delete_event(
+    db: &Pool,
+    actor: Uuid,
+    class_id: Uuid,
+    event_id: Uuid,
+) -> ServiceResult<usize> {
+    let conn = db.get()?;
+
+    conn.transaction(|| {
+        let old = match get_event_for_update(&conn, event_id) {
+            Ok(old) if old.0.class == class_id => old,
+            Ok(_) | Err(ServiceErr::NotFound) => return Ok(0),
+            Err(err) => return Err(err),
+        };
+        delete_event_in(&conn, actor, old)
+    })
+}
+
+fn delete_event_in(
+    conn: &PgConnection,
+    actor: Uuid,
+    old: EventWithNotifications,
+) -> ServiceResult<usize> {
+    let event_id = old.0.id;
+    let now = chrono::Utc::now().naive_utc();
+    let deleted = update(events.find(event_id))
+        .set(deleted_at.eq(now))
+        .execute(conn)?;
+    // the detached occurrences are deleted together with the series
+    update(events.filter(series.eq(event_id).and(deleted_at.is_null())))
+        .set(deleted_at.eq(now))
+        .execute(conn)?;
+    record_revision(conn, actor, RevisionKind::Delete, Some(&old), None)?;
+    Ok(deleted)
+}
+
+/// An operation of a batch, the `class` of the events is ignored
+pub enum BatchOperation<'a> {
+    Create(NewEvent<'a>, &'a [chrono::NaiveDateTime]),
+    /// The `series` and `import_uid` of the event are kept
+    Update(NewEvent<'a>, &'a [chrono::NaiveDateTime]),
+    Delete(Uuid),
+}
+
+pub enum BatchOutcome {
+    Created(EventWithNotifications),
+    Updated(EventWithNotifications),
+    Deleted(Uuid),
+}
+
+/// Why the transaction of a batch was rolled back
+enum BatchRollback {
+    /// An operation failed, its error is in the results
+    OperationFailed,
+    Failed(ServiceErr),
+}
+
+impl From<diesel::result::Error> for BatchRollback {
+    fn from(err: diesel::result::Error) -> Self {
+        Self::Failed(err.into())
+    }
+}
+
+/// Runs the operations in a single transaction.
+/// Without `best_effort`, the first failed operation rolls back the whole batch and the
+/// remaining operations are not run. With `best_effort`, only the failed operations are
+/// rolled back.
+///
+/// Returns whether the batch was committed and the results of the operations that were run.
+pub fn batch_events(
+    db: &Pool,
+    actor: Uuid,
+    class_id: Uuid,
+    operations: Vec<BatchOperation>,
+    best_effort: bool,
+) -> ServiceResult<(bool, Vec<ServiceResult<BatchOutcome>>)> {
+    let conn = db.get()?;
+    let mut results = Vec::with_capacity(operations.len());
+
+    let batch = conn.transaction(|| {
+        for operation in operations {
+            // a nested transaction is a savepoint, a failed operation doesn't abort the batch
+            let result =
+                conn.transaction(|| run_batch_operation(&conn, actor, class_id, operation));
+            let failed = result.is_err();
+            results.push(result);
+            if failed && !best_effort {
+                return Err(BatchRollback::OperationFailed);
+            }
+        }
+        Ok(())
+    });
+
+    match batch {
+        Ok(()) => Ok((true, results)),
+        Err(BatchRollback::OperationFailed) => Ok((false, results)),
+        Err(BatchRollback::Failed(err)) => Err(err),
+    }
+}
+
+fn run_batch_operation(
+    conn: &PgConnection,
+    actor: Uuid,
+    class_id: Uuid,
+    operation: BatchOperation,
+) -> ServiceResult<BatchOutcome> {
+    match operation {
+        BatchOperation::Create(new_event, notifications) => {
+            let new_event = NewEvent {
+                class: class_id,
+                ..new_event
+            };
+            insert_event_in(conn, actor, new_event, notifications).map(BatchOutcome::Created)
+        }
+        BatchOperation::Update(new_event, notifications) => {
+            let (old, _) = get_event_for_update(conn, new_event.id)?;
+            if old.class != class_id {
+                return Err(ServiceErr::NotFound);
+            }
+            let new_event = NewEvent {
+                class: class_id,
+                series: old.series,
+                import_uid: old.import_uid.as_deref(),
+                ..new_event
+            };
+            update_event_in(conn, actor, new_event, notifications).map(BatchOutcome::Updated)
+        }
+        BatchOperation::Delete(event_id) => {
+            let old = get_event_for_update(conn, event_id)?;
+            if old.0.class != class_id {
+                return Err(ServiceErr::NotFound);
+            }
+            delete_event_in(conn, actor, old)?;
+            Ok(BatchOutcome::Deleted(event_id))
+        }
+    }
+}
+
+/// The deleted events of the class, the most recently deleted first
+pub fn get_deleted_events(db: &Pool, class_id: Uuid) -> ServiceResult<Vec<EventWithNotifications>> {
+    let conn = db.get()?;
+
+    let deleted: Vec<Event> = events
+        .filter(class.eq(class_id).and(deleted_at.is_not_null()))
+        .order(deleted_at.desc())
         .load(&conn)?;
+    let notifications = EventNotification::belonging_to(&deleted)
+        .load(&conn)?
+        .grouped_by(&deleted);
+
+    Ok(deleted.into_iter().zip(notifications).collect())
+}
+
+/// Takes the event out of the trash, together with the detached occurrences that were deleted with it
+pub fn restore_event(
+    db: &Pool,
+    actor: Uuid,
+    class_id: Uuid,
+    event_id: Uuid,
+) -> ServiceResult<EventWithNotifications> {
+    let conn = db.get()?;
+
+    conn.transaction(|| {
+        let old_deleted_at = events
+            .find(event_id)
+            .filter(class.eq(class_id))
+            .select(deleted_at)
+            .for_update()
+            .get_result::<Option<chrono::NaiveDateTime>>(&conn)?;
+        let event: Event = update(events.find(event_id))
+            .filter(class.eq(class_id).and(deleted_at.is_not_null()))
+            .set(deleted_at.eq(None::<chrono::NaiveDateTime>))
+            .get_result(&conn)?;
+        update(events.filter(series.eq(event_id).and(deleted_at.eq(old_deleted_at))))
+            .set(deleted_at.eq(None::<chrono::NaiveDateTime>))
+            .execute(&conn)?;
+        let notifications = EventNotification::belonging_to(&event).load(&conn)?;
+        let restored = (event, notifications);
+        record_revision(&conn, actor, RevisionKind::Restore, None, Some(&restored))?;
+        Ok(restored)
+    })
+}
+
+/// Removes a single occurrence from a recurring event of the class
+pub fn exclude_occurrence(
+    db: &Pool,
+    actor: Uuid,
+    class_id: Uuid,
+    event_id: Uuid,
+    occurrence: chrono::NaiveDateTime,
+) -> ServiceResult<Event> {
+    let conn = db.get()?;
+
+    conn.transaction(|| exclude_occurrence_in(&conn, actor, class_id, event_id, occurrence))
+}
+
+/// Replaces a single occurrence of a recurring event with a standalone event
+/// The `series` of the new event has to be set to the recurring event, which has to be in the same class
+pub fn detach_occurrence(
+    db: &Pool,
+    actor: Uuid,
+    occurrence: chrono::NaiveDateTime,
+    new_event: NewEvent,
+    notifications: &[chrono::NaiveDateTime],
+) -> ServiceResult<EventWithNotifications> {
+    let series_id = new_event
+        .series
+        .ok_or(ServiceErr::BadRequest("no-series"))?;
+    let conn = db.get()?;
 
-    Ok(vec)
+    conn.transaction(|| {
+        exclude_occurrence_in(&conn, actor, new_event.class, series_id, occurrence)?;
+        let event: Event = insert_into(events).values(&new_event).get_result(&conn)?;
+        check_exam_limit(&conn, &event)?;
+        let notifications = replace_notifications(&conn, event.id, notifications)?;
+        let new = (event, notifications);
+        record_revision(&conn, actor, RevisionKind::Create, None, Some(&new))?;
+        Ok(new)
+    })
 }
 
-pub fn get_event_by_id(db: &Pool, event_id: Uuid) -> ServiceResult<Event> {
+/// Imports the events into a class, all of them need an `import_uid`.
+/// Events that have been imported before are replaced, matched by their `import_uid`.
+/// With `preview`, nothing is written.
+/// Returns the amount of created and of updated events.
+pub fn import_events(
+    db: &Pool,
+    actor: Uuid,
+    class_id: Uuid,
+    new_events: Vec<(NewEvent, &[chrono::NaiveDateTime])>,
+    preview: bool,
+) -> ServiceResult<(usize, usize)> {
     let conn = db.get()?;
 
-    Ok(events.find(event_id).get_result(&conn)?)
+    conn.transaction(|| {
+        let mut created = 0;
+        let mut updated = 0;
+
+        for (new_event, notifications) in new_events {
+            let uid = new_event
+                .import_uid
+                .ok_or(ServiceErr::BadRequest("no-import-uid"))?;
+            let existing: Option<Event> = events
+                .filter(class.eq(class_id).and(import_uid.eq(uid)))
+                .first(&conn)
+                .optional()?;
+
+            match existing {
+                // deleted events are not imported again
+                Some(Event {
+                    deleted_at: Some(_),
+                    ..
+                }) => {}
+                Some(existing) => {
+                    updated += 1;
+                    // calendars have no subjects, the subject is kept
+                    let new_event = NewEvent {
+                        id: existing.id,
+                        subject: existing.subject.as_deref(),
+                        subject_id: existing.subject_id,
+                        ..new_event
+                    };
+                    if !preview {
+                        let old = get_event_for_update(&conn, existing.id)?;
+                        let event: Event = new_event.save_changes(&*conn)?;
+                        let notifications =
+                            replace_notifications(&conn, existing.id, notifications)?;
+                        let new = (event, notifications);
+                        record_revision(
+                            &conn,
+                            actor,
+                            RevisionKind::Update,
+                            Some(&old),
+                            Some(&new),
+                        )?;
+                    }
+                }
+                None => {
+                    created += 1;
+                    if !preview {
+                        let event: Event =
+                            insert_into(events).values(&new_event).get_result(&conn)?;
+                        let notifications =
+                            replace_notifications(&conn, new_event.id, notifications)?;
+                        let new = (event, notifications);
+                        record_revision(&conn, actor, RevisionKind::Create, None, Some(&new))?;
+                    }
+                }
+            }
+        }
+
+        Ok((created, updated))
+    })
 }
 
-pub fn update_event(db: &Pool, new_event: NewEvent) -> ServiceResult<Event> {
+/// Fails with `NotFound` if the event is not one of the class
+fn exclude_occurrence_in(
+    conn: &PgConnection,
+    actor: Uuid,
+    class_id: Uuid,
+    event_id: Uuid,
+    occurrence: chrono::NaiveDateTime,
+) -> ServiceResult<Event> {
+    let old = get_event_for_update(conn, event_id)?;
+
+    if old.0.class != class_id
+        || !recurrence::is_occurrence(&old.0, class_timezone(conn, class_id)?, occurrence)
+    {
+        return Err(ServiceErr::NotFound);
+    }
+
+    let mut exceptions = old.0.rec_exceptions.clone();
+    exceptions.push(occurrence);
+
+    let event: Event = update(events.find(event_id))
+        .set(rec_exceptions.eq(exceptions))
+        .get_result(conn)?;
+    let new = (event, old.1.clone());
+    record_revision(conn, actor, RevisionKind::Update, Some(&old), Some(&new))?;
+
+    Ok(new.0)
+}
+
+/// The revisions of an event, oldest first
+pub fn get_event_history(
+    db: &Pool,
+    class_id: Uuid,
+    event_id: Uuid,
+) -> ServiceResult<Vec<EventRevision>> {
+    use crate::schema::event_revisions::dsl::{
+        class as rclass, event as revent, event_revisions, id as rid,
+    };
+
     let conn = db.get()?;
 
-    Ok(new_event.save_changes(&*conn)?)
+    Ok(event_revisions
+        .filter(rclass.eq(class_id).and(revent.eq(event_id)))
+        .order(rid)
+        .load(&conn)?)
 }
 
-pub fn insert_event(db: &Pool, new_event: NewEvent) -> ServiceResult<Event> {
+pub fn get_event_revision(
+    db: &Pool,
+    class_id: Uuid,
+    event_id: Uuid,
+    revision_id: i32,
+) -> ServiceResult<EventRevision> {
+    use crate::schema::event_revisions::dsl::{class as rclass, event as revent, event_revisions};
+
     let conn = db.get()?;
 
-    Ok(insert_into(events).values(&new_event).get_result(&conn)?)
+    Ok(event_revisions
+        .find(revision_id)
+        .filter(rclass.eq(class_id).and(revent.eq(event_id)))
+        .get_result(&conn)?)
 }
 
-pub fn delete_event(db: &Pool, event_id: Uuid) -> ServiceResult<usize> {
+/// Replaces the event with an older version of it, a deleted event is restored.
+/// The `series` and `import_uid` of the event are kept.
+pub fn revert_event(
+    db: &Pool,
+    actor: Uuid,
+    new_event: NewEvent,
+    notifications: &[chrono::NaiveDateTime],
+) -> ServiceResult<EventWithNotifications> {
     let conn = db.get()?;
 
-    Ok(delete(events).filter(id.eq(event_id)).execute(&conn)?)
+    conn.transaction(|| {
+        let existing: Option<Event> = events
+            .find(new_event.id)
+            .for_update()
+            .get_result(&conn)
+            .optional()?;
+        let old = match &existing {
+            Some(event) if event.deleted_at.is_none() => Some((
+                event.clone(),
+                EventNotification::belonging_to(event).load(&conn)?,
+            )),
+            _ => None,
+        };
+
+        let event: Event = match &existing {
+            Some(existing) => {
+                let new_event = NewEvent {
+                    series: existing.series,
+                    import_uid: existing.import_uid.as_deref(),
+                    ..new_event
+                };
+                update(events.find(new_event.id))
+                    .set((&new_event, deleted_at.eq(None::<chrono::NaiveDateTime>)))
+                    .get_result(&conn)?
+            }
+            None => insert_into(events).values(&new_event).get_result(&conn)?,
+        };
+        let notifications = replace_notifications(&conn, event.id, notifications)?;
+        let new = (event, notifications);
+        record_revision(&conn, actor, RevisionKind::Revert, old.as_ref(), Some(&new))?;
+        Ok(new)
+    })
+}
+
+/// Locks the event until the end of the transaction
+fn get_event_for_update(
+    conn: &PgConnection,
+    event_id: Uuid,
+) -> ServiceResult<EventWithNotifications> {
+    let event: Event = events
+        .find(event_id)
+        .filter(deleted_at.is_null())
+        .for_update()
+        .get_result(conn)?;
+    let notifications = EventNotification::belonging_to(&event).load(conn)?;
+    Ok((event, notifications))
+}
+
+/// `old` is `None` for created events, `new` for deleted events
+fn record_revision(
+    conn: &PgConnection,
+    actor: Uuid,
+    kind: RevisionKind,
+    old: Option<&EventWithNotifications>,
+    new: Option<&EventWithNotifications>,
+) -> ServiceResult<()> {
+    use crate::schema::event_revisions::dsl::event_revisions;
+
+    let (event_id, class_id) = match new.or(old) {
+        Some((event, _)) => (event.id, event.class),
+        None => return Ok(()),
+    };
+
+    let old: Option<dto::Event> = old.cloned().map(IntoDto::into_dto).transpose()?;
+    let new: Option<dto::Event> = new.cloned().map(IntoDto::into_dto).transpose()?;
+
+    let serialize_err = |_| ServiceErr::InternalServerError("serialize-revision".to_string());
+    let changes =
+        serde_json::to_string(&history::diff(old.as_ref(), new.as_ref())).map_err(serialize_err)?;
+    let snapshot = new
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(serialize_err)?;
+
+    insert_into(event_revisions)
+        .values(NewEventRevision {
+            event: event_id,
+            class: class_id,
+            // the bot has no user
+            actor: Some(actor).filter(|actor| !actor.is_nil()),
+            kind: kind.as_str(),
+            time: chrono::Utc::now().naive_utc(),
+            changes: &changes,
+            snapshot: snapshot.as_deref(),
+        })
+        .execute(conn)?;
+
+    Ok(())
 }
 
-pub type Notifications = (chrono::NaiveDateTime, Vec<(Event, (Class, Guild))>);
+/// A due notification, with the event it belongs to
+pub type DueNotification = (
+    chrono::NaiveDateTime,
+    EventWithNotifications,
+    (Class, Guild),
+);
 
+pub type Notifications = (chrono::NaiveDateTime, Vec<DueNotification>);
+
+/// Every notification is returned on its own, an event with several due notifications is returned
+/// once for each of them
 pub fn get_notifications(db: &Pool, since: chrono::NaiveDateTime) -> ServiceResult<Notifications> {
-    use crate::schema::classes::dsl::{classes, discord_id};
+    use crate::schema::classes::dsl::{classes, deleted_at as class_deleted_at, discord_id};
+    use crate::schema::event_notifications::dsl::{event, event_notifications, time};
     use crate::schema::guilds::dsl::{guilds, id as gid, notif_channel};
 
     let conn = db.get()?;
@@ -105,16 +1039,196 @@ pub fn get_notifications(db: &Pool, since: chrono::NaiveDateTime) -> ServiceResu
 
     sql_function!(fn coalesce(a: Nullable<VarChar>, b: VarChar) -> VarChar);
 
-    let notifications = events
+    let single: Vec<(Event, (Class, Guild))> = events
+        .inner_join(classes.inner_join(guilds.on(coalesce(discord_id, "").eq(gid))))
+        .filter(
+            id.eq_any(
+                event_notifications
+                    .select(event)
+                    .filter(time.lt(current_time).and(time.gt(since))),
+            )
+            .and(rec_frequency.is_null())
+            .and(notif_channel.is_not_null()),
+        )
+        .filter(deleted_at.is_null().and(class_deleted_at.is_null()))
+        .load(&conn)?;
+
+    let recurring: Vec<(Event, (Class, Guild))> = events
         .inner_join(classes.inner_join(guilds.on(coalesce(discord_id, "").eq(gid))))
         .filter(
-            notification
-                .is_not_null()
-                .and(notification.lt(current_time))
-                .and(notification.gt(since))
+            id.eq_any(event_notifications.select(event))
+                .and(rec_frequency.is_not_null())
                 .and(notif_channel.is_not_null()),
         )
+        .filter(deleted_at.is_null().and(class_deleted_at.is_null()))
         .load(&conn)?;
 
+    let (single, single_guilds): (Vec<_>, Vec<_>) = single.into_iter().unzip();
+    let mut notifications = with_notifications(&conn, single)?
+        .into_iter()
+        .zip(single_guilds)
+        .flat_map(|(single, class_guild)| {
+            single
+                .1
+                .iter()
+                .map(|notif| notif.time)
+                .filter(|notif| *notif > since && *notif < current_time)
+                .map(|notif| (notif, single.clone(), class_guild.clone()))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let (recurring, recurring_guilds): (Vec<_>, Vec<_>) = recurring.into_iter().unzip();
+    for ((recurring, notifs), class_guild) in with_notifications(&conn, recurring)?
+        .into_iter()
+        .zip(recurring_guilds)
+    {
+        let timezone = class_guild.0.tz()?;
+        notifications.extend(
+            recurrence::due_notifications(&recurring, timezone, &notifs, since, current_time)
+                .into_iter()
+                .map(|(notif, occurrence)| (notif, occurrence, class_guild.clone())),
+        );
+    }
+
     Ok((current_time, notifications))
 }
+
+/// The built-in event types and the types of the class
+pub fn get_event_types(db: &Pool, class_id: Uuid) -> ServiceResult<Vec<EventType>> {
+    use crate::schema::event_types::dsl::{class as tclass, event_types, id as tid};
+
+    let conn = db.get()?;
+
+    Ok(event_types
+        .filter(tclass.is_null().or(tclass.eq(class_id)))
+        .order(tid)
+        .load(&conn)?)
+}
+
+/// Returns `NotFound` if the type is neither built-in nor a type of the class
+pub fn get_event_type(db: &Pool, class_id: Uuid, type_id: i32) -> ServiceResult<EventType> {
+    use crate::schema::event_types::dsl::{class as tclass, event_types};
+
+    let conn = db.get()?;
+
+    Ok(event_types
+        .find(type_id)
+        .filter(tclass.is_null().or(tclass.eq(class_id)))
+        .get_result(&conn)?)
+}
+
+pub fn insert_event_type(db: &Pool, new_type: NewEventType) -> ServiceResult<EventType> {
+    use crate::schema::event_types::dsl::event_types;
+
+    let conn = db.get()?;
+
+    Ok(insert_into(event_types)
+        .values(&new_type)
+        .get_result(&conn)?)
+}
+
+/// Built-in types can't be updated
+pub fn update_event_type(
+    db: &Pool,
+    type_id: i32,
+    new_type: NewEventType,
+) -> ServiceResult<EventType> {
+    use crate::schema::event_types::dsl::{class as tclass, event_types};
+
+    let conn = db.get()?;
+
+    Ok(
+        update(event_types.find(type_id).filter(tclass.eq(new_type.class)))
+            .set(&new_type)
+            .get_result(&conn)?,
+    )
+}
+
+/// The events of the deleted type become events of the built-in type `other`.
+/// Built-in types can't be deleted.
+pub fn delete_event_type(db: &Pool, class_id: Uuid, type_id: i32) -> ServiceResult<usize> {
+    use crate::schema::event_types::dsl::{class as tclass, event_types};
+
+    let conn = db.get()?;
+
+    conn.transaction(|| {
+        let found = event_types
+            .find(type_id)
+            .filter(tclass.eq(class_id))
+            .for_update()
+            .execute(&conn)?;
+        if found == 0 {
+            return Ok(0);
+        }
+
+        update(events.filter(e_type.eq(type_id)))
+            .set(e_type.eq(dto::EventType::OTHER))
+            .execute(&conn)?;
+
+        Ok(delete(event_types.find(type_id)).execute(&conn)?)
+    })
+}
+
+/// Marking an event as done twice keeps the first `done_at`
+pub fn mark_event_done(db: &Pool, completion: EventCompletion) -> ServiceResult<usize> {
+    use crate::schema::event_completions::dsl::event_completions;
+
+    let conn = db.get()?;
+
+    Ok(insert_into(event_completions)
+        .values(&completion)
+        .on_conflict_do_nothing()
+        .execute(&conn)?)
+}
+
+pub fn unmark_event_done(db: &Pool, event_id: Uuid, user_id: Uuid) -> ServiceResult<usize> {
+    use crate::schema::event_completions::dsl::{event as cevent, event_completions, user};
+
+    let conn = db.get()?;
+
+    Ok(
+        delete(event_completions.filter(cevent.eq(event_id).and(user.eq(user_id))))
+            .execute(&conn)?,
+    )
+}
+
+/// The ids of the events of the classes that the member has marked as done
+pub fn get_done_events(db: &Pool, class_ids: &[Uuid], user_id: Uuid) -> ServiceResult<Vec<Uuid>> {
+    use crate::schema::event_completions::dsl::{
+        class as cclass, event as cevent, event_completions, user,
+    };
+
+    let conn = db.get()?;
+
+    Ok(event_completions
+        .filter(cclass.eq_any(class_ids).and(user.eq(user_id)))
+        .select(cevent)
+        .load(&conn)?)
+}
+
+/// How many members have marked the events of the class as done, without who.
+/// Events that no member has done are missing.
+pub fn get_completion_counts(
+    db: &Pool,
+    class_id: Uuid,
+    event_ids: &[Uuid],
+) -> ServiceResult<HashMap<Uuid, i64>> {
+    use crate::schema::event_completions::dsl::{
+        class as cclass, event as cevent, event_completions,
+    };
+    use diesel::dsl::sql;
+    use diesel::query_dsl::GroupByDsl;
+    use diesel::sql_types::BigInt;
+
+    let conn = db.get()?;
+
+    let counts: Vec<(Uuid, i64)> = event_completions
+        .filter(cclass.eq(class_id).and(cevent.eq_any(event_ids)))
+        // diesel can't mix aggregates with grouped columns yet
+        .select((cevent, sql::<BigInt>("COUNT(*)")))
+        .group_by(cevent)
+        .load(&conn)?;
+
+    Ok(counts.into_iter().collect())
+}
@@ -0,0 +1,43 @@
+use super::Pool;
+use crate::diesel::{QueryDsl, RunQueryDsl};
+use crate::error::ServiceResult;
+use crate::models::{Event, Grade};
+use crate::schema::grades::dsl::*;
+use diesel::{delete, insert_into, BoolExpressionMethods, ExpressionMethods};
+use uuid::Uuid;
+
+/// The grades of the user in the classes together with their exams, sorted by the start of the exams.
+/// Grades of deleted exams are not included.
+pub fn get_grades(
+    db: &Pool,
+    user_id: Uuid,
+    class_ids: &[Uuid],
+) -> ServiceResult<Vec<(Grade, Event)>> {
+    use crate::schema::events::dsl::{deleted_at, events, start};
+    let conn = db.get()?;
+
+    Ok(grades
+        .inner_join(events)
+        .filter(user.eq(user_id).and(class.eq_any(class_ids)))
+        .filter(deleted_at.is_null())
+        .order(start)
+        .load(&conn)?)
+}
+
+/// Replaces the grade of the user for the exam
+pub fn set_grade(db: &Pool, new_grade: Grade) -> ServiceResult<Grade> {
+    let conn = db.get()?;
+
+    Ok(insert_into(grades)
+        .values(&new_grade)
+        .on_conflict((user, event))
+        .do_update()
+        .set(&new_grade)
+        .get_result(&conn)?)
+}
+
+pub fn delete_grade(db: &Pool, user_id: Uuid, event_id: Uuid) -> ServiceResult<usize> {
+    let conn = db.get()?;
+
+    Ok(delete(grades.filter(user.eq(user_id).and(event.eq(event_id)))).execute(&conn)?)
+}
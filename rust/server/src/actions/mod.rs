@@ -3,6 +3,9 @@ use diesel::PgConnection;
 
 pub mod class;
 pub mod event;
+pub mod grade;
+pub mod subject;
+pub mod timetable;
 pub mod user;
 
 type Connection = ConnectionManager<PgConnection>;
@@ -16,12 +19,32 @@ mod test {
     use super::class::*;
     use super::user::*;
     use crate::actions::event::{
+        batch_events, delete_event, delete_event_type, detach_occurrence, exam_warnings,
+        exclude_occurrence, get_completion_counts, get_deleted_events, get_done_events,
+        get_event_by_id, get_event_history, get_event_revision, get_event_type, get_event_types,
         get_events_by_class, get_events_by_class_filtered_after,
-        get_events_by_class_filtered_before, get_events_by_class_filtered_both, insert_event,
+        get_events_by_class_filtered_before, get_events_by_class_filtered_both, get_events_page,
+        get_notifications, import_events, insert_event, insert_event_type, mark_event_done,
+        restore_event, revert_event, search_events, unmark_event_done, update_event,
+        update_event_type, BatchOperation, BatchOutcome, EventPageQuery,
+    };
+    use crate::actions::grade::{delete_grade, get_grades, set_grade};
+    use crate::actions::subject::{
+        delete_subject, get_subject, get_subjects, insert_subject, update_subject,
+    };
+    use crate::actions::timetable::{
+        create_timetable, delete_exception, delete_lesson, delete_timetable, delete_version,
+        get_exceptions, get_lesson, get_timetable, get_versions, insert_exception, insert_lesson,
+        insert_version, update_exception, update_lesson, update_timetable, update_timetable_cycle,
+        update_version,
     };
     use crate::actions::Pool;
+    use crate::error::ServiceErr;
     use crate::models;
-    use crate::models::{Class, NewClass, NewEvent, NewMember, NewUser, User};
+    use crate::models::conversion::into_timetable;
+    use crate::models::{
+        Class, NewClass, NewEvent, NewEventType, NewGuild, NewMember, NewUser, User,
+    };
     use chrono::NaiveDateTime;
     use dto::{Lesson, Timetable};
 
@@ -68,6 +91,9 @@ mod test {
                 name: "testklasse",
                 description: "",
                 discord_id: Some("4387208542528543"),
+                exam_limit: 3,
+                strict_exam_limit: false,
+                timezone: "Europe/Zurich",
             },
         )
         .unwrap();
@@ -101,7 +127,30 @@ mod test {
         assert_eq!(full_class.id, class.id);
         assert_eq!(full_class.name, class.name);
         assert_eq!(full_class.description, class.description);
+        assert_eq!(full_class.exam_limit, 3);
+        assert!(!full_class.strict_exam_limit);
+        assert_eq!(full_class.timezone, "Europe/Zurich");
         assert_eq!(members.len(), 1);
+
+        let strict_class = update_class(
+            &db,
+            NewClass {
+                id: class.id,
+                owner: user.id,
+                name: "testklasse",
+                description: "streng",
+                discord_id: None,
+                exam_limit: 2,
+                strict_exam_limit: true,
+                timezone: "Europe/Berlin",
+            },
+        )
+        .unwrap();
+        assert_eq!(strict_class.exam_limit, 2);
+        assert!(strict_class.strict_exam_limit);
+        assert_eq!(strict_class.timezone, "Europe/Berlin");
+        // the discord id is not changed by updates
+        assert_eq!(strict_class.discord_id, class.discord_id);
         let (member, _) = members.into_iter().next().unwrap();
         assert_eq!(member.user, user.id);
 
@@ -116,40 +165,467 @@ mod test {
 
         let (user, class) = insert_class_user(&db);
 
-        let timetable = create_timetable(&db, class.id).unwrap();
-        assert_eq!(&*timetable.timetable, "[[],[],[],[],[],[],[]]");
+        assert!(matches!(
+            get_timetable(&db, class.id),
+            Err(ServiceErr::NotFound)
+        ));
+        create_timetable(&db, class.id).unwrap();
+        assert!(get_timetable(&db, class.id).unwrap().1.is_empty());
+        let version = get_versions(&db, class.id).unwrap()[0].id;
 
+        let lesson = |subject: &str, start| Lesson {
+            id: None,
+            subject: subject.to_string(),
+            subject_id: None,
+            description: "".to_string(),
+            start,
+            end: start + 100,
+            room: "B204".to_string(),
+            teacher: "".to_string(),
+            week: None,
+        };
         let new_timetable: Timetable = [
-            vec![Lesson {
-                subject: "subj".to_string(),
-                description: "".to_string(),
-                start: 100,
-                end: 200,
-            }],
-            vec![],
+            vec![lesson("subj", 100), lesson("other", 300)],
             vec![],
             vec![],
             vec![],
+            vec![lesson("friday", 100)],
             vec![],
             vec![],
         ];
-        let new_timetable_json = serde_json::to_string(&new_timetable).unwrap();
 
-        let updated_table = update_timetable(
+        fn new_lessons(
+            class_id: uuid::Uuid,
+            version: uuid::Uuid,
+            table: &Timetable,
+        ) -> Vec<(Option<uuid::Uuid>, models::NewLesson<'_>)> {
+            table
+                .iter()
+                .enumerate()
+                .flat_map(|(weekday, day)| {
+                    day.iter().map(move |lesson| {
+                        (
+                            lesson.id,
+                            models::NewLesson::from_dto(class_id, version, weekday as u8, lesson),
+                        )
+                    })
+                })
+                .collect()
+        }
+
+        let lessons = update_timetable(
             &db,
-            models::Timetable {
-                class: class.id,
-                timetable: new_timetable_json,
-            },
+            class.id,
+            version,
+            &new_lessons(class.id, version, &new_timetable),
+        )
+        .unwrap();
+        let mut timetable = into_timetable(lessons).unwrap();
+        assert_eq!(timetable[0].len(), 2);
+        assert_eq!(timetable[0][1].subject, "other");
+        assert_eq!(timetable[0][1].room, "B204");
+        assert_eq!(timetable[4][0].subject, "friday");
+
+        // lessons that are sent with their id keep it
+        let kept_id = timetable[0][1].id;
+        timetable[0].remove(0);
+        timetable[0][0].teacher = "Teacher".to_string();
+        timetable[2].push(lesson("new", 500));
+        let lessons = update_timetable(
+            &db,
+            class.id,
+            version,
+            &new_lessons(class.id, version, &timetable),
         )
         .unwrap();
+        assert_eq!(lessons.len(), 3);
+        assert_eq!(Some(lessons[0].id), kept_id);
+        assert_eq!(lessons[0].teacher, "Teacher");
+        assert_eq!(lessons[1].weekday, 2);
 
-        assert_eq!(updated_table.class, class.id);
+        let monday = dto::Lesson {
+            subject: "monday".to_string(),
+            ..lesson("", 0)
+        };
+        let inserted = insert_lesson(
+            &db,
+            models::NewLesson::from_dto(class.id, version, 0, &monday),
+        )
+        .unwrap();
+        assert_eq!(get_timetable(&db, class.id).unwrap().1[0].id, inserted.id);
+        let updated = update_lesson(
+            &db,
+            inserted.id,
+            models::NewLesson::from_dto(class.id, version, 6, &monday),
+        )
+        .unwrap();
+        assert_eq!(updated.weekday, 6);
+        assert_eq!(get_lesson(&db, class.id, inserted.id).unwrap().weekday, 6);
+        assert_eq!(delete_lesson(&db, class.id, inserted.id).unwrap(), 1);
+        assert_eq!(delete_lesson(&db, class.id, inserted.id).unwrap(), 0);
+
+        let anchor = chrono::NaiveDate::from_ymd_opt(2021, 8, 23).unwrap();
+        let cycle = update_timetable_cycle(&db, class.id, 2, anchor).unwrap();
+        assert_eq!(cycle.cycle_weeks, 2);
+        assert_eq!(get_timetable(&db, class.id).unwrap().0.cycle_anchor, anchor);
+        let b_week = dto::Lesson {
+            week: Some(1),
+            ..monday.clone()
+        };
+        let inserted = insert_lesson(
+            &db,
+            models::NewLesson::from_dto(class.id, version, 0, &b_week),
+        )
+        .unwrap();
+        assert_eq!(inserted.cycle_week, Some(1));
+        let c_week = dto::Lesson {
+            week: Some(2),
+            ..monday.clone()
+        };
+        assert!(matches!(
+            insert_lesson(
+                &db,
+                models::NewLesson::from_dto(class.id, version, 0, &c_week)
+            ),
+            Err(ServiceErr::BadRequest("invalid-cycle-week"))
+        ));
+        assert!(matches!(
+            update_timetable_cycle(&db, class.id, 1, anchor),
+            Err(ServiceErr::BadRequest("lessons-outside-cycle"))
+        ));
+        let every_week = update_lesson(
+            &db,
+            inserted.id,
+            models::NewLesson::from_dto(class.id, version, 0, &monday),
+        )
+        .unwrap();
+        assert_eq!(every_week.cycle_week, None);
+        update_timetable_cycle(&db, class.id, 1, anchor).unwrap();
+
+        assert_eq!(delete_timetable(&db, class.id).unwrap(), 1);
+        assert!(matches!(
+            insert_lesson(
+                &db,
+                models::NewLesson::from_dto(class.id, version, 0, &monday)
+            ),
+            Err(ServiceErr::NotFound)
+        ));
+
+        delete_class(&db, class.id).unwrap();
+        delete_user(&db, user.id).unwrap();
+    }
+
+    #[test]
+    fn timetable_versions() {
+        let db = get_pool();
+
+        let (user, class) = insert_class_user(&db);
+        create_timetable(&db, class.id).unwrap();
+        let versions = get_versions(&db, class.id).unwrap();
+        assert_eq!(versions.len(), 1);
+        let first = versions[0].id;
         assert_eq!(
-            serde_json::from_str::<Timetable>(&updated_table.timetable).unwrap(),
-            new_timetable
+            (versions[0].valid_from, versions[0].valid_until),
+            (None, None)
         );
 
+        let date = |day| Some(chrono::NaiveDate::from_ymd_opt(2021, 9, day).unwrap());
+        let version = |valid_from, valid_until| models::NewTimetableVersion {
+            class: class.id,
+            valid_from,
+            valid_until,
+        };
+
+        // the version without an end ends when the new one starts
+        let second = insert_version(&db, version(date(13), None)).unwrap();
+        let versions = get_versions(&db, class.id).unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].id, first);
+        assert_eq!(versions[0].valid_until, date(12));
+        assert_eq!(versions[1].id, second.id);
+
+        assert!(matches!(
+            insert_version(&db, version(date(1), date(20))),
+            Err(ServiceErr::BadRequest("overlapping-versions"))
+        ));
+        assert!(matches!(
+            update_version(&db, first, version(None, date(13))),
+            Err(ServiceErr::BadRequest("overlapping-versions"))
+        ));
+        let shorter = update_version(&db, first, version(None, date(10))).unwrap();
+        assert_eq!(shorter.valid_until, date(10));
+
+        let math = Lesson {
+            id: None,
+            subject: "Mathe".to_string(),
+            subject_id: None,
+            description: "".to_string(),
+            start: 100,
+            end: 200,
+            room: "B204".to_string(),
+            teacher: "".to_string(),
+            week: None,
+        };
+        insert_lesson(&db, models::NewLesson::from_dto(class.id, first, 0, &math)).unwrap();
+        let lesson = insert_lesson(
+            &db,
+            models::NewLesson::from_dto(class.id, second.id, 1, &math),
+        )
+        .unwrap();
+        assert_eq!(lesson.version, second.id);
+        assert!(matches!(
+            insert_lesson(
+                &db,
+                models::NewLesson::from_dto(class.id, uuid::Uuid::new_v4(), 1, &math)
+            ),
+            Err(ServiceErr::BadRequest("invalid-version"))
+        ));
+
+        // replacing the lessons of a version keeps the lessons of the other versions
+        let lessons = update_timetable(&db, class.id, second.id, &[]).unwrap();
+        assert!(lessons.is_empty());
+        let lessons = get_timetable(&db, class.id).unwrap().1;
+        assert_eq!(lessons.len(), 1);
+        assert_eq!(lessons[0].version, first);
+
+        // deleting a version deletes its lessons
+        assert_eq!(delete_version(&db, class.id, first).unwrap(), 1);
+        assert_eq!(delete_version(&db, class.id, first).unwrap(), 0);
+        assert!(get_timetable(&db, class.id).unwrap().1.is_empty());
+
+        delete_class(&db, class.id).unwrap();
+        delete_user(&db, user.id).unwrap();
+    }
+
+    #[test]
+    fn timetable_exceptions() {
+        let db = get_pool();
+
+        let (user, class) = insert_class_user(&db);
+        create_timetable(&db, class.id).unwrap();
+        let version = get_versions(&db, class.id).unwrap()[0].id;
+        let math = Lesson {
+            id: None,
+            subject: "Mathe".to_string(),
+            subject_id: None,
+            description: "".to_string(),
+            start: 100,
+            end: 200,
+            room: "B204".to_string(),
+            teacher: "".to_string(),
+            week: None,
+        };
+        let lesson = insert_lesson(
+            &db,
+            models::NewLesson::from_dto(class.id, version, 0, &math),
+        )
+        .unwrap();
+
+        let date = |day| chrono::NaiveDate::from_ymd_opt(2021, 8, day).unwrap();
+        let exception = |day, lesson| dto::TimetableException {
+            id: None,
+            date: date(day).and_hms_opt(0, 0, 0).unwrap().into(),
+            lesson,
+            cancelled: false,
+            subject: None,
+            room: Some("A101".to_string()),
+            teacher: None,
+            start: None,
+            end: None,
+            description: "".to_string(),
+        };
+        let changed = exception(23, Some(lesson.id));
+        let changed = insert_exception(
+            &db,
+            models::NewTimetableException::from_dto(class.id, &changed),
+        )
+        .unwrap();
+        assert_eq!(changed.date, date(23));
+        assert_eq!(changed.room.as_deref(), Some("A101"));
+        let extra = dto::TimetableException {
+            subject: Some("Physik".to_string()),
+            start: Some(300),
+            end: Some(400),
+            ..exception(30, None)
+        };
+        insert_exception(
+            &db,
+            models::NewTimetableException::from_dto(class.id, &extra),
+        )
+        .unwrap();
+
+        assert_eq!(get_exceptions(&db, class.id, None, None).unwrap().len(), 2);
+        let first_week = get_exceptions(&db, class.id, Some(date(23)), Some(date(29))).unwrap();
+        assert_eq!(first_week.len(), 1);
+        assert_eq!(first_week[0].id, changed.id);
+
+        let cancelled = dto::TimetableException {
+            cancelled: true,
+            room: None,
+            ..exception(23, Some(lesson.id))
+        };
+        let cancelled = update_exception(
+            &db,
+            changed.id,
+            models::NewTimetableException::from_dto(class.id, &cancelled),
+        )
+        .unwrap();
+        assert!(cancelled.cancelled);
+        assert_eq!(cancelled.room, None);
+
+        // deleting the lesson deletes its exceptions
+        delete_lesson(&db, class.id, lesson.id).unwrap();
+        let exceptions = get_exceptions(&db, class.id, None, None).unwrap();
+        assert_eq!(exceptions.len(), 1);
+        assert_eq!(
+            delete_exception(&db, class.id, exceptions[0].id).unwrap(),
+            1
+        );
+        assert!(get_exceptions(&db, class.id, None, None)
+            .unwrap()
+            .is_empty());
+
+        delete_class(&db, class.id).unwrap();
+        delete_user(&db, user.id).unwrap();
+    }
+
+    #[test]
+    fn subjects() {
+        let db = get_pool();
+
+        let (user, class) = insert_class_user(&db);
+        create_timetable(&db, class.id).unwrap();
+        let version = get_versions(&db, class.id).unwrap()[0].id;
+        let lesson = |subject: &str, room: &str, start| Lesson {
+            id: None,
+            subject: subject.to_string(),
+            subject_id: None,
+            description: "".to_string(),
+            start,
+            end: start + 100,
+            room: room.to_string(),
+            teacher: "".to_string(),
+            week: None,
+        };
+        let (first, second) = (lesson("Mathe", "B204", 100), lesson(" mathe", "", 300));
+        let lessons = update_timetable(
+            &db,
+            class.id,
+            version,
+            &[
+                (
+                    None,
+                    models::NewLesson::from_dto(class.id, version, 0, &first),
+                ),
+                (
+                    None,
+                    models::NewLesson::from_dto(class.id, version, 0, &second),
+                ),
+            ],
+        )
+        .unwrap();
+
+        // differently written subjects are the same subject, its room is the default
+        let subjects = get_subjects(&db, class.id).unwrap();
+        assert_eq!(subjects.len(), 1);
+        let mathe = &subjects[0];
+        assert_eq!(
+            (mathe.name.as_str(), mathe.room.as_str()),
+            ("Mathe", "B204")
+        );
+        assert!(lessons.iter().all(|lesson| lesson.subject_id == mathe.id));
+        assert!(lessons.iter().all(|lesson| lesson.subject == "Mathe"));
+        assert_eq!(lessons[1].room, "B204");
+
+        let renamed = dto::Subject {
+            id: None,
+            name: "Mathematik".to_string(),
+            short: "M".to_string(),
+            colour: "#1e90ff".to_string(),
+            teacher: "".to_string(),
+            room: "B204".to_string(),
+        };
+        let renamed = update_subject(
+            &db,
+            mathe.id,
+            models::NewSubject::from_dto(class.id, &renamed),
+        )
+        .unwrap();
+        assert_eq!(renamed.short, "M");
+        let (_, lessons) = get_timetable(&db, class.id).unwrap();
+        assert!(lessons.iter().all(|lesson| lesson.subject == "Mathematik"));
+
+        assert!(matches!(
+            insert_subject(
+                &db,
+                models::NewSubject {
+                    class: class.id,
+                    name: "MATHEMATIK",
+                    short: "",
+                    colour: "",
+                    teacher: "",
+                    room: "",
+                },
+            ),
+            Err(ServiceErr::Conflict(_))
+        ));
+        assert!(matches!(
+            delete_subject(&db, class.id, renamed.id),
+            Err(ServiceErr::Conflict("subject-in-use"))
+        ));
+        assert!(get_subject(&db, uuid::Uuid::new_v4(), renamed.id).is_err());
+
+        // events lose the subject when it is deleted
+        let start = NaiveDateTime::from_timestamp(1000, 0);
+        let (exam, _) = insert_event(
+            &db,
+            user.id,
+            NewEvent {
+                name: "Algebra",
+                subject: Some(&renamed.name),
+                subject_id: Some(renamed.id),
+                ..NewEvent::test(class.id, &start)
+            },
+            &[],
+        )
+        .unwrap();
+        update_timetable(&db, class.id, version, &[]).unwrap();
+        assert_eq!(delete_subject(&db, class.id, renamed.id).unwrap(), 1);
+        let (exam, _) = get_event_by_id(&db, class.id, exam.id).unwrap();
+        assert_eq!((exam.subject, exam.subject_id), (None, None));
+
+        delete_class(&db, class.id).unwrap();
+        delete_user(&db, user.id).unwrap();
+    }
+
+    #[test]
+    fn feed_tokens() {
+        let db = get_pool();
+
+        let (user, class) = insert_class_user(&db);
+        create_member(
+            &db,
+            NewMember {
+                user: user.id,
+                class: class.id,
+                display_name: "member",
+                role: 0,
+            },
+        )
+        .unwrap();
+
+        let token = create_feed_token(&db, user.id, class.id).unwrap();
+        assert_eq!(token.user, user.id);
+        assert_eq!(token.class, class.id);
+
+        let new_token = create_feed_token(&db, user.id, class.id).unwrap();
+        assert_ne!(new_token.token, token.token);
+        assert!(get_feed_token(&db, token.token).is_err());
+        assert_eq!(get_feed_token(&db, new_token.token).unwrap().user, user.id);
+
+        assert_eq!(delete_feed_token(&db, user.id, class.id).unwrap(), 1);
+        assert!(get_feed_token(&db, new_token.token).is_err());
+
         delete_class(&db, class.id).unwrap();
         delete_user(&db, user.id).unwrap();
     }
@@ -197,44 +673,40 @@ mod test {
         let events = vec![
             insert_event(
                 &db,
+                owner.id,
                 NewEvent {
-                    id: uuid::Uuid::new_v4(),
-                    class: class.id,
                     e_type: 1,
                     name: "event1000",
-                    start: &NaiveDateTime::from_timestamp(1000, 0),
                     end: Some(&NaiveDateTime::from_timestamp(2000, 0)),
                     description: "event",
-                    notification: None,
+                    ..NewEvent::test(class.id, &NaiveDateTime::from_timestamp(1000, 0))
                 },
+                &[],
             )
             .unwrap(),
             insert_event(
                 &db,
+                owner.id,
                 NewEvent {
-                    id: uuid::Uuid::new_v4(),
-                    class: class.id,
                     e_type: 1,
                     name: "event1000",
-                    start: &NaiveDateTime::from_timestamp(2000, 0),
-                    end: None,
                     description: "event",
-                    notification: None,
+                    ..NewEvent::test(class.id, &NaiveDateTime::from_timestamp(2000, 0))
                 },
+                &[],
             )
             .unwrap(),
             insert_event(
                 &db,
+                owner.id,
                 NewEvent {
-                    id: uuid::Uuid::new_v4(),
-                    class: class.id,
                     e_type: 1,
                     name: "event1000",
-                    start: &NaiveDateTime::from_timestamp(0, 0),
                     end: Some(&NaiveDateTime::from_timestamp(10000, 0)),
                     description: "event",
-                    notification: None,
+                    ..NewEvent::test(class.id, &NaiveDateTime::from_timestamp(0, 0))
                 },
+                &[],
             )
             .unwrap(),
         ];
@@ -297,6 +769,932 @@ mod test {
         delete_user(&db, owner.id).unwrap();
     }
 
+    #[test]
+    fn recurring_events() {
+        let db = get_pool();
+
+        let (owner, class) = insert_class_user(&db);
+        let day = 24 * 60 * 60;
+
+        // every second day, 5 times
+        let series = insert_event(
+            &db,
+            owner.id,
+            NewEvent {
+                e_type: 1,
+                name: "voci",
+                end: Some(&NaiveDateTime::from_timestamp(1000, 0)),
+                description: "event",
+                rec_frequency: Some("daily"),
+                rec_interval: 2,
+                rec_count: Some(5),
+                ..NewEvent::test(class.id, &NaiveDateTime::from_timestamp(0, 0))
+            },
+            &[],
+        )
+        .unwrap();
+
+        let events = get_events_by_class(&db, class.id).unwrap();
+        assert_eq!(events.len(), 5);
+
+        let events = get_events_by_class_filtered_both(
+            &db,
+            class.id,
+            NaiveDateTime::from_timestamp(5 * day, 0),
+            NaiveDateTime::from_timestamp(1500, 0),
+        )
+        .unwrap();
+        let starts = events
+            .iter()
+            .map(|(e, _)| e.start.timestamp())
+            .collect::<Vec<_>>();
+        assert_eq!(starts, vec![2 * day, 4 * day]);
+
+        let events = get_events_by_class_filtered_after(
+            &db,
+            class.id,
+            NaiveDateTime::from_timestamp(7 * day, 0),
+        )
+        .unwrap();
+        assert_eq!(events.len(), 1);
+
+        // delete the second occurrence
+        exclude_occurrence(
+            &db,
+            owner.id,
+            class.id,
+            series.0.id,
+            NaiveDateTime::from_timestamp(2 * day, 0),
+        )
+        .unwrap();
+        assert!(exclude_occurrence(
+            &db,
+            owner.id,
+            class.id,
+            series.0.id,
+            NaiveDateTime::from_timestamp(day, 0)
+        )
+        .is_err());
+
+        // the series can't be changed from another class
+        let (other_owner, other_class) = insert_class_user(&db);
+        assert!(matches!(
+            exclude_occurrence(
+                &db,
+                other_owner.id,
+                other_class.id,
+                series.0.id,
+                NaiveDateTime::from_timestamp(6 * day, 0),
+            ),
+            Err(ServiceErr::NotFound)
+        ));
+        let moved_start = NaiveDateTime::from_timestamp(5 * day, 0);
+        let moved = |class_id| NewEvent {
+            e_type: 1,
+            name: "voci",
+            description: "moved",
+            series: Some(series.0.id),
+            ..NewEvent::test(class_id, &moved_start)
+        };
+        assert!(matches!(
+            detach_occurrence(
+                &db,
+                other_owner.id,
+                NaiveDateTime::from_timestamp(4 * day, 0),
+                moved(other_class.id),
+                &[],
+            ),
+            Err(ServiceErr::NotFound)
+        ));
+        assert!(matches!(
+            update_event(
+                &db,
+                other_owner.id,
+                NewEvent {
+                    id: series.0.id,
+                    series: None,
+                    ..moved(other_class.id)
+                },
+                &[],
+            ),
+            Err(ServiceErr::NotFound)
+        ));
+        assert_eq!(
+            delete_event(&db, other_owner.id, other_class.id, series.0.id).unwrap(),
+            0
+        );
+        assert!(get_event_by_id(&db, other_class.id, series.0.id).is_err());
+        assert!(get_event_by_id(&db, class.id, series.0.id).is_ok());
+        assert!(get_events_by_class(&db, other_class.id).unwrap().is_empty());
+
+        // move the third occurrence
+        let detached = detach_occurrence(
+            &db,
+            owner.id,
+            NaiveDateTime::from_timestamp(4 * day, 0),
+            moved(class.id),
+            &[],
+        )
+        .unwrap();
+
+        let mut starts = get_events_by_class(&db, class.id)
+            .unwrap()
+            .iter()
+            .map(|(e, _)| e.start.timestamp())
+            .collect::<Vec<_>>();
+        starts.sort_unstable();
+        assert_eq!(starts, vec![0, 5 * day, 6 * day, 8 * day]);
+
+        // deleting the series deletes the detached occurrence
+        delete_event(&db, owner.id, class.id, series.0.id).unwrap();
+        assert!(get_event_by_id(&db, class.id, detached.0.id).is_err());
+
+        delete_class(&db, class.id).unwrap();
+        delete_user(&db, owner.id).unwrap();
+        delete_class(&db, other_class.id).unwrap();
+        delete_user(&db, other_owner.id).unwrap();
+    }
+
+    #[test]
+    fn multiple_notifications() {
+        let db = get_pool();
+
+        let (owner, class) = insert_class_user(&db);
+        // snowflakes are at most 20 digits long
+        let guild_id = (uuid::Uuid::new_v4().as_u128() % 10u128.pow(18)).to_string();
+        set_discord_id_class(&db, class.id, Some(&guild_id)).unwrap();
+        insert_guild(
+            &db,
+            NewGuild {
+                id: &guild_id,
+                notif_channel: Some("channel"),
+                notif_ping_role: None,
+                notif_ping_everyone: false,
+            },
+        )
+        .unwrap();
+
+        // the database only stores microseconds
+        let now = NaiveDateTime::from_timestamp(chrono::Utc::now().timestamp(), 0);
+        let hour = chrono::Duration::hours(1);
+        let start = now + hour * 24;
+        let new_event = || NewEvent {
+            id: uuid::Uuid::nil(),
+            name: "exam",
+            ..NewEvent::test(class.id, &start)
+        };
+
+        let (event, notifications) = insert_event(
+            &db,
+            owner.id,
+            NewEvent {
+                id: uuid::Uuid::new_v4(),
+                ..new_event()
+            },
+            &[now - hour, now - hour * 2, now + hour, now - hour],
+        )
+        .unwrap();
+        assert_eq!(notifications.len(), 3);
+
+        let (_, due) = get_notifications(&db, now - hour * 3).unwrap();
+        let due = due
+            .iter()
+            .filter(|(_, (due_event, _), _)| due_event.id == event.id)
+            .map(|(time, _, _)| *time)
+            .collect::<Vec<_>>();
+        assert_eq!(due.len(), 2);
+        assert!(due.contains(&(now - hour)) && due.contains(&(now - hour * 2)));
+
+        update_event(
+            &db,
+            owner.id,
+            NewEvent {
+                id: event.id,
+                ..new_event()
+            },
+            &[now + hour],
+        )
+        .unwrap();
+        let (_, notifications) = get_event_by_id(&db, class.id, event.id).unwrap();
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].time, now + hour);
+
+        delete_class(&db, class.id).unwrap();
+        delete_user(&db, owner.id).unwrap();
+    }
+
+    #[test]
+    fn import_events_twice() {
+        let db = get_pool();
+
+        let (owner, class) = insert_class_user(&db);
+
+        let start = NaiveDateTime::from_timestamp(1000, 0);
+        let imported = |name| NewEvent {
+            name,
+            import_uid: Some("exam@school"),
+            ..NewEvent::test(class.id, &start)
+        };
+
+        let preview = import_events(
+            &db,
+            owner.id,
+            class.id,
+            vec![(imported("exam"), &[][..])],
+            true,
+        )
+        .unwrap();
+        assert_eq!(preview, (1, 0));
+        assert_eq!(get_events_by_class(&db, class.id).unwrap().len(), 0);
+
+        let result = import_events(
+            &db,
+            owner.id,
+            class.id,
+            vec![(imported("exam"), &[][..])],
+            false,
+        )
+        .unwrap();
+        assert_eq!(result, (1, 0));
+
+        let result = import_events(
+            &db,
+            owner.id,
+            class.id,
+            vec![(imported("moved exam"), &[][..])],
+            false,
+        )
+        .unwrap();
+        assert_eq!(result, (0, 1));
+
+        let events = get_events_by_class(&db, class.id).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].0.name, "moved exam");
+
+        delete_class(&db, class.id).unwrap();
+        delete_user(&db, owner.id).unwrap();
+    }
+
+    #[test]
+    fn class_event_types() {
+        let db = get_pool();
+
+        let (owner, class) = insert_class_user(&db);
+        let (other_owner, other_class) = insert_class_user(&db);
+
+        let new_type = |display| NewEventType {
+            display,
+            class: class.id,
+            colour: "#ff9800",
+            reminders: &[86400],
+        };
+
+        let presentation = insert_event_type(&db, new_type("Vortrag")).unwrap();
+        assert_eq!(get_event_types(&db, class.id).unwrap().len(), 5);
+        assert_eq!(get_event_types(&db, other_class.id).unwrap().len(), 4);
+        assert!(get_event_type(&db, class.id, presentation.id).is_ok());
+        assert!(get_event_type(&db, other_class.id, presentation.id).is_err());
+        assert!(insert_event_type(&db, new_type("Vortrag")).is_err());
+
+        let presentation =
+            update_event_type(&db, presentation.id, new_type("Präsentation")).unwrap();
+        assert_eq!(presentation.display, "Präsentation");
+        assert_eq!(presentation.reminders, vec![86400]);
+
+        let start = NaiveDateTime::from_timestamp(1000, 0);
+        let (event, _) = insert_event(
+            &db,
+            owner.id,
+            NewEvent {
+                e_type: presentation.id,
+                name: "Vortrag Geschichte",
+                ..NewEvent::test(class.id, &start)
+            },
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(
+            delete_event_type(&db, other_class.id, presentation.id).unwrap(),
+            0
+        );
+        assert_eq!(
+            delete_event_type(&db, class.id, dto::EventType::EXAM).unwrap(),
+            0
+        );
+        assert_eq!(
+            delete_event_type(&db, class.id, presentation.id).unwrap(),
+            1
+        );
+        let (event, _) = get_event_by_id(&db, class.id, event.id).unwrap();
+        assert_eq!(event.e_type, dto::EventType::OTHER);
+
+        delete_class(&db, class.id).unwrap();
+        delete_user(&db, owner.id).unwrap();
+        delete_class(&db, other_class.id).unwrap();
+        delete_user(&db, other_owner.id).unwrap();
+    }
+
+    #[test]
+    fn event_completions() {
+        let db = get_pool();
+
+        let (owner, class) = insert_class_user(&db);
+
+        create_member(
+            &db,
+            NewMember {
+                user: owner.id,
+                class: class.id,
+                display_name: "member",
+                role: 0,
+            },
+        )
+        .unwrap();
+
+        let start = NaiveDateTime::from_timestamp(1000, 0);
+        let (homework, _) = insert_event(
+            &db,
+            owner.id,
+            NewEvent {
+                e_type: 1,
+                name: "Aufgaben S. 42",
+                ..NewEvent::test(class.id, &start)
+            },
+            &[],
+        )
+        .unwrap();
+
+        let completion = || models::EventCompletion {
+            event: homework.id,
+            user: owner.id,
+            class: class.id,
+            done_at: start,
+        };
+        assert_eq!(mark_event_done(&db, completion()).unwrap(), 1);
+        assert_eq!(mark_event_done(&db, completion()).unwrap(), 0);
+
+        assert_eq!(
+            get_done_events(&db, &[class.id], owner.id).unwrap(),
+            vec![homework.id]
+        );
+        assert_eq!(
+            get_completion_counts(&db, class.id, &[homework.id]).unwrap()[&homework.id],
+            1
+        );
+        assert!(get_completion_counts(&db, class.id, &[])
+            .unwrap()
+            .is_empty());
+
+        assert_eq!(unmark_event_done(&db, homework.id, owner.id).unwrap(), 1);
+        assert_eq!(unmark_event_done(&db, homework.id, owner.id).unwrap(), 0);
+        assert!(get_done_events(&db, &[class.id], owner.id)
+            .unwrap()
+            .is_empty());
+        assert!(get_completion_counts(&db, class.id, &[homework.id])
+            .unwrap()
+            .is_empty());
+
+        delete_class(&db, class.id).unwrap();
+        delete_user(&db, owner.id).unwrap();
+    }
+
+    #[test]
+    fn full_text_search() {
+        let db = get_pool();
+
+        let (owner, class) = insert_class_user(&db);
+        let (other_owner, other_class) = insert_class_user(&db);
+
+        let start = NaiveDateTime::from_timestamp(1000, 0);
+        let insert = |class_id, name, description| {
+            insert_event(
+                &db,
+                owner.id,
+                NewEvent {
+                    name,
+                    description,
+                    ..NewEvent::test(class_id, &start)
+                },
+                &[],
+            )
+            .unwrap()
+            .0
+        };
+
+        let equations = insert(class.id, "Mathe", "Lineare Gleichung");
+        let exam = insert(
+            class.id,
+            "Matheprüfung",
+            "Gleichungen, Gleichungssysteme, Gleichungen",
+        );
+        insert(class.id, "Deutsch", "Aufsatz");
+        let other = insert(other_class.id, "Physik", "Gleichungen umformen");
+
+        let found = search_events(&db, &[class.id], "gleichungen", None, None).unwrap();
+        let found = found.iter().map(|(event, _)| event.id).collect::<Vec<_>>();
+        assert_eq!(found, vec![exam.id, equations.id]);
+
+        let found =
+            search_events(&db, &[class.id, other_class.id], "gleichung", None, None).unwrap();
+        assert_eq!(found.len(), 3);
+        assert!(found.iter().any(|(event, _)| event.id == other.id));
+
+        let after = start + chrono::Duration::hours(1);
+        assert!(
+            search_events(&db, &[class.id], "gleichung", Some(after), None)
+                .unwrap()
+                .is_empty()
+        );
+
+        delete_class(&db, class.id).unwrap();
+        delete_user(&db, owner.id).unwrap();
+        delete_class(&db, other_class.id).unwrap();
+        delete_user(&db, other_owner.id).unwrap();
+    }
+
+    #[test]
+    fn paged_events() {
+        let db = get_pool();
+
+        let (owner, class) = insert_class_user(&db);
+
+        let day = |days: i64| NaiveDateTime::from_timestamp(days * 86400, 0);
+        let starts = [day(1), day(1), day(3), day(4)];
+        let mathe = insert_named_subject(&db, class.id, "Mathe");
+        for (i, start) in starts.iter().enumerate() {
+            let subject = (i == 0 || i == 3).then_some(&mathe);
+            insert_event(
+                &db,
+                owner.id,
+                NewEvent {
+                    e_type: 1,
+                    name: "event",
+                    rec_frequency: if i == 3 { Some("daily") } else { None },
+                    rec_count: Some(3),
+                    subject: subject.map(|subject| subject.name.as_str()),
+                    subject_id: subject.map(|subject| subject.id),
+                    ..NewEvent::test(class.id, start)
+                },
+                &[],
+            )
+            .unwrap();
+        }
+
+        let query = |cursor, descending| EventPageQuery {
+            after: None,
+            before: None,
+            search: None,
+            subject: None,
+            done: None,
+            cursor,
+            descending,
+            limit: 2,
+        };
+        for descending in [false, true] {
+            let mut keys = vec![];
+            let mut cursor = None;
+            loop {
+                let (page, next) =
+                    get_events_page(&db, class.id, query(cursor, descending)).unwrap();
+                assert!(page.len() <= 2);
+                keys.extend(page.iter().map(|(event, _)| (event.start, event.id)));
+                match next {
+                    Some(next) => cursor = Some(next),
+                    None => break,
+                }
+            }
+
+            let mut sorted = keys.clone();
+            sorted.sort_unstable();
+            if descending {
+                sorted.reverse();
+            }
+            assert_eq!(keys.len(), 6);
+            assert_eq!(keys, sorted);
+            sorted.dedup();
+            assert_eq!(sorted.len(), 6);
+        }
+
+        let (page, next) = get_events_page(
+            &db,
+            class.id,
+            EventPageQuery {
+                subject: Some("Mathe"),
+                limit: 10,
+                ..query(None, false)
+            },
+        )
+        .unwrap();
+        assert!(next.is_none());
+        assert_eq!(page.len(), 4);
+        assert!(page
+            .iter()
+            .all(|(event, _)| event.subject.as_deref() == Some("Mathe")));
+
+        delete_class(&db, class.id).unwrap();
+        delete_user(&db, owner.id).unwrap();
+    }
+
+    #[test]
+    fn event_history() {
+        let db = get_pool();
+
+        let (owner, class) = insert_class_user(&db);
+
+        let start = NaiveDateTime::from_timestamp(1000, 0);
+        let moved = NaiveDateTime::from_timestamp(90000, 0);
+        let event_id = uuid::Uuid::new_v4();
+        let exam = |start| NewEvent {
+            id: event_id,
+            ..NewEvent::test(class.id, start)
+        };
+
+        insert_event(&db, owner.id, exam(&start), &[]).unwrap();
+        update_event(&db, owner.id, exam(&moved), &[]).unwrap();
+        delete_event(&db, owner.id, class.id, event_id).unwrap();
+
+        let history = get_event_history(&db, class.id, event_id).unwrap();
+        let kinds = history
+            .iter()
+            .map(|revision| revision.kind.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(kinds, vec!["create", "update", "delete"]);
+        assert!(history
+            .iter()
+            .all(|revision| revision.actor == Some(owner.id)));
+        assert!(history[1].changes.contains("start"));
+        assert!(!history[1].changes.contains("name"));
+        assert!(history[2].snapshot.is_none());
+
+        let revision = get_event_revision(&db, class.id, event_id, history[1].id).unwrap();
+        assert_eq!(revision.id, history[1].id);
+        assert!(get_event_revision(&db, uuid::Uuid::new_v4(), event_id, history[1].id).is_err());
+
+        let (event, _) = revert_event(&db, owner.id, exam(&moved), &[]).unwrap();
+        assert_eq!(event.start, moved);
+        let history = get_event_history(&db, class.id, event_id).unwrap();
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[3].kind, "revert");
+
+        delete_class(&db, class.id).unwrap();
+        delete_user(&db, owner.id).unwrap();
+    }
+
+    #[test]
+    fn trash() {
+        let db = get_pool();
+
+        let (owner, class) = insert_class_user(&db);
+        let (member, member_class) = insert_class_user(&db);
+        create_member(
+            &db,
+            NewMember {
+                user: member.id,
+                class: class.id,
+                display_name: "Hugo",
+                role: 2,
+            },
+        )
+        .unwrap();
+
+        let start = NaiveDateTime::from_timestamp(1000, 0);
+        let event_id = uuid::Uuid::new_v4();
+        insert_event(
+            &db,
+            owner.id,
+            NewEvent {
+                id: event_id,
+                ..NewEvent::test(class.id, &start)
+            },
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(delete_event(&db, owner.id, class.id, event_id).unwrap(), 1);
+        assert_eq!(delete_event(&db, owner.id, class.id, event_id).unwrap(), 0);
+        assert!(get_event_by_id(&db, class.id, event_id).is_err());
+        assert_eq!(delete_member(&db, member.id, class.id).unwrap(), 1);
+        assert!(get_member(&db, member.id, class.id).is_err());
+
+        let deleted_events = get_deleted_events(&db, class.id).unwrap();
+        assert_eq!(deleted_events.len(), 1);
+        assert!(deleted_events[0].0.deleted_at.is_some());
+        let deleted_members = get_deleted_members(&db, class.id).unwrap();
+        assert_eq!(deleted_members.len(), 1);
+        assert_eq!(deleted_members[0].0.user, member.id);
+
+        let (event, _) = restore_event(&db, owner.id, class.id, event_id).unwrap();
+        assert!(event.deleted_at.is_none());
+        assert!(get_event_by_id(&db, class.id, event_id).is_ok());
+        assert!(restore_event(&db, owner.id, class.id, event_id).is_err());
+        let history = get_event_history(&db, class.id, event_id).unwrap();
+        assert_eq!(history.last().unwrap().kind, "restore");
+
+        restore_member(&db, member.id, class.id).unwrap();
+        assert!(get_member(&db, member.id, class.id).is_ok());
+        assert!(get_deleted_members(&db, class.id).unwrap().is_empty());
+
+        assert_eq!(delete_class(&db, class.id).unwrap(), 1);
+        assert!(get_class(&db, class.id).unwrap().is_none());
+        assert!(get_member(&db, owner.id, class.id).is_err());
+        let deleted_classes = get_deleted_classes(&db, owner.id).unwrap();
+        assert_eq!(deleted_classes.len(), 1);
+        assert!(restore_class(&db, member.id, class.id).is_err());
+        restore_class(&db, owner.id, class.id).unwrap();
+        assert!(get_class(&db, class.id).unwrap().is_some());
+
+        // nothing has been in the trash for long enough
+        delete_event(&db, owner.id, class.id, event_id).unwrap();
+        let long_ago = NaiveDateTime::from_timestamp(0, 0);
+        assert_eq!(purge_trash(&db, long_ago).unwrap(), 0);
+        assert_eq!(get_deleted_events(&db, class.id).unwrap().len(), 1);
+
+        let now = chrono::Utc::now().naive_utc() + chrono::Duration::seconds(1);
+        assert!(purge_trash(&db, now).unwrap() >= 1);
+        assert!(get_deleted_events(&db, class.id).unwrap().is_empty());
+
+        delete_class(&db, class.id).unwrap();
+        delete_class(&db, member_class.id).unwrap();
+        delete_user(&db, owner.id).unwrap();
+        delete_user(&db, member.id).unwrap();
+    }
+
+    #[test]
+    fn batch() {
+        let db = get_pool();
+
+        let (owner, class) = insert_class_user(&db);
+        let (other_owner, other_class) = insert_class_user(&db);
+
+        let start = NaiveDateTime::from_timestamp(1000, 0);
+        let exam = |event_id, class_id, name| NewEvent {
+            id: event_id,
+            name,
+            ..NewEvent::test(class_id, &start)
+        };
+        let existing = uuid::Uuid::new_v4();
+        insert_event(&db, owner.id, exam(existing, class.id, "Prüfung"), &[]).unwrap();
+        let foreign = uuid::Uuid::new_v4();
+        insert_event(
+            &db,
+            other_owner.id,
+            exam(foreign, other_class.id, "Fremd"),
+            &[],
+        )
+        .unwrap();
+
+        let first = uuid::Uuid::new_v4();
+        let second = uuid::Uuid::new_v4();
+        let operations = || {
+            vec![
+                BatchOperation::Create(exam(first, class.id, "Mathe"), &[]),
+                BatchOperation::Update(exam(existing, class.id, "Deutsch"), &[]),
+                // events of other classes can't be deleted
+                BatchOperation::Delete(foreign),
+                BatchOperation::Create(exam(second, class.id, "Englisch"), &[]),
+            ]
+        };
+
+        let (committed, results) =
+            batch_events(&db, owner.id, class.id, operations(), false).unwrap();
+        assert!(!committed);
+        assert_eq!(results.len(), 3);
+        assert!(matches!(results[0], Ok(BatchOutcome::Created(_))));
+        assert!(matches!(results[1], Ok(BatchOutcome::Updated(_))));
+        assert!(results[2].is_err());
+        assert!(get_event_by_id(&db, class.id, first).is_err());
+        assert_eq!(
+            get_event_by_id(&db, class.id, existing).unwrap().0.name,
+            "Prüfung"
+        );
+
+        let (committed, results) =
+            batch_events(&db, owner.id, class.id, operations(), true).unwrap();
+        assert!(committed);
+        assert_eq!(results.len(), 4);
+        assert!(results[2].is_err());
+        assert!(get_event_by_id(&db, class.id, first).is_ok());
+        assert!(get_event_by_id(&db, class.id, second).is_ok());
+        assert!(get_event_by_id(&db, other_class.id, foreign).is_ok());
+        assert_eq!(
+            get_event_by_id(&db, class.id, existing).unwrap().0.name,
+            "Deutsch"
+        );
+
+        let (committed, results) = batch_events(
+            &db,
+            owner.id,
+            class.id,
+            vec![
+                BatchOperation::Delete(first),
+                BatchOperation::Delete(second),
+            ],
+            false,
+        )
+        .unwrap();
+        assert!(committed);
+        assert!(results
+            .iter()
+            .all(|result| matches!(result, Ok(BatchOutcome::Deleted(_)))));
+        assert!(get_event_by_id(&db, class.id, first).is_err());
+
+        delete_class(&db, class.id).unwrap();
+        delete_class(&db, other_class.id).unwrap();
+        delete_user(&db, owner.id).unwrap();
+        delete_user(&db, other_owner.id).unwrap();
+    }
+
+    #[test]
+    fn grades() {
+        let db = get_pool();
+
+        let (owner, class) = insert_class_user(&db);
+        let (member, member_class) = insert_class_user(&db);
+        for (user, role) in [(owner.id, 0), (member.id, 2)] {
+            create_member(
+                &db,
+                NewMember {
+                    user,
+                    class: class.id,
+                    display_name: "Hugo",
+                    role,
+                },
+            )
+            .unwrap();
+        }
+
+        let start = NaiveDateTime::from_timestamp(1000, 0);
+        let mathe = insert_named_subject(&db, class.id, "Mathe");
+        let exam = |id, name| NewEvent {
+            id,
+            name,
+            subject: Some(&mathe.name),
+            subject_id: Some(mathe.id),
+            ..NewEvent::test(class.id, &start)
+        };
+        let (first_id, second_id) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+        insert_event(&db, owner.id, exam(first_id, "Algebra"), &[]).unwrap();
+        insert_event(&db, owner.id, exam(second_id, "Geometrie"), &[]).unwrap();
+
+        let grade = |user, event, grade| models::Grade {
+            user,
+            event,
+            class: class.id,
+            grade,
+            weight: 1.0,
+            scale: "swiss".to_string(),
+        };
+        set_grade(&db, grade(member.id, first_id, 4.5)).unwrap();
+        set_grade(&db, grade(member.id, first_id, 5.0)).unwrap();
+        set_grade(&db, grade(member.id, second_id, 3.5)).unwrap();
+        set_grade(&db, grade(owner.id, first_id, 6.0)).unwrap();
+
+        // grades are private
+        let grades = get_grades(&db, member.id, &[class.id]).unwrap();
+        assert_eq!(grades.len(), 2);
+        assert!(grades.iter().all(|(grade, _)| grade.user == member.id));
+        let (first, event) = &grades[0];
+        assert_eq!(first.grade, 5.0);
+        assert_eq!(event.subject.as_deref(), Some("Mathe"));
+        assert!(get_grades(&db, member.id, &[member_class.id])
+            .unwrap()
+            .is_empty());
+
+        // grades of deleted exams are hidden
+        delete_event(&db, owner.id, class.id, second_id).unwrap();
+        assert_eq!(get_grades(&db, member.id, &[class.id]).unwrap().len(), 1);
+
+        assert_eq!(delete_grade(&db, member.id, first_id).unwrap(), 1);
+        assert_eq!(delete_grade(&db, member.id, first_id).unwrap(), 0);
+        assert_eq!(get_grades(&db, owner.id, &[class.id]).unwrap().len(), 1);
+
+        delete_class(&db, class.id).unwrap();
+        delete_class(&db, member_class.id).unwrap();
+        delete_user(&db, member.id).unwrap();
+        delete_user(&db, owner.id).unwrap();
+    }
+
+    #[test]
+    fn millisecond_timestamps() {
+        use crate::models::conversion::IntoDto;
+        use dto::Timestamp;
+
+        let db = get_pool();
+        let (owner, class) = insert_class_user(&db);
+
+        let start = Timestamp(1628000000123).to_naive();
+        let notification = Timestamp(1627999999999).to_naive();
+        let (event, _) = insert_event(
+            &db,
+            owner.id,
+            NewEvent {
+                ..NewEvent::test(class.id, &start)
+            },
+            &[notification],
+        )
+        .unwrap();
+
+        let dto: dto::Event = get_event_by_id(&db, class.id, event.id)
+            .unwrap()
+            .into_dto()
+            .unwrap();
+        assert_eq!(dto.start, Timestamp(1628000000123));
+        assert_eq!(dto.notifications, vec![Timestamp(1627999999999)]);
+
+        let in_window = |after: i64, before: i64| {
+            get_events_by_class_filtered_both(
+                &db,
+                class.id,
+                Timestamp(before).to_naive(),
+                Timestamp(after).to_naive(),
+            )
+            .unwrap()
+            .iter()
+            .any(|(found, _)| found.id == event.id)
+        };
+        assert!(in_window(1628000000122, 1628000000124));
+        assert!(!in_window(1628000000123, 1628000000200));
+        assert!(!in_window(1628000000000, 1628000000123));
+
+        delete_class(&db, class.id).unwrap();
+        delete_user(&db, owner.id).unwrap();
+    }
+
+    #[test]
+    fn strict_exam_limit() {
+        let db = get_pool();
+
+        let (owner, class) = insert_class_user(&db);
+        let limit = |strict_exam_limit| NewClass {
+            id: class.id,
+            owner: owner.id,
+            name: "testklasse",
+            description: "",
+            discord_id: None,
+            exam_limit: 1,
+            strict_exam_limit,
+            timezone: "Europe/Zurich",
+        };
+        update_class(&db, limit(true)).unwrap();
+
+        let monday = models::datetime("2021-08-23 08:00");
+        let tuesday = models::datetime("2021-08-24 08:00");
+        let next_monday = models::datetime("2021-08-30 08:00");
+        let too_many = |result: Result<_, ServiceErr>| {
+            matches!(result, Err(ServiceErr::BadRequest("too-many-exams")))
+        };
+
+        let (first, _) =
+            insert_event(&db, owner.id, NewEvent::test(class.id, &monday), &[]).unwrap();
+        assert!(too_many(insert_event(
+            &db,
+            owner.id,
+            NewEvent::test(class.id, &tuesday),
+            &[]
+        )));
+        let (second, _) =
+            insert_event(&db, owner.id, NewEvent::test(class.id, &next_monday), &[]).unwrap();
+
+        // the only exam of a week can be moved within it, but not into a full week
+        let moved = |id| NewEvent {
+            id,
+            ..NewEvent::test(class.id, &tuesday)
+        };
+        assert!(too_many(update_event(&db, owner.id, moved(second.id), &[])));
+        update_event(&db, owner.id, moved(first.id), &[]).unwrap();
+
+        let (committed, results) = batch_events(
+            &db,
+            owner.id,
+            class.id,
+            vec![BatchOperation::Create(
+                NewEvent::test(class.id, &monday),
+                &[],
+            )],
+            false,
+        )
+        .unwrap();
+        assert!(!committed);
+        assert!(matches!(
+            results[0],
+            Err(ServiceErr::BadRequest("too-many-exams"))
+        ));
+        assert_eq!(get_events_by_class(&db, class.id).unwrap().len(), 2);
+
+        // without a strict limit, the exam is saved with a warning
+        update_class(&db, limit(false)).unwrap();
+        let (third, _) =
+            insert_event(&db, owner.id, NewEvent::test(class.id, &monday), &[]).unwrap();
+        let warnings = exam_warnings(&db, &third).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].exams, 2);
+        assert!(exam_warnings(&db, &second).unwrap().is_empty());
+
+        delete_class(&db, class.id).unwrap();
+        delete_user(&db, owner.id).unwrap();
+    }
+
     fn insert_class_user(db: &Pool) -> (User, Class) {
         let user = insert_user(
             &db,
@@ -319,9 +1717,27 @@ mod test {
                 name: "testklasse",
                 description: "",
                 discord_id: None,
+                exam_limit: 3,
+                strict_exam_limit: false,
+                timezone: "Europe/Zurich",
             },
         )
         .unwrap();
         (user, class)
     }
+
+    fn insert_named_subject(db: &Pool, class_id: uuid::Uuid, name: &str) -> models::Subject {
+        insert_subject(
+            db,
+            models::NewSubject {
+                class: class_id,
+                name,
+                short: "",
+                colour: "",
+                teacher: "",
+                room: "",
+            },
+        )
+        .unwrap()
+    }
 }
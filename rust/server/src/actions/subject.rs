@@ -0,0 +1,113 @@
+use super::Pool;
+use crate::diesel::{QueryDsl, RunQueryDsl};
+use crate::error::{ServiceErr, ServiceResult};
+use crate::models::{NewSubject, Subject};
+use crate::schema::subjects::dsl::*;
+use diesel::sql_types::VarChar;
+use diesel::{
+    delete, insert_into, update, BoolExpressionMethods, Connection, ExpressionMethods,
+    OptionalExtension, PgConnection,
+};
+use uuid::Uuid;
+
+sql_function!(fn lower(x: VarChar) -> VarChar);
+
+/// The subject of the class with the name, ignoring the case and surrounding whitespace
+pub fn find_subject(
+    conn: &PgConnection,
+    class_id: Uuid,
+    subject_name: &str,
+) -> ServiceResult<Option<Subject>> {
+    Ok(subjects
+        .filter(class.eq(class_id))
+        .filter(lower(name).eq(lower(subject_name.trim())))
+        .first(conn)
+        .optional()?)
+}
+
+/// Lessons can introduce new subjects, they get the teacher and room of the lesson.
+/// Fails with `missing-subject` if the name is empty.
+pub fn find_or_insert_subject(
+    conn: &PgConnection,
+    class_id: Uuid,
+    subject_name: &str,
+    lesson_teacher: &str,
+    lesson_room: &str,
+) -> ServiceResult<Subject> {
+    if subject_name.trim().is_empty() {
+        return Err(ServiceErr::BadRequest("missing-subject"));
+    }
+    match find_subject(conn, class_id, subject_name)? {
+        Some(subject) => Ok(subject),
+        None => Ok(insert_into(subjects)
+            .values(NewSubject {
+                class: class_id,
+                name: subject_name.trim(),
+                short: "",
+                colour: "",
+                teacher: lesson_teacher,
+                room: lesson_room,
+            })
+            .get_result(conn)?),
+    }
+}
+
+/// The subjects of the class, sorted by their name
+pub fn get_subjects(db: &Pool, class_id: Uuid) -> ServiceResult<Vec<Subject>> {
+    let conn = db.get()?;
+
+    Ok(subjects
+        .filter(class.eq(class_id))
+        .order(lower(name))
+        .load(&conn)?)
+}
+
+pub fn get_subject(db: &Pool, class_id: Uuid, subject_id: Uuid) -> ServiceResult<Subject> {
+    let conn = db.get()?;
+
+    Ok(subjects
+        .filter(id.eq(subject_id).and(class.eq(class_id)))
+        .get_result(&conn)?)
+}
+
+/// Fails with `Conflict` if the class already has a subject with the name
+pub fn insert_subject(db: &Pool, new_subject: NewSubject) -> ServiceResult<Subject> {
+    let conn = db.get()?;
+
+    Ok(insert_into(subjects)
+        .values(&new_subject)
+        .get_result(&conn)?)
+}
+
+/// Renaming a subject renames it in its lessons and events as well
+pub fn update_subject(
+    db: &Pool,
+    subject_id: Uuid,
+    new_subject: NewSubject,
+) -> ServiceResult<Subject> {
+    let conn = db.get()?;
+
+    Ok(
+        update(subjects.filter(id.eq(subject_id).and(class.eq(new_subject.class))))
+            .set(&new_subject)
+            .get_result(&conn)?,
+    )
+}
+
+/// Fails with `subject-in-use` if a lesson has the subject, events lose their subject instead
+pub fn delete_subject(db: &Pool, class_id: Uuid, subject_id: Uuid) -> ServiceResult<usize> {
+    use crate::schema::lessons;
+    let conn = db.get()?;
+
+    conn.transaction(|| {
+        let used: i64 = lessons::table
+            .filter(lessons::subject_id.eq(subject_id))
+            .count()
+            .get_result(&conn)?;
+        if used > 0 {
+            return Err(ServiceErr::Conflict("subject-in-use"));
+        }
+
+        Ok(delete(subjects.filter(id.eq(subject_id).and(class.eq(class_id)))).execute(&conn)?)
+    })
+}
@@ -0,0 +1,413 @@
+use super::subject::find_or_insert_subject;
+use super::Pool;
+use crate::diesel::{QueryDsl, RunQueryDsl};
+use crate::error::{ServiceErr, ServiceResult};
+use crate::models::{
+    Lesson, NewLesson, NewTimetableException, NewTimetableVersion, Subject, Timetable,
+    TimetableException, TimetableVersion,
+};
+use crate::schema::lessons::dsl::*;
+use chrono::NaiveDate;
+use diesel::{
+    delete, insert_into, update, BoolExpressionMethods, Connection, ExpressionMethods, PgConnection,
+};
+use uuid::Uuid;
+
+fn lessons_of_class(conn: &PgConnection, class_id: Uuid) -> ServiceResult<Vec<Lesson>> {
+    Ok(lessons
+        .filter(class.eq(class_id))
+        .order((weekday, start))
+        .load(conn)?)
+}
+
+fn lessons_of_version(conn: &PgConnection, version_id: Uuid) -> ServiceResult<Vec<Lesson>> {
+    Ok(lessons
+        .filter(version.eq(version_id))
+        .order((weekday, start))
+        .load(conn)?)
+}
+
+/// Fails with `invalid-version` if the version is not one of the class
+fn find_version(
+    conn: &PgConnection,
+    class_id: Uuid,
+    version_id: Uuid,
+) -> ServiceResult<TimetableVersion> {
+    use crate::schema::timetable_versions::dsl::{class, id, timetable_versions};
+
+    timetable_versions
+        .filter(id.eq(version_id).and(class.eq(class_id)))
+        .get_result(conn)
+        .map_err(|err| match err {
+            diesel::result::Error::NotFound => ServiceErr::BadRequest("invalid-version"),
+            err => err.into(),
+        })
+}
+
+fn versions_of_class(conn: &PgConnection, class_id: Uuid) -> ServiceResult<Vec<TimetableVersion>> {
+    use crate::schema::timetable_versions::dsl::{class, timetable_versions, valid_from};
+    use diesel::PgSortExpressionMethods;
+
+    Ok(timetable_versions
+        .filter(class.eq(class_id))
+        .order(valid_from.asc().nulls_first())
+        .load(conn)?)
+}
+
+fn find_timetable(conn: &PgConnection, class_id: Uuid) -> ServiceResult<Timetable> {
+    use crate::schema::timetables::dsl::timetables;
+
+    Ok(timetables.find(class_id).get_result(conn)?)
+}
+
+/// Fails if a lesson is in a week that is not part of the cycle of the timetable
+fn check_cycle_weeks<'a>(
+    timetable: &Timetable,
+    mut new_lessons: impl Iterator<Item = &'a NewLesson<'a>>,
+) -> ServiceResult<()> {
+    let outside = new_lessons
+        .any(|lesson| matches!(lesson.cycle_week, Some(week) if week >= timetable.cycle_weeks));
+    if outside {
+        return Err(ServiceErr::BadRequest("invalid-cycle-week"));
+    }
+    Ok(())
+}
+
+/// The subject that the lesson names, it is created if the class doesn't have it yet
+fn subject_of_lesson(conn: &PgConnection, new_lesson: &NewLesson) -> ServiceResult<Subject> {
+    find_or_insert_subject(
+        conn,
+        new_lesson.class,
+        new_lesson.subject,
+        new_lesson.teacher,
+        new_lesson.room,
+    )
+}
+
+/// The lesson with the name of its subject as the class spells it.
+/// A lesson without a teacher or room gets the one of the subject.
+fn with_subject<'a>(new_lesson: &NewLesson<'a>, registered: &'a Subject) -> NewLesson<'a> {
+    let or_default = |value: &'a str, default: &'a str| {
+        if value.is_empty() {
+            default
+        } else {
+            value
+        }
+    };
+    NewLesson {
+        subject: &registered.name,
+        teacher: or_default(new_lesson.teacher, &registered.teacher),
+        room: or_default(new_lesson.room, &registered.room),
+        ..*new_lesson
+    }
+}
+
+/// The timetable of the class with the lessons of all versions, sorted by their day and start
+pub fn get_timetable(db: &Pool, class_id: Uuid) -> ServiceResult<(Timetable, Vec<Lesson>)> {
+    let conn = db.get()?;
+
+    let timetable = find_timetable(&conn, class_id)?;
+    Ok((timetable, lessons_of_class(&conn, class_id)?))
+}
+
+/// Fails if a lesson is in a week that is no longer part of the cycle
+pub fn update_timetable_cycle(
+    db: &Pool,
+    class_id: Uuid,
+    weeks: i32,
+    anchor: NaiveDate,
+) -> ServiceResult<Timetable> {
+    use crate::schema::timetables::dsl::{class, cycle_anchor, cycle_weeks, timetables};
+    let conn = db.get()?;
+
+    conn.transaction(|| {
+        let outside: i64 = lessons
+            .filter(crate::schema::lessons::class.eq(class_id))
+            .filter(cycle_week.ge(weeks))
+            .count()
+            .get_result(&conn)?;
+        if outside > 0 {
+            return Err(ServiceErr::BadRequest("lessons-outside-cycle"));
+        }
+
+        Ok(update(timetables.filter(class.eq(class_id)))
+            .set((cycle_weeks.eq(weeks), cycle_anchor.eq(anchor)))
+            .get_result(&conn)?)
+    })
+}
+
+/// The timetable starts with a single version that is always valid
+pub fn create_timetable(db: &Pool, class_id: Uuid) -> ServiceResult<Timetable> {
+    use crate::schema::timetable_versions::dsl::timetable_versions;
+    use crate::schema::timetables::dsl::{class, timetables};
+    let conn = db.get()?;
+
+    conn.transaction(|| {
+        let timetable = insert_into(timetables)
+            .values(class.eq(class_id))
+            .get_result(&conn)?;
+        insert_into(timetable_versions)
+            .values(NewTimetableVersion {
+                class: class_id,
+                valid_from: None,
+                valid_until: None,
+            })
+            .execute(&conn)?;
+        Ok(timetable)
+    })
+}
+
+/// Replaces all lessons of the version of the timetable.
+/// Lessons with the id of an existing lesson keep their id, all other lessons get a new one.
+pub fn update_timetable(
+    db: &Pool,
+    class_id: Uuid,
+    version_id: Uuid,
+    new_lessons: &[(Option<Uuid>, NewLesson)],
+) -> ServiceResult<Vec<Lesson>> {
+    let conn = db.get()?;
+
+    conn.transaction(|| {
+        let timetable = find_timetable(&conn, class_id)?;
+        find_version(&conn, class_id, version_id)?;
+        check_cycle_weeks(&timetable, new_lessons.iter().map(|(_, lesson)| lesson))?;
+
+        let kept_ids = new_lessons
+            .iter()
+            .filter_map(|(lesson_id, _)| *lesson_id)
+            .collect::<Vec<_>>();
+        delete(lessons.filter(version.eq(version_id).and(id.ne_all(&kept_ids)))).execute(&conn)?;
+
+        for (lesson_id, new_lesson) in new_lessons {
+            let registered = subject_of_lesson(&conn, new_lesson)?;
+            let new_lesson = with_subject(new_lesson, &registered);
+            let updated = match lesson_id {
+                Some(lesson_id) => {
+                    update(lessons.filter(id.eq(lesson_id).and(version.eq(version_id))))
+                        .set((&new_lesson, subject_id.eq(registered.id)))
+                        .execute(&conn)?
+                }
+                None => 0,
+            };
+            if updated == 0 {
+                insert_into(lessons)
+                    .values((&new_lesson, subject_id.eq(registered.id)))
+                    .execute(&conn)?;
+            }
+        }
+
+        lessons_of_version(&conn, version_id)
+    })
+}
+
+/// The versions of the timetable, sorted by the date they start
+pub fn get_versions(db: &Pool, class_id: Uuid) -> ServiceResult<Vec<TimetableVersion>> {
+    let conn = db.get()?;
+
+    find_timetable(&conn, class_id)?;
+    versions_of_class(&conn, class_id)
+}
+
+/// Fails if the version overlaps with another version.
+/// The version that has no end and is valid when the new version starts ends the day before.
+pub fn insert_version(
+    db: &Pool,
+    new_version: NewTimetableVersion,
+) -> ServiceResult<TimetableVersion> {
+    use crate::schema::timetable_versions::dsl::{timetable_versions, valid_until};
+    let conn = db.get()?;
+
+    conn.transaction(|| {
+        find_timetable(&conn, new_version.class)?;
+
+        if let Some(from) = new_version.valid_from {
+            let previous = versions_of_class(&conn, new_version.class)?
+                .into_iter()
+                .find(|previous| {
+                    previous.valid_until.is_none()
+                        && !matches!(previous.valid_from, Some(previous_from) if previous_from >= from)
+                });
+            if let Some(previous) = previous {
+                update(timetable_versions.find(previous.id))
+                    .set(valid_until.eq(from.pred_opt()))
+                    .execute(&conn)?;
+            }
+        }
+
+        check_version_overlaps(&conn, None, &new_version)?;
+        Ok(insert_into(timetable_versions)
+            .values(&new_version)
+            .get_result(&conn)?)
+    })
+}
+
+pub fn update_version(
+    db: &Pool,
+    version_id: Uuid,
+    new_version: NewTimetableVersion,
+) -> ServiceResult<TimetableVersion> {
+    use crate::schema::timetable_versions::dsl::{class, id, timetable_versions};
+    let conn = db.get()?;
+
+    conn.transaction(|| {
+        check_version_overlaps(&conn, Some(version_id), &new_version)?;
+        Ok(
+            update(timetable_versions.filter(id.eq(version_id).and(class.eq(new_version.class))))
+                .set(&new_version)
+                .get_result(&conn)?,
+        )
+    })
+}
+
+/// Deleting a version deletes its lessons
+pub fn delete_version(db: &Pool, class_id: Uuid, version_id: Uuid) -> ServiceResult<usize> {
+    use crate::schema::timetable_versions::dsl::{class, id, timetable_versions};
+    let conn = db.get()?;
+
+    Ok(
+        delete(timetable_versions.filter(id.eq(version_id).and(class.eq(class_id))))
+            .execute(&conn)?,
+    )
+}
+
+fn check_version_overlaps(
+    conn: &PgConnection,
+    version_id: Option<Uuid>,
+    new_version: &NewTimetableVersion,
+) -> ServiceResult<()> {
+    let overlapping = versions_of_class(conn, new_version.class)?
+        .iter()
+        .filter(|other| Some(other.id) != version_id)
+        .any(|other| {
+            crate::timetable::ranges_overlap(
+                (other.valid_from, other.valid_until),
+                (new_version.valid_from, new_version.valid_until),
+            )
+        });
+    if overlapping {
+        return Err(ServiceErr::BadRequest("overlapping-versions"));
+    }
+    Ok(())
+}
+
+pub fn delete_timetable(db: &Pool, class_id: Uuid) -> ServiceResult<usize> {
+    use crate::schema::timetables::dsl::{class, timetables};
+    let conn = db.get()?;
+
+    Ok(delete(timetables)
+        .filter(class.eq(class_id))
+        .execute(&conn)?)
+}
+
+pub fn get_lesson(db: &Pool, class_id: Uuid, lesson_id: Uuid) -> ServiceResult<Lesson> {
+    let conn = db.get()?;
+
+    Ok(lessons
+        .filter(id.eq(lesson_id).and(class.eq(class_id)))
+        .get_result(&conn)?)
+}
+
+/// Fails with `NotFound` if the class has no timetable
+pub fn insert_lesson(db: &Pool, new_lesson: NewLesson) -> ServiceResult<Lesson> {
+    let conn = db.get()?;
+
+    conn.transaction(|| {
+        let timetable = find_timetable(&conn, new_lesson.class)?;
+        find_version(&conn, new_lesson.class, new_lesson.version)?;
+        check_cycle_weeks(&timetable, std::iter::once(&new_lesson))?;
+        let registered = subject_of_lesson(&conn, &new_lesson)?;
+        Ok(insert_into(lessons)
+            .values((
+                &with_subject(&new_lesson, &registered),
+                subject_id.eq(registered.id),
+            ))
+            .get_result(&conn)?)
+    })
+}
+
+pub fn update_lesson(db: &Pool, lesson_id: Uuid, new_lesson: NewLesson) -> ServiceResult<Lesson> {
+    let conn = db.get()?;
+
+    conn.transaction(|| {
+        let timetable = find_timetable(&conn, new_lesson.class)?;
+        find_version(&conn, new_lesson.class, new_lesson.version)?;
+        check_cycle_weeks(&timetable, std::iter::once(&new_lesson))?;
+        let registered = subject_of_lesson(&conn, &new_lesson)?;
+        Ok(
+            update(lessons.filter(id.eq(lesson_id).and(class.eq(new_lesson.class))))
+                .set((
+                    &with_subject(&new_lesson, &registered),
+                    subject_id.eq(registered.id),
+                ))
+                .get_result(&conn)?,
+        )
+    })
+}
+
+pub fn delete_lesson(db: &Pool, class_id: Uuid, lesson_id: Uuid) -> ServiceResult<usize> {
+    let conn = db.get()?;
+
+    Ok(delete(lessons.filter(id.eq(lesson_id).and(class.eq(class_id)))).execute(&conn)?)
+}
+
+/// The exceptions of the timetable between the dates, sorted by their date
+pub fn get_exceptions(
+    db: &Pool,
+    class_id: Uuid,
+    after: Option<NaiveDate>,
+    before: Option<NaiveDate>,
+) -> ServiceResult<Vec<TimetableException>> {
+    use crate::schema::timetable_exceptions::dsl::{class, date, start, timetable_exceptions};
+    let conn = db.get()?;
+
+    let mut query = timetable_exceptions.filter(class.eq(class_id)).into_boxed();
+    if let Some(after) = after {
+        query = query.filter(date.ge(after));
+    }
+    if let Some(before) = before {
+        query = query.filter(date.le(before));
+    }
+
+    Ok(query.order((date, start)).load(&conn)?)
+}
+
+/// Fails with `NotFound` if the class has no timetable
+pub fn insert_exception(
+    db: &Pool,
+    new_exception: NewTimetableException,
+) -> ServiceResult<TimetableException> {
+    use crate::schema::timetable_exceptions::dsl::timetable_exceptions;
+    let conn = db.get()?;
+
+    conn.transaction(|| {
+        find_timetable(&conn, new_exception.class)?;
+        Ok(insert_into(timetable_exceptions)
+            .values(&new_exception)
+            .get_result(&conn)?)
+    })
+}
+
+pub fn update_exception(
+    db: &Pool,
+    exception_id: Uuid,
+    new_exception: NewTimetableException,
+) -> ServiceResult<TimetableException> {
+    use crate::schema::timetable_exceptions::dsl::{class, id, timetable_exceptions};
+    let conn = db.get()?;
+
+    Ok(
+        update(timetable_exceptions.filter(id.eq(exception_id).and(class.eq(new_exception.class))))
+            .set(&new_exception)
+            .get_result(&conn)?,
+    )
+}
+
+pub fn delete_exception(db: &Pool, class_id: Uuid, exception_id: Uuid) -> ServiceResult<usize> {
+    use crate::schema::timetable_exceptions::dsl::{class, id, timetable_exceptions};
+    let conn = db.get()?;
+
+    Ok(
+        delete(timetable_exceptions.filter(id.eq(exception_id).and(class.eq(class_id))))
+            .execute(&conn)?,
+    )
+}
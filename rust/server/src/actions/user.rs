@@ -4,7 +4,7 @@ use crate::error::{ServiceErr, ServiceResult};
 use crate::models::{NewUser, User};
 use crate::schema::users::dsl::*;
 use diesel::sql_types::{Integer, Text};
-use diesel::{delete, insert_into, update, BoolExpressionMethods, ExpressionMethods};
+use diesel::{delete, insert_into, update, BoolExpressionMethods, Connection, ExpressionMethods};
 use uuid::Uuid;
 
 sql_function!(fn crypt(pwd: Text, salt: Text) -> Text);
@@ -46,10 +46,18 @@ pub fn insert_user(db: &Pool, user: NewUser) -> ServiceResult<User> {
         .get_result(&conn)?)
 }
 
+/// Classes of the user that are in the trash are deleted for good
 pub fn delete_user(db: &Pool, user_id: Uuid) -> ServiceResult<usize> {
+    use crate::schema::classes::dsl::{classes, deleted_at, owner};
     let conn = db.get()?;
 
-    Ok(delete(users).filter(id.eq(user_id)).execute(&conn)?)
+    conn.transaction(|| {
+        delete(classes)
+            .filter(owner.eq(user_id).and(deleted_at.is_not_null()))
+            .execute(&conn)?;
+
+        Ok(delete(users).filter(id.eq(user_id)).execute(&conn)?)
+    })
 }
 
 pub fn update_user(db: &Pool, user: User) -> ServiceResult<User> {
@@ -15,6 +15,7 @@ pub enum ServiceErr {
     BadRequest(&'static str),
     Conflict(&'static str),
     Unauthorized(&'static str),
+    InvalidTimetable(Vec<dto::LessonError>),
     TokenExpiredError,
     JWTokenError,
     // 500
@@ -53,6 +54,7 @@ impl Display for ServiceErr {
                 ServiceErr::IntoDTOError(msg) => msg.to_string(),
                 ServiceErr::BadRequest(msg) => msg.to_string(),
                 ServiceErr::Conflict(msg) => msg.to_string(),
+                ServiceErr::InvalidTimetable(_) => "invalid-timetable".to_string(),
                 ServiceErr::NoAdminPermissions => "perms/no-admin".to_string(),
             }
         )
@@ -70,6 +72,12 @@ impl ResponseError for ServiceErr {
             ServiceErr::Unauthorized(msg) => HttpResponse::Unauthorized().body(*msg),
             ServiceErr::NoAdminPermissions => HttpResponse::Unauthorized().body("no-admin"),
             ServiceErr::Conflict(msg) => HttpResponse::Conflict().body(msg.to_string()),
+            ServiceErr::InvalidTimetable(lessons) => {
+                HttpResponse::BadRequest().json(dto::TimetableErrors {
+                    error: "invalid-timetable".to_string(),
+                    lessons: lessons.clone(),
+                })
+            }
             err => {
                 error!(%err, "an error occurred");
                 HttpResponse::InternalServerError().finish()
@@ -0,0 +1,130 @@
+use dto::{Grade, GradeScale, GradeSummary, SubjectGrades};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// The weighted sum of the grades, the sum of the weights and the amount of grades
+type Totals = (f64, f64, usize);
+
+/// The weighted averages of the grades per class and subject.
+/// Grades on different scales are averaged separately.
+pub fn summarize(grades: &[Grade]) -> Vec<GradeSummary> {
+    let mut classes = BTreeMap::<Uuid, BTreeMap<(Option<String>, GradeScale), Totals>>::new();
+
+    for grade in grades {
+        let (sum, weight, count) = classes
+            .entry(grade.class)
+            .or_default()
+            .entry((grade.subject.clone(), grade.scale))
+            .or_insert((0.0, 0.0, 0));
+        *sum += grade.grade * grade.weight;
+        *weight += grade.weight;
+        *count += 1;
+    }
+
+    classes
+        .into_iter()
+        .map(|(class, subjects)| GradeSummary {
+            class,
+            subjects: subjects
+                .into_iter()
+                .map(|((subject, scale), (sum, weight, count))| {
+                    let average = sum / weight;
+                    SubjectGrades {
+                        subject,
+                        scale,
+                        average,
+                        passing: scale.is_passing(average),
+                        weight,
+                        count,
+                    }
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::summarize;
+    use dto::{Grade, GradeScale};
+    use uuid::Uuid;
+
+    fn grade(class: Uuid, subject: Option<&str>, grade: f64, weight: f64) -> Grade {
+        Grade {
+            event: Uuid::new_v4(),
+            class,
+            grade,
+            weight,
+            scale: GradeScale::Swiss,
+            name: "Prüfung".to_string(),
+            subject: subject.map(str::to_string),
+            start: dto::Timestamp(1628000000000),
+        }
+    }
+
+    #[test]
+    fn weighted_averages() {
+        let class = Uuid::new_v4();
+        let grades = vec![
+            grade(class, Some("Mathe"), 5.0, 1.0),
+            grade(class, Some("Mathe"), 3.5, 2.0),
+            grade(class, Some("Deutsch"), 5.5, 1.0),
+            grade(class, None, 4.5, 0.5),
+        ];
+
+        let summary = summarize(&grades);
+        assert_eq!(summary.len(), 1);
+        let subjects = &summary[0].subjects;
+        assert_eq!(subjects.len(), 3);
+
+        let math = subjects
+            .iter()
+            .find(|subject| subject.subject.as_deref() == Some("Mathe"))
+            .unwrap();
+        assert_eq!(math.average, 4.0);
+        assert_eq!(math.weight, 3.0);
+        assert_eq!(math.count, 2);
+        assert!(math.passing);
+
+        let without_subject = subjects
+            .iter()
+            .find(|subject| subject.subject.is_none())
+            .unwrap();
+        assert_eq!(without_subject.average, 4.5);
+    }
+
+    #[test]
+    fn classes_and_scales_are_separate() {
+        let class = Uuid::new_v4();
+        let other_class = Uuid::new_v4();
+        let grades = vec![
+            grade(class, Some("Mathe"), 3.0, 1.0),
+            grade(other_class, Some("Mathe"), 6.0, 1.0),
+            Grade {
+                scale: GradeScale::German,
+                ..grade(class, Some("Mathe"), 2.0, 1.0)
+            },
+        ];
+
+        let summary = summarize(&grades);
+        assert_eq!(summary.len(), 2);
+        let subjects = &summary
+            .iter()
+            .find(|summary| summary.class == class)
+            .unwrap()
+            .subjects;
+        assert_eq!(subjects.len(), 2);
+        let swiss = subjects
+            .iter()
+            .find(|subject| subject.scale == GradeScale::Swiss)
+            .unwrap();
+        assert_eq!(swiss.average, 3.0);
+        assert!(!swiss.passing);
+        let german = subjects
+            .iter()
+            .find(|subject| subject.scale == GradeScale::German)
+            .unwrap();
+        assert_eq!(german.average, 2.0);
+        assert!(german.passing);
+    }
+}
@@ -67,7 +67,7 @@ async fn refresh_token(
         Ok(HttpResponse::Ok()
             .header("token", format!("Bearer {}", new_token.0))
             .json(dto::RefreshResponse {
-                expires: new_token.1,
+                expires: dto::Timestamp(new_token.1),
             }))
     } else {
         Err(ServiceErr::Unauthorized("wrong-token-kind"))
@@ -91,7 +91,7 @@ async fn login(mut body: Json<UserLogin>, db: Data<Pool>, key: Data<EncodingKey>
                 .header("refresh-token", format!("Bearer {}", refresh_token))
                 .json(LoginResponse {
                     userid: user.id,
-                    expires,
+                    expires: dto::Timestamp(expires),
                 }))
         }
         None => Ok(HttpResponse::Forbidden().body("invalid-email-password")),
@@ -135,7 +135,7 @@ pub async fn create_user(
                 description: user.description,
                 classes: None,
             },
-            expires,
+            expires: dto::Timestamp(expires),
         }))
 }
 
@@ -1,29 +1,60 @@
+use crate::actions::event::{BatchOutcome, EventPageQuery};
 use crate::actions::{self, Pool};
 use crate::error::ServiceErr;
 use crate::handlers::auth::Claims;
 use crate::handlers::extractors::Role;
 use crate::handlers::HttpResult;
+use crate::ical;
 use crate::models;
-use crate::models::conversion::IntoDto;
-use crate::models::{NewClass, NewEvent, NewGuild, NewMember};
+use crate::models::conversion::{into_timetable, IntoDto};
+use crate::models::{
+    NewClass, NewEvent, NewEventType, NewGuild, NewLesson, NewMember, NewSubject,
+    NewTimetableException, NewTimetableVersion,
+};
+use crate::timetable;
+use crate::timetable_import;
+use crate::workload;
 use actix_web::web::{
     block, delete, get, post, put, scope, Data, Json, Path, Query, ServiceConfig,
 };
 use actix_web::HttpResponse;
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime};
+use chrono_tz::Tz;
 use dto::{
-    Class, Event, GetEventQueryParams, Guild, Member, MemberAcceptDto, MemberRole, SingleSnowflake,
-    Timetable,
+    BatchOperation, BatchQueryParams, BatchRes, BatchResult, CalendarQueryParams, Class,
+    EffectiveDay, EffectiveTimetableQueryParams, Event, EventCursor, EventImportError,
+    EventImportRes, EventRevision, EventType, GetEventQueryParams, Guild, ImportQueryParams,
+    Member, MemberAcceptDto, MemberRole, OccurrenceQueryParams, Page, Recurrence, SingleSnowflake,
+    SortOrder, Subject, Timestamp, Timetable, TimetableCycle, TimetableException,
+    TimetableExceptionQueryParams, TimetableImportError, TimetableImportFormat,
+    TimetableImportQueryParams, TimetableImportRes, TimetableLesson, TimetableQueryParams,
+    TimetableVersion, Trash, Trashed, WorkloadQueryParams,
 };
 use tracing::debug;
 use uuid::Uuid;
 
+/// The maximum amount of days of the effective timetable
+const MAX_EFFECTIVE_DAYS: u8 = 14;
+
+/// The maximum `limit` of a page of events
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// The maximum amount of operations in a batch
+const MAX_BATCH_SIZE: usize = 100;
+
+/// The amount of weeks of the workload without a `before`
+const DEFAULT_WORKLOAD_WEEKS: i64 = 16;
+
+/// The maximum amount of weeks of the workload
+const MAX_WORKLOAD_WEEKS: i64 = 53;
+
 pub(super) fn class_config(cfg: &mut ServiceConfig) {
     cfg.route("/classes", post().to(create_class))
         .route(
             "/classes/discord/{snowflake}",
             get().to(get_class_by_discord),
         )
+        .route("/classes/trash", get().to(get_deleted_classes))
         .route("/bot/guilds", put().to(edit_guild_settings))
         .route("/bot/guilds/{snowflake}", get().to(get_guild))
         .service(
@@ -31,6 +62,10 @@ pub(super) fn class_config(cfg: &mut ServiceConfig) {
                 .route("", get().to(get_class))
                 .route("", put().to(edit_class))
                 .route("", delete().to(delete_class))
+                .route("/restore", post().to(restore_class))
+                .route("/trash", get().to(get_trash))
+                .route("/trash/events/{uuid}/restore", post().to(restore_event))
+                .route("/trash/members/{uuid}/restore", post().to(restore_member))
                 .route("/members/{uuid}", get().to(get_member))
                 .route("/members/{uuid}", put().to(edit_member))
                 .route("/members/{uuid}", delete().to(delete_member))
@@ -38,15 +73,78 @@ pub(super) fn class_config(cfg: &mut ServiceConfig) {
                 .route("/join", post().to(request_join))
                 .route("/requests", get().to(get_join_requests))
                 .route("/requests/{uuid}", post().to(accept_member))
+                .route("/calendar.ics", get().to(get_calendar))
+                .route("/timetable.ics", get().to(get_timetable_calendar))
+                .route("/calendar/token", post().to(create_feed_token))
+                .route("/calendar/token", delete().to(delete_feed_token))
+                .route("/event-types", get().to(get_event_types))
+                .route("/event-types", post().to(create_event_type))
+                .route("/event-types/{id}", put().to(edit_event_type))
+                .route("/event-types/{id}", delete().to(delete_event_type))
                 .route("/events", get().to(get_events))
                 .route("/events", post().to(create_event))
+                .route("/events/import", post().to(import_events))
+                .route("/events/batch", post().to(batch_events))
                 .route("/events/{uuid}", get().to(get_event))
                 .route("/events/{uuid}", put().to(edit_event))
                 .route("/events/{uuid}", delete().to(delete_event))
+                .route("/events/{uuid}/history", get().to(get_event_history))
+                .route(
+                    "/events/{uuid}/history/{revision}/revert",
+                    post().to(revert_event),
+                )
+                .route("/events/{uuid}/done", put().to(mark_event_done))
+                .route("/events/{uuid}/done", delete().to(unmark_event_done))
+                .route("/workload", get().to(get_workload))
                 .route("/timetable", get().to(get_timetable))
                 .route("/timetable", post().to(create_timetable))
                 .route("/timetable", delete().to(delete_timetable))
                 .route("/timetable", put().to(edit_timetable))
+                .route("/timetable/cycle", get().to(get_timetable_cycle))
+                .route("/timetable/cycle", put().to(edit_timetable_cycle))
+                .route("/timetable/effective", get().to(get_effective_timetable))
+                .route("/timetable/import", post().to(import_timetable))
+                .route("/timetable/exceptions", get().to(get_timetable_exceptions))
+                .route(
+                    "/timetable/exceptions",
+                    post().to(create_timetable_exception),
+                )
+                .route(
+                    "/timetable/exceptions/{uuid}",
+                    put().to(edit_timetable_exception),
+                )
+                .route(
+                    "/timetable/exceptions/{uuid}",
+                    delete().to(delete_timetable_exception),
+                )
+                .route("/timetable/lessons", get().to(get_lessons))
+                .route("/timetable/lessons", post().to(create_lesson))
+                .route("/timetable/lessons/{uuid}", get().to(get_lesson))
+                .route("/timetable/lessons/{uuid}", put().to(edit_lesson))
+                .route("/timetable/lessons/{uuid}", delete().to(delete_lesson))
+                .route("/timetable/versions", get().to(get_timetable_versions))
+                .route("/timetable/versions", post().to(create_timetable_version))
+                .route(
+                    "/timetable/versions/{uuid}",
+                    put().to(edit_timetable_version),
+                )
+                .route(
+                    "/timetable/versions/{uuid}",
+                    delete().to(delete_timetable_version),
+                )
+                .route(
+                    "/timetable/versions/{uuid}/lessons",
+                    get().to(get_version_timetable),
+                )
+                .route(
+                    "/timetable/versions/{uuid}/lessons",
+                    put().to(edit_version_timetable),
+                )
+                .route("/subjects", get().to(get_subjects))
+                .route("/subjects", post().to(create_subject))
+                .route("/subjects/{uuid}", get().to(get_subject))
+                .route("/subjects/{uuid}", put().to(edit_subject))
+                .route("/subjects/{uuid}", delete().to(delete_subject))
                 .route("/link", post().to(link_class_with_discord)),
         );
 }
@@ -64,6 +162,13 @@ async fn get_class(class_path: Path<Uuid>, db: Data<Pool>, _role: Role) -> HttpR
 async fn create_class(class: Json<Class>, db: Data<Pool>, claims: Claims) -> HttpResult {
     debug!(?class, userid = %claims.uid, "create a new class");
 
+    if class.exam_limit < 0 {
+        return Err(ServiceErr::BadRequest("invalid-exam-limit"));
+    }
+    if class.timezone.parse::<Tz>().is_err() {
+        return Err(ServiceErr::BadRequest("invalid-timezone"));
+    }
+
     let (result_class, owner) = block::<_, _, ServiceErr>(move || {
         let class_id = uuid::Uuid::new_v4();
 
@@ -73,6 +178,9 @@ async fn create_class(class: Json<Class>, db: Data<Pool>, claims: Claims) -> Htt
             name: &class.name,
             description: &class.description,
             discord_id: None,
+            exam_limit: class.exam_limit,
+            strict_exam_limit: class.strict_exam_limit,
+            timezone: &class.timezone,
         };
 
         let class = actions::class::insert_class(&db, new_class)?;
@@ -105,6 +213,12 @@ async fn edit_class(
     if !role.has_rights() {
         return Err(ServiceErr::NoAdminPermissions);
     }
+    if new_class.exam_limit < 0 {
+        return Err(ServiceErr::BadRequest("invalid-exam-limit"));
+    }
+    if new_class.timezone.parse::<Tz>().is_err() {
+        return Err(ServiceErr::BadRequest("invalid-timezone"));
+    }
 
     let class = block(move || {
         let update_class = NewClass {
@@ -113,6 +227,9 @@ async fn edit_class(
             name: &new_class.name,
             description: &new_class.description,
             discord_id: None,
+            exam_limit: new_class.exam_limit,
+            strict_exam_limit: new_class.strict_exam_limit,
+            timezone: &new_class.timezone,
         };
 
         actions::class::update_class(&db, update_class)
@@ -141,6 +258,120 @@ async fn delete_class(class_id: Path<Uuid>, db: Data<Pool>, role: Role) -> HttpR
     })
 }
 
+/// The deleted classes of the user, only the owner can restore a class
+async fn get_deleted_classes(db: Data<Pool>, claims: Claims) -> HttpResult {
+    debug!(userid = %claims.uid, "get deleted classes");
+
+    let classes = block(move || actions::class::get_deleted_classes(&db, claims.uid))
+        .await?
+        .into_iter()
+        .map(|class| {
+            let deleted_at = class.deleted_at;
+            trashed(deleted_at, class.into_dto()?)
+        })
+        .collect::<Result<Vec<Trashed<Class>>, ServiceErr>>()?;
+
+    Ok(HttpResponse::Ok().json(classes))
+}
+
+async fn restore_class(class_id: Path<Uuid>, db: Data<Pool>, claims: Claims) -> HttpResult {
+    debug!(%class_id, userid = %claims.uid, "restore class");
+
+    let class = block(move || actions::class::restore_class(&db, claims.uid, *class_id))
+        .await?
+        .into_dto()?;
+
+    Ok(HttpResponse::Ok().json::<Class>(class))
+}
+
+async fn get_trash(class_id: Path<Uuid>, db: Data<Pool>, role: Role) -> HttpResult {
+    debug!(%class_id, ?role, "get trash");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+
+    let trash = block::<_, _, ServiceErr>(move || {
+        let events = actions::event::get_deleted_events(&db, *class_id)?
+            .into_iter()
+            .map(|event| {
+                let deleted_at = event.0.deleted_at;
+                trashed(deleted_at, event.into_dto()?)
+            })
+            .collect::<Result<_, ServiceErr>>()?;
+        let members = actions::class::get_deleted_members(&db, *class_id)?
+            .into_iter()
+            .map(|member| {
+                let deleted_at = member.0.deleted_at;
+                trashed(deleted_at, member.into_dto()?)
+            })
+            .collect::<Result<_, ServiceErr>>()?;
+        Ok(Trash { events, members })
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(trash))
+}
+
+async fn restore_event(
+    path: Path<(Uuid, Uuid)>,
+    role: Role,
+    claims: Claims,
+    db: Data<Pool>,
+) -> HttpResult {
+    let (class_id, event_id) = path.into_inner();
+
+    debug!(%class_id, %event_id, ?role, "restore event");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+
+    let event = block(move || actions::event::restore_event(&db, claims.uid, class_id, event_id))
+        .await?
+        .into_dto()?;
+
+    Ok(HttpResponse::Ok().json::<Event>(event))
+}
+
+async fn restore_member(path: Path<(Uuid, Uuid)>, role: Role, db: Data<Pool>) -> HttpResult {
+    let (class_id, member_id) = path.into_inner();
+
+    debug!(%class_id, %member_id, ?role, "restore member");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+
+    let member = block(move || {
+        let (deleted_member, _) = actions::class::get_deleted_members(&db, class_id)?
+            .into_iter()
+            .find(|(member, _)| member.user == member_id)
+            .ok_or(ServiceErr::NotFound)?;
+
+        // Can only restore members lower than self
+        if deleted_member.role <= role.0 as i32 {
+            return Err(ServiceErr::Unauthorized("not-enough-permissions"));
+        }
+
+        actions::class::restore_member(&db, member_id, class_id)?;
+        actions::class::get_member(&db, member_id, class_id)
+    })
+    .await?
+    .into_dto()?;
+
+    Ok(HttpResponse::Ok().json::<Member>(member))
+}
+
+fn trashed<T>(deleted_at: Option<NaiveDateTime>, item: T) -> Result<Trashed<T>, ServiceErr> {
+    Ok(Trashed {
+        deleted_at: deleted_at
+            .ok_or_else(|| ServiceErr::InternalServerError("not-deleted".to_string()))?
+            .into(),
+        item,
+    })
+}
+
 async fn get_member(path: Path<(Uuid, Uuid)>, _role: Role, db: Data<Pool>) -> HttpResult {
     let (class_id, member_id) = path.into_inner();
     debug!(%class_id, %member_id, ?_role, "get member");
@@ -348,232 +579,1958 @@ async fn accept_member(
     Ok(HttpResponse::Ok().body(response))
 }
 
-async fn get_event(path: Path<(String, Uuid)>, _role: Role, db: Data<Pool>) -> HttpResult {
-    debug!(event_id = %path.1, ?_role, "get event");
+async fn get_event(
+    path: Path<(Uuid, Uuid)>,
+    role: Role,
+    claims: Claims,
+    db: Data<Pool>,
+) -> HttpResult {
+    let (class_id, event_id) = path.into_inner();
+
+    debug!(%event_id, ?role, "get event");
 
-    let event = block(move || actions::event::get_event_by_id(&db, path.1))
-        .await?
-        .into_dto()?;
+    let event = block::<_, _, ServiceErr>(move || {
+        let event = actions::event::get_event_by_id(&db, class_id, event_id)?.into_dto()?;
+        Ok(with_completions(&db, class_id, claims.uid, &role, vec![event])?.remove(0))
+    })
+    .await?;
 
     Ok(HttpResponse::Ok().json(event))
 }
 
 async fn get_events(
     class_id: Path<Uuid>,
-    _role: Role,
+    role: Role,
+    claims: Claims,
     db: Data<Pool>,
     query: Query<GetEventQueryParams>,
 ) -> HttpResult {
-    let GetEventQueryParams { before, after } = query.into_inner();
+    let query = query.into_inner();
 
-    debug!(%class_id, ?_role, ?before, ?after, "get events");
+    debug!(%class_id, ?role, ?query, "get events");
 
-    let before = before.map(|b| b / 1000);
-    let after = after.map(|a| a / 1000);
+    if query.limit.is_some() {
+        return get_events_page(*class_id, role, claims, db, query).await;
+    }
 
-    let events = block(move || match (before, after) {
-        (None, None) => actions::event::get_events_by_class(&db, *class_id),
-        (Some(before), Some(after)) => actions::event::get_events_by_class_filtered_both(
-            &db,
-            *class_id,
-            NaiveDateTime::from_timestamp(before, 0),
-            NaiveDateTime::from_timestamp(after, 0),
-        ),
-        (Some(before), None) => actions::event::get_events_by_class_filtered_before(
-            &db,
-            *class_id,
-            NaiveDateTime::from_timestamp(before, 0),
-        ),
-        (None, Some(after)) => actions::event::get_events_by_class_filtered_after(
-            &db,
-            *class_id,
-            NaiveDateTime::from_timestamp(after, 0),
-        ),
+    let GetEventQueryParams {
+        before,
+        after,
+        done,
+        q,
+        sort,
+        subject,
+        ..
+    } = query;
+
+    let before = before.map(Timestamp::to_naive);
+    let after = after.map(Timestamp::to_naive);
+
+    let events = block::<_, _, ServiceErr>(move || {
+        let events = match (q, before, after) {
+            (Some(q), before, after) => {
+                actions::event::search_events(&db, &[*class_id], &q, after, before)
+            }
+            (None, None, None) => actions::event::get_events_by_class(&db, *class_id),
+            (None, Some(before), Some(after)) => {
+                actions::event::get_events_by_class_filtered_both(&db, *class_id, before, after)
+            }
+            (None, Some(before), None) => {
+                actions::event::get_events_by_class_filtered_before(&db, *class_id, before)
+            }
+            (None, None, Some(after)) => {
+                actions::event::get_events_by_class_filtered_after(&db, *class_id, after)
+            }
+        }?
+        .into_dto()?;
+
+        let events = with_completions(&db, *class_id, claims.uid, &role, events)?;
+
+        let mut events = events
+            .into_iter()
+            .filter(|event| done.is_none_or(|done| event.done == done))
+            .filter(|event| subject.is_none() || event.subject == subject)
+            .collect::<Vec<_>>();
+        match sort {
+            Some(SortOrder::Asc) => events.sort_by_key(|event| (event.start, event.id)),
+            Some(SortOrder::Desc) => {
+                events.sort_by_key(|event| std::cmp::Reverse((event.start, event.id)))
+            }
+            None => {}
+        }
+        Ok(events)
     })
-    .await?
-    .into_dto()?;
+    .await?;
 
     Ok(HttpResponse::Ok().json(events))
 }
 
-async fn create_event(
-    class_id: Path<Uuid>,
+/// Pages are sorted by `(start, id)`, the cursor is the last `(start, id)` of the previous page
+async fn get_events_page(
+    class_id: Uuid,
     role: Role,
+    claims: Claims,
     db: Data<Pool>,
-    event: Json<Event>,
+    query: GetEventQueryParams,
 ) -> HttpResult {
-    debug!(%class_id, ?role, ?event, "create event");
-
-    if !role.has_rights() {
-        return Err(ServiceErr::NoAdminPermissions);
+    let limit = query.limit.unwrap_or(MAX_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(ServiceErr::BadRequest("invalid-limit"));
     }
+    let cursor = query
+        .cursor
+        .as_deref()
+        .map(str::parse::<EventCursor>)
+        .transpose()
+        .map_err(|_| ServiceErr::BadRequest("invalid-cursor"))?
+        .map(|cursor| (cursor.start.to_naive(), cursor.id));
+
+    let page = block::<_, _, ServiceErr>(move || {
+        let (events, next) = actions::event::get_events_page(
+            &db,
+            class_id,
+            EventPageQuery {
+                after: query.after.map(|after| after.to_naive()),
+                before: query.before.map(|before| before.to_naive()),
+                search: query.q.as_deref(),
+                subject: query.subject.as_deref(),
+                done: query.done.map(|done| (claims.uid, done)),
+                cursor,
+                descending: query.sort == Some(SortOrder::Desc),
+                limit,
+            },
+        )?;
 
-    let event = block(move || {
-        let end = event
-            .end
-            .map(|ts| chrono::NaiveDateTime::from_timestamp(ts / 1000, 0));
-        let notification = event
-            .notification
-            .map(|ts| chrono::NaiveDateTime::from_timestamp(ts / 1000, 0));
-
-        let new_event = NewEvent {
-            id: uuid::Uuid::new_v4(),
-            class: *class_id,
-            e_type: event.r#type as i32,
-            name: &event.name,
-            start: &chrono::NaiveDateTime::from_timestamp(event.start / 1000, 0),
-            end: end.as_ref(),
-            description: &event.description,
-            notification: notification.as_ref(),
-        };
-
-        actions::event::insert_event(&db, new_event)
+        Ok(Page {
+            items: with_completions(&db, class_id, claims.uid, &role, events.into_dto()?)?,
+            next: next.map(|(start, id)| {
+                EventCursor {
+                    start: start.into(),
+                    id,
+                }
+                .to_string()
+            }),
+        })
     })
-    .await?
-    .into_dto()?;
+    .await?;
 
-    Ok(HttpResponse::Created().json(event))
+    Ok(HttpResponse::Ok().json(page))
 }
 
-async fn edit_event(
+/// Sets whether the member has marked the events as done, admins also see how many members did
+fn with_completions(
+    db: &Pool,
+    class_id: Uuid,
+    user_id: Uuid,
+    role: &Role,
+    events: Vec<Event>,
+) -> Result<Vec<Event>, ServiceErr> {
+    let done_events = actions::event::get_done_events(db, &[class_id], user_id)?;
+    let counts = if role.has_rights() {
+        let event_ids = events.iter().map(|event| event.id).collect::<Vec<_>>();
+        Some(actions::event::get_completion_counts(
+            db, class_id, &event_ids,
+        )?)
+    } else {
+        None
+    };
+
+    Ok(events
+        .into_iter()
+        .map(|event| Event {
+            done: done_events.contains(&event.id),
+            completions: counts
+                .as_ref()
+                .map(|counts| counts.get(&event.id).copied().unwrap_or(0)),
+            ..event
+        })
+        .collect())
+}
+
+async fn mark_event_done(
     path: Path<(Uuid, Uuid)>,
     role: Role,
+    claims: Claims,
     db: Data<Pool>,
-    event: Json<Event>,
 ) -> HttpResult {
     let (class_id, event_id) = path.into_inner();
 
-    debug!(%class_id, %event_id, ?role, ?event, "edit event");
+    debug!(%class_id, %event_id, ?role, userid = %claims.uid, "mark event as done");
 
-    if !role.has_rights() {
-        return Err(ServiceErr::NoAdminPermissions);
-    }
+    let event = block::<_, _, ServiceErr>(move || {
+        let (event, notifications) = actions::event::get_event_by_id(&db, class_id, event_id)?;
 
-    let event = block(move || {
-        let end = event
-            .end
-            .map(|ts| chrono::NaiveDateTime::from_timestamp(ts / 1000, 0));
-        let notification = event
-            .notification
-            .map(|ts| chrono::NaiveDateTime::from_timestamp(ts / 1000, 0));
-        let new_event = NewEvent {
-            id: event_id,
-            class: class_id,
-            e_type: event.r#type as i32,
-            name: &event.name,
-            start: &chrono::NaiveDateTime::from_timestamp(event.start / 1000, 0),
-            end: end.as_ref(),
-            description: &event.description,
-            notification: notification.as_ref(),
-        };
+        actions::event::mark_event_done(
+            &db,
+            models::EventCompletion {
+                event: event_id,
+                user: claims.uid,
+                class: class_id,
+                done_at: chrono::Utc::now().naive_utc(),
+            },
+        )?;
 
-        actions::event::update_event(&db, new_event)
+        let event = (event, notifications).into_dto()?;
+        Ok(with_completions(&db, class_id, claims.uid, &role, vec![event])?.remove(0))
     })
-    .await?
-    .into_dto()?;
+    .await?;
 
     Ok(HttpResponse::Ok().json(event))
 }
 
-async fn delete_event(path: Path<(String, Uuid)>, role: Role, db: Data<Pool>) -> HttpResult {
-    debug!(class_id = ?path.0, event_id = ?path.1, ?role, "delete event");
+async fn unmark_event_done(
+    path: Path<(Uuid, Uuid)>,
+    _role: Role,
+    claims: Claims,
+    db: Data<Pool>,
+) -> HttpResult {
+    let (class_id, event_id) = path.into_inner();
 
-    if !role.has_rights() {
-        return Err(ServiceErr::NoAdminPermissions);
-    }
+    debug!(%class_id, %event_id, ?_role, userid = %claims.uid, "unmark event as done");
 
-    let deleted = block(move || actions::event::delete_event(&db, path.1)).await?;
+    let deleted =
+        block(move || actions::event::unmark_event_done(&db, event_id, claims.uid)).await?;
 
     Ok(match deleted {
-        0 => HttpResponse::NotFound().body("Event not found"),
-        1 => HttpResponse::Ok().body("Deleted event."),
+        0 => HttpResponse::NotFound().body("Event not marked as done"),
+        1 => HttpResponse::Ok().body("Unmarked event as done."),
         _ => unreachable!(),
     })
 }
 
-async fn get_timetable(class_id: Path<Uuid>, _role: Role, db: Data<Pool>) -> HttpResult {
-    debug!(%class_id, ?_role, "get timetable");
+/// The calendar feed can't use the `Role` extractor, since calendar apps can't send a Bearer token.
+/// Instead, a revocable per-member feed token is passed in the query.
+async fn get_calendar(
+    class_id: Path<Uuid>,
+    db: Data<Pool>,
+    query: Query<CalendarQueryParams>,
+) -> HttpResult {
+    debug!(%class_id, "get calendar");
+
+    let token = query.into_inner().token;
 
-    let timetable = block(move || actions::class::get_timetable(&db, *class_id))
-        .await?
-        .timetable;
+    let calendar = block::<_, _, ServiceErr>(move || {
+        check_feed_token(&db, token, *class_id)?;
+
+        let (class, _) = actions::class::get_class(&db, *class_id)?.ok_or(ServiceErr::NotFound)?;
+        let events = actions::event::get_series_by_class(&db, *class_id)?;
+        let types = actions::event::get_event_types(&db, *class_id)?;
+
+        ical::render_calendar(
+            &class.name,
+            class.tz()?,
+            events,
+            &types,
+            chrono::Utc::now().naive_utc(),
+        )
+    })
+    .await?;
 
     Ok(HttpResponse::Ok()
-        .header("content-type", "application/json")
-        .body(timetable))
+        .header("content-type", "text/calendar; charset=utf-8")
+        .body(calendar))
 }
 
-async fn edit_timetable(
+/// The lessons of all versions of the timetable as weekly recurring events, with the same feed token as
+/// the calendar feed. Lessons on holidays are left out.
+async fn get_timetable_calendar(
     class_id: Path<Uuid>,
-    role: Role,
     db: Data<Pool>,
-    table: Json<Timetable>,
+    query: Query<CalendarQueryParams>,
 ) -> HttpResult {
-    debug!(%class_id, ?role, ?table, "edit timetable");
+    debug!(%class_id, "get timetable calendar");
+
+    let token = query.into_inner().token;
+
+    let calendar = block::<_, _, ServiceErr>(move || {
+        check_feed_token(&db, token, *class_id)?;
+
+        let (class, _) = actions::class::get_class(&db, *class_id)?.ok_or(ServiceErr::NotFound)?;
+        let timezone = class.tz()?;
+        let today = class_date(&class, None)?;
+        let (table, lessons) = actions::timetable::get_timetable(&db, *class_id)?;
+        let cycle: TimetableCycle = table.into_dto()?;
+        let versions = actions::timetable::get_versions(&db, *class_id)?
+            .into_iter()
+            .map(|version| {
+                let range = timetable::version_range(&version, today);
+                (version.id, range)
+            })
+            .collect::<Vec<_>>();
+
+        let first = versions.iter().map(|(_, (from, _))| *from).min();
+        let last = versions.iter().map(|(_, (_, until))| *until).max();
+        let holidays = match (first, last) {
+            (Some(first), Some(last)) => holiday_events(&db, *class_id, first, last)?,
+            _ => vec![],
+        };
+        let holidays = timetable::holiday_dates(&holidays, timezone);
+
+        let recurring = versions
+            .iter()
+            .flat_map(|(version, range)| {
+                let lessons = lessons
+                    .iter()
+                    .filter(move |lesson| lesson.version == *version);
+                timetable::recurring_lessons(lessons, &cycle, *range, &holidays, timezone)
+            })
+            .collect::<Vec<_>>();
+
+        Ok(ical::render_timetable(
+            &class.name,
+            timezone,
+            &recurring,
+            chrono::Utc::now().naive_utc(),
+        ))
+    })
+    .await?;
 
-    if !role.has_rights() {
-        return Err(ServiceErr::NoAdminPermissions);
-    }
+    Ok(HttpResponse::Ok()
+        .header("content-type", "text/calendar; charset=utf-8")
+        .body(calendar))
+}
 
-    let table = table
-        .into_inner()
-        .iter_mut()
-        .map(|day| {
-            day.sort_unstable();
-            day.clone() // todo oh
-        })
-        .collect::<Vec<_>>();
+/// Calendar apps can't send a Bearer token, the feeds use a feed token of a member in the query instead
+fn check_feed_token(db: &Pool, token: Uuid, class_id: Uuid) -> Result<(), ServiceErr> {
+    let feed_token = actions::class::get_feed_token(db, token).map_err(|err| match err {
+        ServiceErr::NotFound => ServiceErr::Unauthorized("invalid-feed-token"),
+        err => err,
+    })?;
+    if feed_token.class != class_id {
+        return Err(ServiceErr::Unauthorized("invalid-feed-token"));
+    }
 
-    let timetable = block(move || {
-        actions::class::update_timetable(
-            &db,
-            models::Timetable {
-                class: *class_id,
-                timetable: serde_json::to_string(&table).map_err(|_| {
-                    ServiceErr::InternalServerError("serialize-timetable".to_string())
-                })?,
-            },
-        )
-    })
-    .await?
-    .timetable;
+    let (member, _) = actions::class::get_member(db, feed_token.user, feed_token.class)?;
+    if member.role >= models::MemberRole::PENDING {
+        return Err(ServiceErr::Unauthorized("no-access"));
+    }
+    Ok(())
+}
 
-    Ok(HttpResponse::Ok()
-        .header("content-type", "application/json")
-        .body(timetable))
+/// The occurrences of the holidays of the class that overlap with the days, in any timezone
+fn holiday_events(
+    db: &Pool,
+    class_id: Uuid,
+    first: NaiveDate,
+    last: NaiveDate,
+) -> Result<Vec<models::Event>, ServiceErr> {
+    let after = (first - chrono::Duration::days(1))
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    let before = (last + chrono::Duration::days(2))
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    Ok(
+        actions::event::get_events_by_class_filtered_both(db, class_id, before, after)?
+            .into_iter()
+            .map(|(event, _)| event)
+            .filter(|event| event.e_type == EventType::HOLIDAYS)
+            .collect(),
+    )
 }
 
-async fn create_timetable(class_id: Path<Uuid>, role: Role, db: Data<Pool>) -> HttpResult {
-    debug!(%class_id, ?role, "create timetable");
+async fn create_feed_token(
+    class_id: Path<Uuid>,
+    _role: Role,
+    db: Data<Pool>,
+    claims: Claims,
+) -> HttpResult {
+    debug!(%class_id, userid = %claims.uid, "create calendar feed token");
 
-    if !role.has_rights() {
-        return Err(ServiceErr::NoAdminPermissions);
+    // the bot is let through as a member, but a token belongs to a member
+    if claims.uid.is_nil() {
+        return Ok(HttpResponse::Forbidden().body("members-only"));
     }
 
-    let timetable = block(move || actions::class::create_timetable(&db, class_id.into_inner()))
+    let token = block(move || actions::class::create_feed_token(&db, claims.uid, *class_id))
         .await?
-        .timetable;
+        .into_dto()?;
 
-    Ok(HttpResponse::Ok()
-        .header("content-type", "application/json")
-        .body(timetable))
+    Ok(HttpResponse::Created().json(token))
 }
 
-async fn delete_timetable(class_id: Path<Uuid>, role: Role, db: Data<Pool>) -> HttpResult {
-    debug!(%class_id, ?role, "delete timetable");
+async fn delete_feed_token(
+    class_id: Path<Uuid>,
+    _role: Role,
+    db: Data<Pool>,
+    claims: Claims,
+) -> HttpResult {
+    debug!(%class_id, userid = %claims.uid, "delete calendar feed token");
+
+    let deleted =
+        block(move || actions::class::delete_feed_token(&db, claims.uid, *class_id)).await?;
+
+    Ok(match deleted {
+        0 => HttpResponse::NotFound().body("Feed token not found"),
+        1 => HttpResponse::Ok().body("Deleted feed token."),
+        _ => unreachable!(),
+    })
+}
+
+async fn create_event(
+    class_id: Path<Uuid>,
+    role: Role,
+    claims: Claims,
+    db: Data<Pool>,
+    event: Json<Event>,
+) -> HttpResult {
+    debug!(%class_id, ?role, ?event, "create event");
 
     if !role.has_rights() {
         return Err(ServiceErr::NoAdminPermissions);
     }
 
-    let delete_count =
-        block(move || actions::class::delete_timetable(&db, class_id.into_inner())).await?;
+    let recurrence = event.recurrence.as_ref();
+    if !recurrence.is_none_or(Recurrence::is_valid) {
+        return Err(ServiceErr::BadRequest("invalid-recurrence"));
+    }
 
-    Ok(match delete_count {
-        0 => HttpResponse::NotFound().body("Timetable not found"),
-        1 => HttpResponse::Ok().body("Deleted timetable."),
-        _ => unreachable!(),
+    let (event, warnings) = block::<_, _, ServiceErr>(move || {
+        let e_type = event_type_of_class(&db, *class_id, event.r#type)?;
+        let subjects = actions::subject::get_subjects(&db, *class_id)?;
+        let subject = subject_of_class(&subjects, event.subject_id, event.subject.as_deref())?;
+
+        let start = event.start.to_naive();
+        let end = event.end.map(|ts| ts.to_naive());
+        let notifications = if event.notifications.is_empty() {
+            e_type
+                .reminders
+                .iter()
+                .map(|seconds| start - chrono::Duration::seconds(*seconds as i64))
+                .collect()
+        } else {
+            notification_times(&event)
+        };
+        let recurrence = event.recurrence.as_ref();
+        let rec_until = recurrence.and_then(|rec| rec.until).map(|ts| ts.to_naive());
+        let rec_exceptions = recurrence_exceptions(recurrence);
+
+        let new_event = NewEvent {
+            id: uuid::Uuid::new_v4(),
+            class: *class_id,
+            e_type: event.r#type,
+            name: &event.name,
+            start: &start,
+            end: end.as_ref(),
+            description: &event.description,
+            rec_frequency: recurrence.map(|rec| rec.frequency.as_str()),
+            rec_interval: recurrence.map_or(1, |rec| rec.interval),
+            rec_until: rec_until.as_ref(),
+            rec_count: recurrence.and_then(|rec| rec.count),
+            rec_exceptions: &rec_exceptions,
+            series: None,
+            import_uid: None,
+            subject: subject.map(|subject| subject.name.as_str()),
+            subject_id: subject.map(|subject| subject.id),
+        };
+        let event = actions::event::insert_event(&db, claims.uid, new_event, &notifications)?;
+        let warnings = actions::event::exam_warnings(&db, &event.0)?;
+        Ok((event, warnings))
     })
+    .await?;
+    let mut event: Event = event.into_dto()?;
+    event.warnings = warnings;
+
+    Ok(HttpResponse::Created().json(event))
+}
+
+/// Imports the events of an iCalendar file, the body is the file.
+/// Importing the same file again updates the events instead of creating duplicates.
+async fn import_events(
+    class_id: Path<Uuid>,
+    role: Role,
+    claims: Claims,
+    db: Data<Pool>,
+    query: Query<ImportQueryParams>,
+    calendar: String,
+) -> HttpResult {
+    let preview = query.into_inner().preview;
+
+    debug!(%class_id, ?role, preview, "import events");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+
+    let (parsed, errors) = ical::parse_calendar(&calendar).map_err(ServiceErr::BadRequest)?;
+
+    let (created, updated) = block(move || {
+        let types = actions::event::get_event_types(&db, *class_id)?;
+
+        let new_events = parsed
+            .iter()
+            .map(|event| {
+                // a category with the name of a type of the class is used before the guessed type
+                let e_type = types
+                    .iter()
+                    .filter(|typ| typ.class.is_some())
+                    .find(|typ| {
+                        event.categories.iter().any(|category| {
+                            category.trim().to_lowercase() == typ.display.to_lowercase()
+                        })
+                    })
+                    .map_or(event.e_type, |typ| typ.id);
+
+                let new_event = NewEvent {
+                    id: uuid::Uuid::new_v4(),
+                    class: *class_id,
+                    e_type,
+                    name: &event.name,
+                    start: &event.start,
+                    end: event.end.as_ref(),
+                    description: &event.description,
+                    rec_frequency: event.rec_frequency.map(|freq| freq.as_str()),
+                    rec_interval: event.rec_interval,
+                    rec_until: event.rec_until.as_ref(),
+                    rec_count: event.rec_count,
+                    rec_exceptions: &event.rec_exceptions,
+                    series: None,
+                    import_uid: Some(&event.uid),
+                    subject: None,
+                    subject_id: None,
+                };
+                (new_event, &*event.notifications)
+            })
+            .collect();
+
+        actions::event::import_events(&db, claims.uid, *class_id, new_events, preview)
+    })
+    .await?;
+
+    let errors = errors
+        .into_iter()
+        .map(|err| EventImportError {
+            uid: err.uid,
+            error: err.error.to_string(),
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(EventImportRes {
+        created,
+        updated,
+        errors,
+    }))
+}
+
+/// The owned values of a `NewEvent` of a batch operation
+struct BatchEvent {
+    event: Event,
+    subject: Option<models::Subject>,
+    start: NaiveDateTime,
+    end: Option<NaiveDateTime>,
+    rec_until: Option<NaiveDateTime>,
+    rec_exceptions: Vec<NaiveDateTime>,
+    notifications: Vec<NaiveDateTime>,
+}
+
+impl BatchEvent {
+    /// Validates the event, new events get the reminders of their type if they have no notifications
+    fn new(
+        event: Event,
+        types: &[models::EventType],
+        subjects: &[models::Subject],
+        new: bool,
+    ) -> Result<Self, ServiceErr> {
+        let recurrence = event.recurrence.as_ref();
+        if !recurrence.is_none_or(Recurrence::is_valid) {
+            return Err(ServiceErr::BadRequest("invalid-recurrence"));
+        }
+        let e_type = types
+            .iter()
+            .find(|typ| typ.id == event.r#type)
+            .ok_or(ServiceErr::BadRequest("invalid-event-type"))?;
+        let subject =
+            subject_of_class(subjects, event.subject_id, event.subject.as_deref())?.cloned();
+
+        let start = event.start.to_naive();
+        let notifications = if new && event.notifications.is_empty() {
+            e_type
+                .reminders
+                .iter()
+                .map(|seconds| start - chrono::Duration::seconds(*seconds as i64))
+                .collect()
+        } else {
+            notification_times(&event)
+        };
+
+        Ok(BatchEvent {
+            start,
+            end: event.end.map(|ts| ts.to_naive()),
+            rec_until: recurrence.and_then(|rec| rec.until).map(|ts| ts.to_naive()),
+            rec_exceptions: recurrence_exceptions(recurrence),
+            notifications,
+            subject,
+            event,
+        })
+    }
+
+    fn new_event(&self, id: Uuid, class_id: Uuid) -> NewEvent<'_> {
+        let recurrence = self.event.recurrence.as_ref();
+        NewEvent {
+            id,
+            class: class_id,
+            e_type: self.event.r#type,
+            name: &self.event.name,
+            start: &self.start,
+            end: self.end.as_ref(),
+            description: &self.event.description,
+            rec_frequency: recurrence.map(|rec| rec.frequency.as_str()),
+            rec_interval: recurrence.map_or(1, |rec| rec.interval),
+            rec_until: self.rec_until.as_ref(),
+            rec_count: recurrence.and_then(|rec| rec.count),
+            rec_exceptions: &self.rec_exceptions,
+            series: None,
+            import_uid: None,
+            subject: self.subject.as_ref().map(|subject| subject.name.as_str()),
+            subject_id: self.subject.as_ref().map(|subject| subject.id),
+        }
+    }
+}
+
+enum PreparedOperation {
+    Create(BatchEvent),
+    Update(BatchEvent),
+    Delete(Uuid),
+}
+
+impl PreparedOperation {
+    fn as_operation(&self, class_id: Uuid) -> actions::event::BatchOperation<'_> {
+        use actions::event::BatchOperation as Op;
+        match self {
+            PreparedOperation::Create(event) => Op::Create(
+                event.new_event(uuid::Uuid::new_v4(), class_id),
+                &event.notifications,
+            ),
+            PreparedOperation::Update(event) => Op::Update(
+                event.new_event(event.event.id, class_id),
+                &event.notifications,
+            ),
+            PreparedOperation::Delete(id) => Op::Delete(*id),
+        }
+    }
+}
+
+/// Runs the operations in a single transaction, see `actions::event::batch_events`.
+/// Operations that are invalid fail without being run.
+async fn batch_events(
+    class_id: Path<Uuid>,
+    role: Role,
+    claims: Claims,
+    db: Data<Pool>,
+    query: Query<BatchQueryParams>,
+    operations: Json<Vec<BatchOperation>>,
+) -> HttpResult {
+    let best_effort = query.into_inner().best_effort;
+
+    debug!(%class_id, ?role, best_effort, amount = operations.len(), "batch events");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+
+    if operations.len() > MAX_BATCH_SIZE {
+        return Err(ServiceErr::BadRequest("batch-too-large"));
+    }
+
+    let res = block::<_, _, ServiceErr>(move || {
+        let class_id = *class_id;
+        let types = actions::event::get_event_types(&db, class_id)?;
+        let subjects = actions::subject::get_subjects(&db, class_id)?;
+
+        let prepared = operations
+            .into_inner()
+            .into_iter()
+            .map(|operation| match operation {
+                BatchOperation::Create { event } => {
+                    BatchEvent::new(event, &types, &subjects, true).map(PreparedOperation::Create)
+                }
+                BatchOperation::Update { event } => {
+                    BatchEvent::new(event, &types, &subjects, false).map(PreparedOperation::Update)
+                }
+                BatchOperation::Delete { id } => Ok(PreparedOperation::Delete(id)),
+            })
+            .collect::<Vec<_>>();
+
+        let all_valid = prepared.iter().all(Result::is_ok);
+        let operations = if all_valid || best_effort {
+            prepared
+                .iter()
+                .flatten()
+                .map(|operation| operation.as_operation(class_id))
+                .collect()
+        } else {
+            vec![]
+        };
+
+        let (committed, results) =
+            actions::event::batch_events(&db, claims.uid, class_id, operations, best_effort)?;
+
+        let mut results = results.into_iter();
+        let results = prepared
+            .iter()
+            .map(|operation| {
+                if let Err(err) = operation {
+                    return Ok(BatchResult::Failed {
+                        error: batch_error(err).to_string(),
+                    });
+                }
+                Ok(match results.next() {
+                    Some(Ok(BatchOutcome::Created(event))) => BatchResult::Created {
+                        event: event.into_dto()?,
+                    },
+                    Some(Ok(BatchOutcome::Updated(event))) => BatchResult::Updated {
+                        event: event.into_dto()?,
+                    },
+                    Some(Ok(BatchOutcome::Deleted(id))) => BatchResult::Deleted { id },
+                    Some(Err(err)) => BatchResult::Failed {
+                        error: batch_error(&err).to_string(),
+                    },
+                    None => BatchResult::Skipped,
+                })
+            })
+            .collect::<Result<_, ServiceErr>>()?;
+
+        Ok(BatchRes {
+            committed: committed && (all_valid || best_effort),
+            results,
+        })
+    })
+    .await?;
+
+    Ok(if res.committed {
+        HttpResponse::Ok().json(res)
+    } else {
+        HttpResponse::BadRequest().json(res)
+    })
+}
+
+/// The error of a failed batch operation, server errors are not exposed
+fn batch_error(err: &ServiceErr) -> &'static str {
+    match err {
+        ServiceErr::NotFound => "not-found",
+        ServiceErr::BadRequest(msg) | ServiceErr::Conflict(msg) | ServiceErr::Unauthorized(msg) => {
+            msg
+        }
+        _ => "internal-error",
+    }
+}
+
+/// Without an `occurrence`, the whole event (or recurring series) is replaced.
+/// With an `occurrence`, only that occurrence is replaced by a new standalone event.
+async fn edit_event(
+    path: Path<(Uuid, Uuid)>,
+    role: Role,
+    claims: Claims,
+    db: Data<Pool>,
+    event: Json<Event>,
+    query: Query<OccurrenceQueryParams>,
+) -> HttpResult {
+    let (class_id, event_id) = path.into_inner();
+    let occurrence = query.into_inner().occurrence;
+
+    debug!(%class_id, %event_id, ?role, ?event, ?occurrence, "edit event");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+
+    let recurrence = event.recurrence.as_ref();
+    if !recurrence.is_none_or(Recurrence::is_valid) {
+        return Err(ServiceErr::BadRequest("invalid-recurrence"));
+    }
+
+    let (event, warnings) = block::<_, _, ServiceErr>(move || {
+        event_type_of_class(&db, class_id, event.r#type)?;
+        let subjects = actions::subject::get_subjects(&db, class_id)?;
+        let subject = subject_of_class(&subjects, event.subject_id, event.subject.as_deref())?;
+
+        let end = event.end.map(|ts| ts.to_naive());
+        let notifications = notification_times(&event);
+        let start = event.start.to_naive();
+
+        if let Some(occurrence) = occurrence {
+            let new_event = NewEvent {
+                id: uuid::Uuid::new_v4(),
+                class: class_id,
+                e_type: event.r#type,
+                name: &event.name,
+                start: &start,
+                end: end.as_ref(),
+                description: &event.description,
+                rec_frequency: None,
+                rec_interval: 1,
+                rec_until: None,
+                rec_count: None,
+                rec_exceptions: &[],
+                series: Some(event_id),
+                import_uid: None,
+                subject: subject.map(|subject| subject.name.as_str()),
+                subject_id: subject.map(|subject| subject.id),
+            };
+            let event = actions::event::detach_occurrence(
+                &db,
+                claims.uid,
+                occurrence.to_naive(),
+                new_event,
+                &notifications,
+            )?;
+            let warnings = actions::event::exam_warnings(&db, &event.0)?;
+            return Ok((event, warnings));
+        }
+
+        let (old_event, _) = actions::event::get_event_by_id(&db, class_id, event_id)?;
+
+        let recurrence = event.recurrence.as_ref();
+        let rec_until = recurrence.and_then(|rec| rec.until).map(|ts| ts.to_naive());
+        let rec_exceptions = recurrence_exceptions(recurrence);
+
+        let new_event = NewEvent {
+            id: event_id,
+            class: class_id,
+            e_type: event.r#type,
+            name: &event.name,
+            start: &start,
+            end: end.as_ref(),
+            description: &event.description,
+            rec_frequency: recurrence.map(|rec| rec.frequency.as_str()),
+            rec_interval: recurrence.map_or(1, |rec| rec.interval),
+            rec_until: rec_until.as_ref(),
+            rec_count: recurrence.and_then(|rec| rec.count),
+            rec_exceptions: &rec_exceptions,
+            series: old_event.series,
+            import_uid: old_event.import_uid.as_deref(),
+            subject: subject.map(|subject| subject.name.as_str()),
+            subject_id: subject.map(|subject| subject.id),
+        };
+        let event = actions::event::update_event(&db, claims.uid, new_event, &notifications)?;
+        let warnings = actions::event::exam_warnings(&db, &event.0)?;
+        Ok((event, warnings))
+    })
+    .await?;
+    let mut event: Event = event.into_dto()?;
+    event.warnings = warnings;
+
+    Ok(HttpResponse::Ok().json(event))
+}
+
+/// Without an `occurrence`, the whole event (or recurring series) is deleted.
+async fn delete_event(
+    path: Path<(Uuid, Uuid)>,
+    role: Role,
+    claims: Claims,
+    db: Data<Pool>,
+    query: Query<OccurrenceQueryParams>,
+) -> HttpResult {
+    let (class_id, event_id) = path.into_inner();
+    let occurrence = query.into_inner().occurrence;
+
+    debug!(%class_id, %event_id, ?role, ?occurrence, "delete event");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+
+    if let Some(occurrence) = occurrence {
+        block(move || {
+            actions::event::exclude_occurrence(
+                &db,
+                claims.uid,
+                class_id,
+                event_id,
+                occurrence.to_naive(),
+            )
+        })
+        .await?;
+
+        return Ok(HttpResponse::Ok().body("Deleted occurrence."));
+    }
+
+    let deleted =
+        block(move || actions::event::delete_event(&db, claims.uid, class_id, event_id)).await?;
+
+    Ok(match deleted {
+        0 => HttpResponse::NotFound().body("Event not found"),
+        1 => HttpResponse::Ok().body("Deleted event."),
+        _ => unreachable!(),
+    })
+}
+
+async fn get_event_history(path: Path<(Uuid, Uuid)>, _role: Role, db: Data<Pool>) -> HttpResult {
+    let (class_id, event_id) = path.into_inner();
+
+    debug!(%class_id, %event_id, ?_role, "get event history");
+
+    let history = block(move || actions::event::get_event_history(&db, class_id, event_id))
+        .await?
+        .into_dto()?;
+
+    Ok(HttpResponse::Ok().json::<Vec<EventRevision>>(history))
+}
+
+/// Restores the event as it was after the revision, this is recorded as a new revision.
+/// A deleted event can be restored by reverting to the revision before the deletion.
+async fn revert_event(
+    path: Path<(Uuid, Uuid, i32)>,
+    role: Role,
+    claims: Claims,
+    db: Data<Pool>,
+) -> HttpResult {
+    let (class_id, event_id, revision_id) = path.into_inner();
+
+    debug!(%class_id, %event_id, %revision_id, ?role, "revert event");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+
+    let event = block(move || {
+        let revision: EventRevision =
+            actions::event::get_event_revision(&db, class_id, event_id, revision_id)?.into_dto()?;
+        let event = revision
+            .snapshot
+            .ok_or(ServiceErr::BadRequest("revision-deleted"))?;
+
+        // the type might have been deleted since
+        let e_type = match event_type_of_class(&db, class_id, event.r#type) {
+            Ok(e_type) => e_type.id,
+            Err(ServiceErr::BadRequest(_)) => EventType::OTHER,
+            Err(err) => return Err(err),
+        };
+        // so might the subject
+        let subjects = actions::subject::get_subjects(&db, class_id)?;
+        let subject =
+            subject_of_class(&subjects, event.subject_id, event.subject.as_deref()).unwrap_or(None);
+        let start = event.start.to_naive();
+        let end = event.end.map(|ts| ts.to_naive());
+        let notifications = notification_times(&event);
+        let recurrence = event.recurrence.as_ref();
+        let rec_until = recurrence.and_then(|rec| rec.until).map(|ts| ts.to_naive());
+        let rec_exceptions = recurrence_exceptions(recurrence);
+
+        let new_event = NewEvent {
+            id: event_id,
+            class: class_id,
+            e_type,
+            name: &event.name,
+            start: &start,
+            end: end.as_ref(),
+            description: &event.description,
+            rec_frequency: recurrence.map(|rec| rec.frequency.as_str()),
+            rec_interval: recurrence.map_or(1, |rec| rec.interval),
+            rec_until: rec_until.as_ref(),
+            rec_count: recurrence.and_then(|rec| rec.count),
+            rec_exceptions: &rec_exceptions,
+            series: None,
+            import_uid: None,
+            subject: subject.map(|subject| subject.name.as_str()),
+            subject_id: subject.map(|subject| subject.id),
+        };
+
+        actions::event::revert_event(&db, claims.uid, new_event, &notifications)
+    })
+    .await?
+    .into_dto()?;
+
+    Ok(HttpResponse::Ok().json::<Event>(event))
+}
+
+/// The type has to be built-in or one of the class
+fn event_type_of_class(
+    db: &Pool,
+    class_id: Uuid,
+    type_id: i32,
+) -> Result<models::EventType, ServiceErr> {
+    actions::event::get_event_type(db, class_id, type_id).map_err(|err| match err {
+        ServiceErr::NotFound => ServiceErr::BadRequest("invalid-event-type"),
+        err => err,
+    })
+}
+
+/// The date of the timestamp in the timezone of the class, today if there is no timestamp
+fn class_date(class: &models::Class, time: Option<Timestamp>) -> Result<NaiveDate, ServiceErr> {
+    Ok(time
+        .map(Timestamp::to_utc)
+        .unwrap_or_else(chrono::Utc::now)
+        .with_timezone(&class.tz()?)
+        .date_naive())
+}
+
+/// The id of the version of the timetable that is valid today
+fn current_version(db: &Pool, class_id: Uuid) -> Result<Uuid, ServiceErr> {
+    let (class, _) = actions::class::get_class(db, class_id)?.ok_or(ServiceErr::NotFound)?;
+    let versions = actions::timetable::get_versions(db, class_id)?;
+    timetable::active_version(&versions, class_date(&class, None)?)
+        .map(|version| version.id)
+        .ok_or(ServiceErr::BadRequest("no-active-version"))
+}
+
+/// The lessons of the version that is valid on the date, a date without a version has no lessons
+fn lessons_on(
+    versions: &[models::TimetableVersion],
+    lessons: &[models::Lesson],
+    date: NaiveDate,
+) -> Result<Timetable, ServiceErr> {
+    let version = match timetable::active_version(versions, date) {
+        Some(version) => version.id,
+        None => return Ok(Timetable::default()),
+    };
+    into_timetable(
+        lessons
+            .iter()
+            .filter(|lesson| lesson.version == version)
+            .cloned()
+            .collect(),
+    )
+}
+
+/// The subject of the class with the id, or with the name (ignoring the case) if there is no id
+fn subject_of_class<'a>(
+    subjects: &'a [models::Subject],
+    subject_id: Option<Uuid>,
+    subject: Option<&str>,
+) -> Result<Option<&'a models::Subject>, ServiceErr> {
+    let found = match (subject_id, subject) {
+        (Some(subject_id), _) => subjects.iter().find(|known| known.id == subject_id),
+        (None, Some(subject)) => {
+            let subject = subject.trim().to_lowercase();
+            subjects
+                .iter()
+                .find(|known| known.name.to_lowercase() == subject)
+        }
+        (None, None) => return Ok(None),
+    };
+    found
+        .map(Some)
+        .ok_or(ServiceErr::BadRequest("invalid-subject"))
+}
+
+/// A lesson with a `subject_id` gets the name of the subject, the actions look it up by its name
+fn name_subject(subjects: &[models::Subject], lesson: &mut dto::Lesson) -> Result<(), ServiceErr> {
+    if let Some(subject) = subject_of_class(subjects, lesson.subject_id, None)? {
+        lesson.subject = subject.name.clone();
+    }
+    Ok(())
+}
+
+fn notification_times(event: &Event) -> Vec<NaiveDateTime> {
+    event.notifications.iter().map(|ts| ts.to_naive()).collect()
+}
+
+fn recurrence_exceptions(recurrence: Option<&Recurrence>) -> Vec<NaiveDateTime> {
+    recurrence
+        .map(|rec| rec.exceptions.iter().map(|ts| ts.to_naive()).collect())
+        .unwrap_or_default()
+}
+
+async fn get_event_types(class_id: Path<Uuid>, _role: Role, db: Data<Pool>) -> HttpResult {
+    debug!(%class_id, "get event types");
+
+    let types = block(move || actions::event::get_event_types(&db, *class_id))
+        .await?
+        .into_dto()?;
+
+    Ok(HttpResponse::Ok().json::<Vec<EventType>>(types))
+}
+
+async fn create_event_type(
+    class_id: Path<Uuid>,
+    role: Role,
+    db: Data<Pool>,
+    event_type: Json<EventType>,
+) -> HttpResult {
+    debug!(%class_id, ?role, ?event_type, "create event type");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+    if !event_type.is_valid() {
+        return Err(ServiceErr::BadRequest("invalid-event-type"));
+    }
+
+    let event_type = block(move || {
+        let reminders = reminder_seconds(&event_type);
+        actions::event::insert_event_type(
+            &db,
+            NewEventType {
+                display: event_type.name.trim(),
+                class: *class_id,
+                colour: &event_type.colour,
+                reminders: &reminders,
+            },
+        )
+    })
+    .await?
+    .into_dto()?;
+
+    Ok(HttpResponse::Created().json::<EventType>(event_type))
+}
+
+/// Built-in types can't be changed
+async fn edit_event_type(
+    path: Path<(Uuid, i32)>,
+    role: Role,
+    db: Data<Pool>,
+    event_type: Json<EventType>,
+) -> HttpResult {
+    let (class_id, type_id) = path.into_inner();
+
+    debug!(%class_id, %type_id, ?role, ?event_type, "edit event type");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+    if !event_type.is_valid() {
+        return Err(ServiceErr::BadRequest("invalid-event-type"));
+    }
+
+    let event_type = block(move || {
+        let old_type = actions::event::get_event_type(&db, class_id, type_id)?;
+        if old_type.class.is_none() {
+            return Err(ServiceErr::BadRequest("built-in-event-type"));
+        }
+
+        let reminders = reminder_seconds(&event_type);
+        actions::event::update_event_type(
+            &db,
+            type_id,
+            NewEventType {
+                display: event_type.name.trim(),
+                class: class_id,
+                colour: &event_type.colour,
+                reminders: &reminders,
+            },
+        )
+    })
+    .await?
+    .into_dto()?;
+
+    Ok(HttpResponse::Ok().json::<EventType>(event_type))
+}
+
+/// The events of the type become events of the type `other`
+async fn delete_event_type(path: Path<(Uuid, i32)>, role: Role, db: Data<Pool>) -> HttpResult {
+    let (class_id, type_id) = path.into_inner();
+
+    debug!(%class_id, %type_id, ?role, "delete event type");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+
+    let deleted = block(move || {
+        let old_type = actions::event::get_event_type(&db, class_id, type_id)?;
+        if old_type.class.is_none() {
+            return Err(ServiceErr::BadRequest("built-in-event-type"));
+        }
+
+        actions::event::delete_event_type(&db, class_id, type_id)
+    })
+    .await?;
+
+    Ok(match deleted {
+        0 => HttpResponse::NotFound().body("Event type not found"),
+        1 => HttpResponse::Ok().body("Deleted event type."),
+        _ => unreachable!(),
+    })
+}
+
+/// The reminders of a valid type fit into an `i32`
+fn reminder_seconds(event_type: &EventType) -> Vec<i32> {
+    event_type
+        .reminders
+        .iter()
+        .map(|ms| (ms / 1000) as i32)
+        .collect()
+}
+
+/// The amount of events of each type per week, starting with the week of `after`
+async fn get_workload(
+    class_id: Path<Uuid>,
+    _role: Role,
+    db: Data<Pool>,
+    query: Query<WorkloadQueryParams>,
+) -> HttpResult {
+    let WorkloadQueryParams { before, after } = query.into_inner();
+
+    debug!(%class_id, ?before, ?after, "get workload");
+
+    let after = after.map_or_else(|| chrono::Utc::now().naive_utc(), |after| after.to_naive());
+    let before = before.map_or_else(
+        || after + chrono::Duration::weeks(DEFAULT_WORKLOAD_WEEKS),
+        |before| before.to_naive(),
+    );
+    if before <= after || before - after > chrono::Duration::weeks(MAX_WORKLOAD_WEEKS) {
+        return Err(ServiceErr::BadRequest("invalid-range"));
+    }
+
+    let workload = block::<_, _, ServiceErr>(move || {
+        let (class, _) = actions::class::get_class(&db, *class_id)?.ok_or(ServiceErr::NotFound)?;
+        let timezone = class.tz()?;
+        // events count for the whole week they start in
+        let week_start = workload::week_start(workload::week_of(after, timezone), timezone);
+        let events =
+            actions::event::get_events_by_class_filtered_both(&db, *class_id, before, week_start)?
+                .into_iter()
+                .map(|(event, _)| event)
+                .filter(|event| event.start >= week_start)
+                .collect::<Vec<_>>();
+
+        Ok(workload::workload(
+            &events,
+            after,
+            before,
+            timezone,
+            class.exam_limit,
+        ))
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(workload))
+}
+
+async fn get_timetable(
+    class_id: Path<Uuid>,
+    _role: Role,
+    db: Data<Pool>,
+    query: Query<TimetableQueryParams>,
+) -> HttpResult {
+    let date = query.into_inner().date;
+
+    debug!(%class_id, ?_role, ?date, "get timetable");
+
+    let (class, timetable, versions, lessons) = block::<_, _, ServiceErr>(move || {
+        let (class, _) = actions::class::get_class(&db, *class_id)?.ok_or(ServiceErr::NotFound)?;
+        let (timetable, lessons) = actions::timetable::get_timetable(&db, *class_id)?;
+        let versions = actions::timetable::get_versions(&db, *class_id)?;
+        Ok((class, timetable, versions, lessons))
+    })
+    .await?;
+
+    let day = class_date(&class, date)?;
+    let lessons = lessons_on(&versions, &lessons, day)?;
+    let lessons = match date {
+        Some(_) => IntoDto::<TimetableCycle>::into_dto(timetable)?.timetable_of_week(&lessons, day),
+        None => lessons,
+    };
+
+    Ok(HttpResponse::Ok().json::<Timetable>(lessons))
+}
+
+async fn get_timetable_cycle(class_id: Path<Uuid>, _role: Role, db: Data<Pool>) -> HttpResult {
+    debug!(%class_id, ?_role, "get timetable cycle");
+
+    let (timetable, _) = block(move || actions::timetable::get_timetable(&db, *class_id)).await?;
+
+    Ok(HttpResponse::Ok().json::<TimetableCycle>(timetable.into_dto()?))
+}
+
+async fn edit_timetable_cycle(
+    class_id: Path<Uuid>,
+    role: Role,
+    db: Data<Pool>,
+    cycle: Json<TimetableCycle>,
+) -> HttpResult {
+    debug!(%class_id, ?role, ?cycle, "edit timetable cycle");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+    if cycle.weeks == 0 || cycle.weeks > TimetableCycle::MAX_WEEKS {
+        return Err(ServiceErr::BadRequest("invalid-cycle-weeks"));
+    }
+
+    let timetable = block(move || {
+        actions::timetable::update_timetable_cycle(
+            &db,
+            *class_id,
+            cycle.weeks as i32,
+            cycle.anchor.to_naive().date(),
+        )
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json::<TimetableCycle>(timetable.into_dto()?))
+}
+
+async fn edit_timetable(
+    class_id: Path<Uuid>,
+    role: Role,
+    db: Data<Pool>,
+    table: Json<Timetable>,
+) -> HttpResult {
+    debug!(%class_id, ?role, ?table, "edit timetable");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+
+    let class_id = class_id.into_inner();
+    let lessons = block(move || {
+        let version = current_version(&db, class_id)?;
+        replace_lessons(&db, class_id, version, &table)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json::<Timetable>(into_timetable(lessons)?))
+}
+
+/// Imports the lessons of a CSV file or a WebUntis export, the body is the file.
+/// Replaces all lessons of the version like `edit_timetable`.
+async fn import_timetable(
+    class_id: Path<Uuid>,
+    role: Role,
+    db: Data<Pool>,
+    query: Query<TimetableImportQueryParams>,
+    file: String,
+) -> HttpResult {
+    let TimetableImportQueryParams {
+        format,
+        preview,
+        version,
+    } = query.into_inner();
+
+    debug!(%class_id, ?role, ?format, preview, ?version, "import timetable");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+
+    let (table, errors) = match format {
+        TimetableImportFormat::Csv => timetable_import::parse_csv(&file),
+        TimetableImportFormat::Untis => {
+            timetable_import::parse_untis(&file).map_err(ServiceErr::BadRequest)?
+        }
+    };
+    let errors = errors
+        .into_iter()
+        .map(|err| TimetableImportError {
+            row: err.row,
+            error: err.error.to_string(),
+        })
+        .collect();
+
+    if preview {
+        return Ok(HttpResponse::Ok().json(TimetableImportRes {
+            lesson_errors: dto::validate_timetable(&table),
+            timetable: table,
+            errors,
+        }));
+    }
+
+    let class_id = class_id.into_inner();
+    let lessons = block(move || {
+        let version = match version {
+            Some(version) => version,
+            None => current_version(&db, class_id)?,
+        };
+        replace_lessons(&db, class_id, version, &table)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(TimetableImportRes {
+        timetable: into_timetable(lessons)?,
+        errors,
+        lesson_errors: vec![],
+    }))
+}
+
+fn replace_lessons(
+    db: &Pool,
+    class_id: Uuid,
+    version: Uuid,
+    table: &Timetable,
+) -> Result<Vec<models::Lesson>, ServiceErr> {
+    let subjects = actions::subject::get_subjects(db, class_id)?;
+    let mut table = table.clone();
+    for lesson in table.iter_mut().flatten() {
+        name_subject(&subjects, lesson)?;
+    }
+
+    let errors = dto::validate_timetable(&table);
+    if !errors.is_empty() {
+        return Err(ServiceErr::InvalidTimetable(errors));
+    }
+
+    let new_lessons = table
+        .iter()
+        .enumerate()
+        .flat_map(|(weekday, day)| {
+            day.iter().map(move |lesson| {
+                (
+                    lesson.id,
+                    NewLesson::from_dto(class_id, version, weekday as u8, lesson),
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+
+    actions::timetable::update_timetable(db, class_id, version, &new_lessons)
+}
+
+async fn create_timetable(class_id: Path<Uuid>, role: Role, db: Data<Pool>) -> HttpResult {
+    debug!(%class_id, ?role, "create timetable");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+
+    block(move || actions::timetable::create_timetable(&db, class_id.into_inner())).await?;
+
+    Ok(HttpResponse::Ok().json(Timetable::default()))
+}
+
+async fn delete_timetable(class_id: Path<Uuid>, role: Role, db: Data<Pool>) -> HttpResult {
+    debug!(%class_id, ?role, "delete timetable");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+
+    let delete_count =
+        block(move || actions::timetable::delete_timetable(&db, class_id.into_inner())).await?;
+
+    Ok(match delete_count {
+        0 => HttpResponse::NotFound().body("Timetable not found"),
+        1 => HttpResponse::Ok().body("Deleted timetable."),
+        _ => unreachable!(),
+    })
+}
+
+async fn get_lessons(class_id: Path<Uuid>, _role: Role, db: Data<Pool>) -> HttpResult {
+    debug!(%class_id, ?_role, "get lessons");
+
+    let (_, lessons) = block(move || actions::timetable::get_timetable(&db, *class_id)).await?;
+
+    Ok(HttpResponse::Ok().json::<Vec<TimetableLesson>>(lessons.into_dto()?))
+}
+
+async fn get_lesson(path: Path<(Uuid, Uuid)>, _role: Role, db: Data<Pool>) -> HttpResult {
+    let (class_id, lesson_id) = path.into_inner();
+    debug!(%class_id, %lesson_id, ?_role, "get lesson");
+
+    let lesson = block(move || actions::timetable::get_lesson(&db, class_id, lesson_id))
+        .await?
+        .into_dto()?;
+
+    Ok(HttpResponse::Ok().json::<TimetableLesson>(lesson))
+}
+
+async fn create_lesson(
+    class_id: Path<Uuid>,
+    role: Role,
+    db: Data<Pool>,
+    lesson: Json<TimetableLesson>,
+) -> HttpResult {
+    debug!(%class_id, ?role, ?lesson, "create lesson");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+    validate_weekday(lesson.weekday)?;
+
+    let mut lesson = lesson.into_inner();
+    let lesson = block(move || {
+        let version = match lesson.version {
+            Some(version) => version,
+            None => current_version(&db, *class_id)?,
+        };
+        let subjects = actions::subject::get_subjects(&db, *class_id)?;
+        name_subject(&subjects, &mut lesson.lesson)?;
+        validate_lesson(&db, *class_id, version, &lesson, None)?;
+        actions::timetable::insert_lesson(
+            &db,
+            NewLesson::from_dto(*class_id, version, lesson.weekday, &lesson.lesson),
+        )
+    })
+    .await?
+    .into_dto()?;
+
+    Ok(HttpResponse::Created().json::<TimetableLesson>(lesson))
+}
+
+async fn edit_lesson(
+    path: Path<(Uuid, Uuid)>,
+    role: Role,
+    db: Data<Pool>,
+    lesson: Json<TimetableLesson>,
+) -> HttpResult {
+    let (class_id, lesson_id) = path.into_inner();
+    debug!(%class_id, %lesson_id, ?role, ?lesson, "edit lesson");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+    validate_weekday(lesson.weekday)?;
+
+    let mut lesson = lesson.into_inner();
+    let lesson = block(move || {
+        let version = match lesson.version {
+            Some(version) => version,
+            None => actions::timetable::get_lesson(&db, class_id, lesson_id)?.version,
+        };
+        let subjects = actions::subject::get_subjects(&db, class_id)?;
+        name_subject(&subjects, &mut lesson.lesson)?;
+        validate_lesson(&db, class_id, version, &lesson, Some(lesson_id))?;
+        actions::timetable::update_lesson(
+            &db,
+            lesson_id,
+            NewLesson::from_dto(class_id, version, lesson.weekday, &lesson.lesson),
+        )
+    })
+    .await?
+    .into_dto()?;
+
+    Ok(HttpResponse::Ok().json::<TimetableLesson>(lesson))
+}
+
+async fn delete_lesson(path: Path<(Uuid, Uuid)>, role: Role, db: Data<Pool>) -> HttpResult {
+    let (class_id, lesson_id) = path.into_inner();
+    debug!(%class_id, %lesson_id, ?role, "delete lesson");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+
+    let delete_count =
+        block(move || actions::timetable::delete_lesson(&db, class_id, lesson_id)).await?;
+
+    Ok(match delete_count {
+        0 => HttpResponse::NotFound().body("Lesson not found"),
+        1 => HttpResponse::Ok().body("Deleted lesson."),
+        _ => unreachable!(),
+    })
+}
+
+fn validate_weekday(weekday: u8) -> Result<(), ServiceErr> {
+    if weekday > 6 {
+        return Err(ServiceErr::BadRequest("invalid-weekday"));
+    }
+    Ok(())
+}
+
+/// Checks the lesson against the other lessons of its day in the version. The errors are about the lessons
+/// of the day in the order of the timetable, with the new lesson at the end
+fn validate_lesson(
+    db: &Pool,
+    class_id: Uuid,
+    version: Uuid,
+    lesson: &TimetableLesson,
+    lesson_id: Option<Uuid>,
+) -> Result<(), ServiceErr> {
+    let (_, lessons) = actions::timetable::get_timetable(db, class_id)?;
+
+    let mut day = Timetable::default();
+    day[lesson.weekday as usize] = lessons
+        .into_iter()
+        .filter(|other| other.version == version)
+        .filter(|other| other.weekday == lesson.weekday as i32 && Some(other.id) != lesson_id)
+        .map(IntoDto::<TimetableLesson>::into_dto)
+        .map(|other| other.map(|other| other.lesson))
+        .collect::<Result<_, _>>()?;
+    day[lesson.weekday as usize].push(lesson.lesson.clone());
+    let index = day[lesson.weekday as usize].len() - 1;
+
+    let errors = dto::validate_timetable(&day)
+        .into_iter()
+        .filter(|error| error.index == index || error.with == Some(index))
+        .collect::<Vec<_>>();
+    if !errors.is_empty() {
+        return Err(ServiceErr::InvalidTimetable(errors));
+    }
+    Ok(())
+}
+
+async fn get_timetable_exceptions(
+    class_id: Path<Uuid>,
+    _role: Role,
+    db: Data<Pool>,
+    query: Query<TimetableExceptionQueryParams>,
+) -> HttpResult {
+    let TimetableExceptionQueryParams { before, after } = query.into_inner();
+
+    debug!(%class_id, ?_role, ?before, ?after, "get timetable exceptions");
+
+    let exceptions = block(move || {
+        actions::timetable::get_exceptions(
+            &db,
+            *class_id,
+            after.map(|after| after.to_naive().date()),
+            before.map(|before| before.to_naive().date()),
+        )
+    })
+    .await?
+    .into_dto()?;
+
+    Ok(HttpResponse::Ok().json::<Vec<TimetableException>>(exceptions))
+}
+
+async fn create_timetable_exception(
+    class_id: Path<Uuid>,
+    role: Role,
+    db: Data<Pool>,
+    exception: Json<TimetableException>,
+) -> HttpResult {
+    debug!(%class_id, ?role, ?exception, "create timetable exception");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+    validate_exception(&exception)?;
+
+    let class_id = class_id.into_inner();
+    let exception = block(move || {
+        check_exception_lesson(&db, class_id, &exception)?;
+        actions::timetable::insert_exception(
+            &db,
+            NewTimetableException::from_dto(class_id, &exception),
+        )
+    })
+    .await?
+    .into_dto()?;
+
+    Ok(HttpResponse::Created().json::<TimetableException>(exception))
+}
+
+async fn edit_timetable_exception(
+    path: Path<(Uuid, Uuid)>,
+    role: Role,
+    db: Data<Pool>,
+    exception: Json<TimetableException>,
+) -> HttpResult {
+    let (class_id, exception_id) = path.into_inner();
+    debug!(%class_id, %exception_id, ?role, ?exception, "edit timetable exception");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+    validate_exception(&exception)?;
+
+    let exception = block(move || {
+        check_exception_lesson(&db, class_id, &exception)?;
+        actions::timetable::update_exception(
+            &db,
+            exception_id,
+            NewTimetableException::from_dto(class_id, &exception),
+        )
+    })
+    .await?
+    .into_dto()?;
+
+    Ok(HttpResponse::Ok().json::<TimetableException>(exception))
+}
+
+async fn delete_timetable_exception(
+    path: Path<(Uuid, Uuid)>,
+    role: Role,
+    db: Data<Pool>,
+) -> HttpResult {
+    let (class_id, exception_id) = path.into_inner();
+    debug!(%class_id, %exception_id, ?role, "delete timetable exception");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+
+    let delete_count =
+        block(move || actions::timetable::delete_exception(&db, class_id, exception_id)).await?;
+
+    Ok(match delete_count {
+        0 => HttpResponse::NotFound().body("Exception not found"),
+        1 => HttpResponse::Ok().body("Deleted exception."),
+        _ => unreachable!(),
+    })
+}
+
+/// An extra lesson needs a subject and a time, and cannot be cancelled
+fn validate_exception(exception: &TimetableException) -> Result<(), ServiceErr> {
+    let incomplete =
+        exception.subject.is_none() || exception.start.is_none() || exception.end.is_none();
+    if exception.lesson.is_none() && (exception.cancelled || incomplete) {
+        return Err(ServiceErr::BadRequest("invalid-extra-lesson"));
+    }
+    Ok(())
+}
+
+/// The changed lesson has to be a lesson of the class
+fn check_exception_lesson(
+    db: &Pool,
+    class_id: Uuid,
+    exception: &TimetableException,
+) -> Result<(), ServiceErr> {
+    if let Some(lesson_id) = exception.lesson {
+        actions::timetable::get_lesson(db, class_id, lesson_id).map_err(|err| match err {
+            ServiceErr::NotFound => ServiceErr::BadRequest("invalid-lesson"),
+            err => err,
+        })?;
+    }
+    Ok(())
+}
+
+async fn get_effective_timetable(
+    class_id: Path<Uuid>,
+    _role: Role,
+    db: Data<Pool>,
+    query: Query<EffectiveTimetableQueryParams>,
+) -> HttpResult {
+    let EffectiveTimetableQueryParams { date, days } = query.into_inner();
+    let days = days.unwrap_or(1);
+
+    debug!(%class_id, ?_role, ?date, %days, "get effective timetable");
+
+    if days == 0 || days > MAX_EFFECTIVE_DAYS {
+        return Err(ServiceErr::BadRequest("invalid-range"));
+    }
+
+    let effective_days = block::<_, _, ServiceErr>(move || {
+        let (class, _) = actions::class::get_class(&db, *class_id)?.ok_or(ServiceErr::NotFound)?;
+        let first = class_date(&class, date)?;
+        let last = first + chrono::Duration::days(days as i64 - 1);
+
+        let (table, lessons) = actions::timetable::get_timetable(&db, *class_id)?;
+        let versions = actions::timetable::get_versions(&db, *class_id)?;
+        let cycle: TimetableCycle = table.into_dto()?;
+        let exceptions: Vec<TimetableException> =
+            actions::timetable::get_exceptions(&db, *class_id, Some(first), Some(last))?
+                .into_dto()?;
+        let holidays = holiday_events(&db, *class_id, first, last)?;
+        let timezone = class.tz()?;
+
+        first
+            .iter_days()
+            .take(days as usize)
+            .map(|date| {
+                let lessons = lessons_on(&versions, &lessons, date)?;
+                let holiday = timetable::holiday_on(&holidays, timezone, date);
+                Ok(timetable::effective_day(
+                    &lessons,
+                    &cycle,
+                    &exceptions,
+                    holiday,
+                    date,
+                ))
+            })
+            .collect::<Result<Vec<_>, ServiceErr>>()
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json::<Vec<EffectiveDay>>(effective_days))
+}
+
+async fn get_timetable_versions(class_id: Path<Uuid>, _role: Role, db: Data<Pool>) -> HttpResult {
+    debug!(%class_id, ?_role, "get timetable versions");
+
+    let versions = block(move || actions::timetable::get_versions(&db, *class_id))
+        .await?
+        .into_dto()?;
+
+    Ok(HttpResponse::Ok().json::<Vec<TimetableVersion>>(versions))
+}
+
+async fn create_timetable_version(
+    class_id: Path<Uuid>,
+    role: Role,
+    db: Data<Pool>,
+    version: Json<TimetableVersion>,
+) -> HttpResult {
+    debug!(%class_id, ?role, ?version, "create timetable version");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+    let version = NewTimetableVersion::from_dto(class_id.into_inner(), &version);
+    validate_version(&version)?;
+
+    let version = block(move || actions::timetable::insert_version(&db, version))
+        .await?
+        .into_dto()?;
+
+    Ok(HttpResponse::Created().json::<TimetableVersion>(version))
+}
+
+async fn edit_timetable_version(
+    path: Path<(Uuid, Uuid)>,
+    role: Role,
+    db: Data<Pool>,
+    version: Json<TimetableVersion>,
+) -> HttpResult {
+    let (class_id, version_id) = path.into_inner();
+    debug!(%class_id, %version_id, ?role, ?version, "edit timetable version");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+    let version = NewTimetableVersion::from_dto(class_id, &version);
+    validate_version(&version)?;
+
+    let version = block(move || actions::timetable::update_version(&db, version_id, version))
+        .await?
+        .into_dto()?;
+
+    Ok(HttpResponse::Ok().json::<TimetableVersion>(version))
+}
+
+async fn delete_timetable_version(
+    path: Path<(Uuid, Uuid)>,
+    role: Role,
+    db: Data<Pool>,
+) -> HttpResult {
+    let (class_id, version_id) = path.into_inner();
+    debug!(%class_id, %version_id, ?role, "delete timetable version");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+
+    let delete_count =
+        block(move || actions::timetable::delete_version(&db, class_id, version_id)).await?;
+
+    Ok(match delete_count {
+        0 => HttpResponse::NotFound().body("Version not found"),
+        1 => HttpResponse::Ok().body("Deleted version."),
+        _ => unreachable!(),
+    })
+}
+
+async fn get_version_timetable(
+    path: Path<(Uuid, Uuid)>,
+    _role: Role,
+    db: Data<Pool>,
+) -> HttpResult {
+    let (class_id, version_id) = path.into_inner();
+    debug!(%class_id, %version_id, ?_role, "get version timetable");
+
+    let (_, lessons) = block(move || actions::timetable::get_timetable(&db, class_id)).await?;
+    let lessons = lessons
+        .into_iter()
+        .filter(|lesson| lesson.version == version_id)
+        .collect();
+
+    Ok(HttpResponse::Ok().json::<Timetable>(into_timetable(lessons)?))
+}
+
+async fn edit_version_timetable(
+    path: Path<(Uuid, Uuid)>,
+    role: Role,
+    db: Data<Pool>,
+    table: Json<Timetable>,
+) -> HttpResult {
+    let (class_id, version_id) = path.into_inner();
+    debug!(%class_id, %version_id, ?role, ?table, "edit version timetable");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+
+    let lessons = block(move || replace_lessons(&db, class_id, version_id, &table)).await?;
+
+    Ok(HttpResponse::Ok().json::<Timetable>(into_timetable(lessons)?))
+}
+
+fn validate_version(version: &NewTimetableVersion) -> Result<(), ServiceErr> {
+    if let (Some(from), Some(until)) = (version.valid_from, version.valid_until) {
+        if until < from {
+            return Err(ServiceErr::BadRequest("invalid-range"));
+        }
+    }
+    Ok(())
+}
+
+async fn get_subjects(class_id: Path<Uuid>, _role: Role, db: Data<Pool>) -> HttpResult {
+    debug!(%class_id, ?_role, "get subjects");
+
+    let subjects = block(move || actions::subject::get_subjects(&db, *class_id))
+        .await?
+        .into_dto()?;
+
+    Ok(HttpResponse::Ok().json::<Vec<Subject>>(subjects))
+}
+
+async fn get_subject(path: Path<(Uuid, Uuid)>, _role: Role, db: Data<Pool>) -> HttpResult {
+    let (class_id, subject_id) = path.into_inner();
+    debug!(%class_id, %subject_id, ?_role, "get subject");
+
+    let subject = block(move || actions::subject::get_subject(&db, class_id, subject_id))
+        .await?
+        .into_dto()?;
+
+    Ok(HttpResponse::Ok().json::<Subject>(subject))
+}
+
+async fn create_subject(
+    class_id: Path<Uuid>,
+    role: Role,
+    db: Data<Pool>,
+    subject: Json<Subject>,
+) -> HttpResult {
+    debug!(%class_id, ?role, ?subject, "create subject");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+    validate_subject(&subject)?;
+
+    let subject = block(move || {
+        actions::subject::insert_subject(&db, NewSubject::from_dto(*class_id, &subject))
+    })
+    .await?
+    .into_dto()?;
+
+    Ok(HttpResponse::Created().json::<Subject>(subject))
+}
+
+async fn edit_subject(
+    path: Path<(Uuid, Uuid)>,
+    role: Role,
+    db: Data<Pool>,
+    subject: Json<Subject>,
+) -> HttpResult {
+    let (class_id, subject_id) = path.into_inner();
+    debug!(%class_id, %subject_id, ?role, ?subject, "edit subject");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+    validate_subject(&subject)?;
+
+    let subject = block(move || {
+        actions::subject::update_subject(&db, subject_id, NewSubject::from_dto(class_id, &subject))
+    })
+    .await?
+    .into_dto()?;
+
+    Ok(HttpResponse::Ok().json::<Subject>(subject))
+}
+
+async fn delete_subject(path: Path<(Uuid, Uuid)>, role: Role, db: Data<Pool>) -> HttpResult {
+    let (class_id, subject_id) = path.into_inner();
+    debug!(%class_id, %subject_id, ?role, "delete subject");
+
+    if !role.has_rights() {
+        return Err(ServiceErr::NoAdminPermissions);
+    }
+
+    let delete_count =
+        block(move || actions::subject::delete_subject(&db, class_id, subject_id)).await?;
+
+    Ok(match delete_count {
+        0 => HttpResponse::NotFound().body("Subject not found"),
+        1 => HttpResponse::Ok().body("Deleted subject."),
+        _ => unreachable!(),
+    })
+}
+
+/// The short name is limited by the database
+fn validate_subject(subject: &Subject) -> Result<(), ServiceErr> {
+    if subject.name.trim().is_empty() {
+        return Err(ServiceErr::BadRequest("missing-subject"));
+    }
+    if subject.short.chars().count() > 10 {
+        return Err(ServiceErr::BadRequest("invalid-short"));
+    }
+    if !subject.has_valid_colour() {
+        return Err(ServiceErr::BadRequest("invalid-colour"));
+    }
+    Ok(())
 }
 
 async fn link_class_with_discord(
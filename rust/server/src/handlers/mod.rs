@@ -2,11 +2,16 @@ use crate::actions::{self, Pool};
 use crate::error::ServiceErr;
 use crate::handlers::auth::{change_password, create_user, Claims};
 use crate::models::conversion::IntoDto;
+use crate::models::MemberRole;
 use actix_web::web::{block, delete, get, post, put, scope, Data, Json, Path, Query};
 use actix_web::web::{patch, ServiceConfig};
 use actix_web::HttpResponse;
-use dto::{NotificationQueryParams, NotificationRes, SingleSnowflake, User};
+use dto::{
+    ClassEvent, GetEventQueryParams, GradeQueryParams, NotificationQueryParams, NotificationRes,
+    SingleSnowflake, User,
+};
 use tracing::debug;
+use uuid::Uuid;
 
 mod auth;
 mod class;
@@ -29,6 +34,11 @@ pub fn other_config(cfg: &mut ServiceConfig) {
                 .route("/me", get().to(get_own_user))
                 .route("/me", put().to(edit_own_user))
                 .route("/me", delete().to(delete_own_user))
+                .route("/me/events", get().to(search_own_events))
+                .route("/me/grades", get().to(get_own_grades))
+                .route("/me/grades/summary", get().to(get_own_grade_summary))
+                .route("/me/grades/{uuid}", put().to(set_own_grade))
+                .route("/me/grades/{uuid}", delete().to(delete_own_grade))
                 .route("/me/password", patch().to(change_password))
                 .route("/me/link", post().to(link_user_with_discord))
                 .route("/discord/{snowflake}", get().to(get_user_by_discord)),
@@ -63,6 +73,160 @@ async fn get_own_user(claims: Claims, db: Data<Pool>) -> HttpResult {
     Ok(HttpResponse::Ok().json(user))
 }
 
+/// Searches the events of all classes of the user
+async fn search_own_events(
+    claims: Claims,
+    db: Data<Pool>,
+    query: Query<GetEventQueryParams>,
+) -> HttpResult {
+    let GetEventQueryParams {
+        before,
+        after,
+        done,
+        q,
+        ..
+    } = query.into_inner();
+
+    debug!(uid = %claims.uid, ?before, ?after, ?done, ?q, "search own events");
+
+    let q = q.ok_or(ServiceErr::BadRequest("missing-query"))?;
+
+    let events = block::<_, _, ServiceErr>(move || {
+        let class_ids = actions::class::get_classes_by_user(&db, claims.uid)?
+            .into_iter()
+            .map(|class| class.id)
+            .collect::<Vec<_>>();
+
+        let events = actions::event::search_events(
+            &db,
+            &class_ids,
+            &q,
+            after.map(|after| after.to_naive()),
+            before.map(|before| before.to_naive()),
+        )?;
+        let done_events = actions::event::get_done_events(&db, &class_ids, claims.uid)?;
+
+        let events = events
+            .into_iter()
+            .map(|event| {
+                let class = event.0.class;
+                let mut event: dto::Event = event.into_dto()?;
+                event.done = done_events.contains(&event.id);
+                Ok(ClassEvent { class, event })
+            })
+            .collect::<Result<Vec<_>, ServiceErr>>()?;
+
+        Ok(events
+            .into_iter()
+            .filter(|ClassEvent { event, .. }| done.is_none_or(|done| event.done == done))
+            .collect::<Vec<_>>())
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(events))
+}
+
+/// The grades of the user in the own classes, or only in the class of the query
+fn own_grades(
+    db: &Pool,
+    user_id: Uuid,
+    class: Option<Uuid>,
+) -> Result<Vec<dto::Grade>, ServiceErr> {
+    let class_ids = actions::class::get_classes_by_user(db, user_id)?
+        .into_iter()
+        .map(|own_class| own_class.id)
+        .filter(|id| class.is_none_or(|class| *id == class))
+        .collect::<Vec<_>>();
+
+    actions::grade::get_grades(db, user_id, &class_ids)?.into_dto()
+}
+
+async fn get_own_grades(
+    claims: Claims,
+    db: Data<Pool>,
+    query: Query<GradeQueryParams>,
+) -> HttpResult {
+    let class = query.into_inner().class;
+    debug!(uid = %claims.uid, ?class, "get own grades");
+
+    let grades = block(move || own_grades(&db, claims.uid, class)).await?;
+
+    Ok(HttpResponse::Ok().json(grades))
+}
+
+async fn get_own_grade_summary(
+    claims: Claims,
+    db: Data<Pool>,
+    query: Query<GradeQueryParams>,
+) -> HttpResult {
+    let class = query.into_inner().class;
+    debug!(uid = %claims.uid, ?class, "get own grade summary");
+
+    let grades = block(move || own_grades(&db, claims.uid, class)).await?;
+
+    Ok(HttpResponse::Ok().json(crate::grades::summarize(&grades)))
+}
+
+async fn set_own_grade(
+    claims: Claims,
+    db: Data<Pool>,
+    event_id: Path<Uuid>,
+    grade: Json<dto::Grade>,
+) -> HttpResult {
+    let event_id = event_id.into_inner();
+    let grade = grade.into_inner();
+    debug!(uid = %claims.uid, %event_id, ?grade, "set own grade");
+
+    if !grade.weight.is_finite() || grade.weight <= 0.0 {
+        return Err(ServiceErr::BadRequest("invalid-weight"));
+    }
+    if !grade.scale.is_valid(grade.grade) {
+        return Err(ServiceErr::BadRequest("invalid-grade"));
+    }
+
+    let grade = block::<_, _, ServiceErr>(move || {
+        let event_class = actions::event::get_event_class(&db, event_id)?;
+        let (event, _) = actions::event::get_event_by_id(&db, event_class, event_id)?;
+        if event.e_type != dto::EventType::EXAM {
+            return Err(ServiceErr::BadRequest("not-an-exam"));
+        }
+        // only members of the class of the exam can grade it
+        let (member, _) = actions::class::get_member(&db, claims.uid, event.class)?;
+        if member.role >= MemberRole::PENDING {
+            return Err(ServiceErr::NotFound);
+        }
+
+        let new_grade = actions::grade::set_grade(
+            &db,
+            crate::models::Grade {
+                user: claims.uid,
+                event: event.id,
+                class: event.class,
+                grade: grade.grade,
+                weight: grade.weight,
+                scale: grade.scale.as_str().to_string(),
+            },
+        )?;
+        (new_grade, event).into_dto()
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(grade))
+}
+
+async fn delete_own_grade(claims: Claims, db: Data<Pool>, event_id: Path<Uuid>) -> HttpResult {
+    let event_id = event_id.into_inner();
+    debug!(uid = %claims.uid, %event_id, "delete own grade");
+
+    let deleted = block(move || actions::grade::delete_grade(&db, claims.uid, event_id)).await?;
+
+    if deleted == 0 {
+        Err(ServiceErr::NotFound)
+    } else {
+        Ok(HttpResponse::Ok().body(""))
+    }
+}
+
 async fn edit_own_user(claims: Claims, db: Data<Pool>, mut new_user: Json<User>) -> HttpResult {
     debug!(uid = %claims.uid, ?new_user, "edit own user");
 
@@ -129,18 +293,13 @@ async fn get_notifications(
         return Err(ServiceErr::Unauthorized("bot-only"));
     }
 
-    let (time, notifications) = block(move || {
-        actions::event::get_notifications(
-            &db,
-            chrono::NaiveDateTime::from_timestamp(params.since / 1000, 0),
-        )
-    })
-    .await?;
+    let (time, notifications) =
+        block(move || actions::event::get_notifications(&db, params.since.to_naive())).await?;
 
     let notifications = notifications.into_dto()?;
 
     Ok(HttpResponse::Ok().json(NotificationRes {
         notifications,
-        time: time.timestamp_millis(),
+        time: time.into(),
     }))
 }
@@ -0,0 +1,99 @@
+use dto::{Event, FieldChange};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// These fields are not part of the stored event
+const IGNORED_FIELDS: [&str; 4] = ["id", "occurrence", "done", "completions"];
+
+/// The fields that differ between the old and the new version of an event.
+/// If the event didn't exist before or after the change, every field is changed from/to `null`.
+pub fn diff(old: Option<&Event>, new: Option<&Event>) -> BTreeMap<String, FieldChange> {
+    let old = fields(old);
+    let new = fields(new);
+
+    old.keys()
+        .chain(new.keys())
+        .filter(|field| !IGNORED_FIELDS.contains(&field.as_str()))
+        .filter_map(|field| {
+            let old = old.get(field).cloned().unwrap_or(Value::Null);
+            let new = new.get(field).cloned().unwrap_or(Value::Null);
+            if old == new {
+                None
+            } else {
+                Some((field.clone(), FieldChange { old, new }))
+            }
+        })
+        .collect()
+}
+
+fn fields(event: Option<&Event>) -> serde_json::Map<String, Value> {
+    match event.map(serde_json::to_value) {
+        Some(Ok(Value::Object(fields))) => fields,
+        _ => serde_json::Map::new(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::diff;
+    use dto::{Event, EventType, FieldChange};
+    use serde_json::{json, Value};
+
+    fn exam() -> Event {
+        Event {
+            id: uuid::Uuid::new_v4(),
+            r#type: EventType::EXAM,
+            name: "Prüfung".to_string(),
+            start: dto::Timestamp(1628000000000),
+            end: None,
+            description: "Kapitel 3".to_string(),
+            subject: Some("Mathematik".to_string()),
+            subject_id: None,
+            notifications: vec![],
+            recurrence: None,
+            occurrence: None,
+            done: false,
+            completions: None,
+            warnings: vec![],
+        }
+    }
+
+    #[test]
+    fn changed_fields() {
+        let old = exam();
+        let new = Event {
+            start: dto::Timestamp(1628600000000),
+            description: "Kapitel 3 und 4".to_string(),
+            done: true,
+            ..old.clone()
+        };
+
+        let changes = diff(Some(&old), Some(&new));
+        assert_eq!(changes.len(), 2);
+        assert_eq!(
+            changes["start"],
+            FieldChange {
+                old: json!(1628000000000i64),
+                new: json!(1628600000000i64),
+            }
+        );
+        assert_eq!(changes["description"].new, json!("Kapitel 3 und 4"));
+
+        assert!(diff(Some(&old), Some(&old)).is_empty());
+    }
+
+    #[test]
+    fn created_and_deleted() {
+        let event = exam();
+
+        let created = diff(None, Some(&event));
+        assert_eq!(created["name"].old, Value::Null);
+        assert_eq!(created["name"].new, json!("Prüfung"));
+        assert!(!created.contains_key("id"));
+
+        let deleted = diff(Some(&event), None);
+        assert_eq!(deleted["name"].new, Value::Null);
+        // fields that are null before and after are unchanged
+        assert!(!deleted.contains_key("end"));
+    }
+}
@@ -0,0 +1,7 @@
+//! iCalendar (RFC 5545) support, used for the calendar feeds and the event import
+
+mod parse;
+mod render;
+
+pub use parse::parse_calendar;
+pub use render::{render_calendar, render_timetable};
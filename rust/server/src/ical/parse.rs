@@ -0,0 +1,588 @@
+//! Parsing of iCalendar files into events that can be imported
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use dto::{EventType, Frequency};
+
+/// Longer names are cut off, the database does not allow more
+const MAX_NAME_LENGTH: usize = 50;
+const MAX_DESCRIPTION_LENGTH: usize = 10000;
+
+const HOMEWORK_KEYWORDS: &[&str] = &["homework", "hausaufgabe", "hausaufgaben", "ha", "aufgabe"];
+const EXAM_KEYWORDS: &[&str] = &[
+    "exam",
+    "test",
+    "prüfung",
+    "pruefung",
+    "klausur",
+    "probe",
+    "lernkontrolle",
+    "lk",
+];
+const HOLIDAYS_KEYWORDS: &[&str] = &["holiday", "holidays", "vacation", "ferien", "feiertag"];
+
+/// A `VEVENT` of an iCalendar file, all times are UTC
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedEvent {
+    pub uid: String,
+    /// One of the built-in types, guessed from the categories and the name
+    pub e_type: i32,
+    pub categories: Vec<String>,
+    pub name: String,
+    pub start: NaiveDateTime,
+    pub end: Option<NaiveDateTime>,
+    pub description: String,
+    pub notifications: Vec<NaiveDateTime>,
+    pub rec_frequency: Option<Frequency>,
+    pub rec_interval: i32,
+    pub rec_until: Option<NaiveDateTime>,
+    pub rec_count: Option<i32>,
+    pub rec_exceptions: Vec<NaiveDateTime>,
+    /// Set if this event replaces a single occurrence of a recurring event with the same `UID`
+    pub recurrence_id: Option<NaiveDateTime>,
+}
+
+/// A `VEVENT` that could not be parsed, the other events can still be imported
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub uid: Option<String>,
+    pub error: &'static str,
+}
+
+/// A content line, eg. `DTSTART;TZID=Europe/Zurich:20210820T080000`
+#[derive(Debug)]
+struct Property<'a> {
+    name: String,
+    params: Vec<(String, &'a str)>,
+    value: &'a str,
+}
+
+impl Property<'_> {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| *value)
+    }
+}
+
+/// Parses all `VEVENT`s of a calendar.
+/// Returns an error if the text is not a calendar at all.
+pub fn parse_calendar(text: &str) -> Result<(Vec<ParsedEvent>, Vec<ParseError>), &'static str> {
+    let lines = unfold(text);
+    let mut properties = lines.iter().filter_map(|line| parse_property(line));
+
+    match properties.next() {
+        Some(first) if first.name == "BEGIN" && first.value.eq_ignore_ascii_case("VCALENDAR") => {}
+        _ => return Err("invalid-calendar"),
+    }
+
+    let mut events = vec![];
+    let mut errors = vec![];
+
+    // the components we are currently in, below the VCALENDAR
+    let mut components: Vec<String> = vec![];
+    let mut event: Vec<Property> = vec![];
+    // the `TRIGGER`s of all `VALARM`s of the event
+    let mut triggers: Vec<Property> = vec![];
+
+    for property in properties {
+        match &*property.name {
+            "BEGIN" => components.push(property.value.to_uppercase()),
+            "END" => {
+                let component = components.pop();
+                match component.as_deref() {
+                    Some("VEVENT") if components.is_empty() => {
+                        match parse_event(&event, &triggers) {
+                            Ok(parsed) => events.push(parsed),
+                            Err(err) => errors.push(err),
+                        }
+                        event.clear();
+                        triggers.clear();
+                    }
+                    None => break, // END:VCALENDAR
+                    _ => {}
+                }
+            }
+            _ => match (components.first().map(|c| &**c), components.len()) {
+                (Some("VEVENT"), 1) => event.push(property),
+                (Some("VEVENT"), 2) if components[1] == "VALARM" && property.name == "TRIGGER" => {
+                    triggers.push(property)
+                }
+                _ => {}
+            },
+        }
+    }
+
+    exclude_overridden_occurrences(&mut events);
+    remove_duplicates(&mut events, &mut errors);
+
+    Ok((events, errors))
+}
+
+/// Occurrences that have been replaced by their own `VEVENT` are imported as standalone events,
+/// so they have to be excluded from the recurring event
+fn exclude_overridden_occurrences(events: &mut [ParsedEvent]) {
+    let overrides = events
+        .iter()
+        .filter_map(|event| {
+            let (uid, _) = event.uid.rsplit_once('/')?;
+            Some((uid.to_string(), event.recurrence_id?))
+        })
+        .collect::<Vec<_>>();
+
+    for (uid, occurrence) in overrides {
+        let series = events
+            .iter_mut()
+            .find(|event| event.recurrence_id.is_none() && event.uid == uid);
+        if let Some(series) = series {
+            series.rec_exceptions.push(occurrence);
+        }
+    }
+}
+
+/// The `UID` identifies an event on a re-import, so it has to be unique
+fn remove_duplicates(events: &mut Vec<ParsedEvent>, errors: &mut Vec<ParseError>) {
+    let mut seen = std::collections::HashSet::new();
+    events.retain(|event| {
+        let unique = seen.insert(event.uid.clone());
+        if !unique {
+            errors.push(ParseError {
+                uid: Some(event.uid.clone()),
+                error: "duplicate-uid",
+            });
+        }
+        unique
+    });
+}
+
+/// Joins lines that were split because they were too long
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+
+    for line in text.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (
+            line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')),
+            lines.last_mut(),
+        ) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    lines
+}
+
+fn parse_property(line: &str) -> Option<Property<'_>> {
+    let mut in_quotes = false;
+    let colon = line.char_indices().find_map(|(i, char)| match char {
+        '"' => {
+            in_quotes = !in_quotes;
+            None
+        }
+        ':' if !in_quotes => Some(i),
+        _ => None,
+    })?;
+
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let mut parts = head.split(';');
+    let name = parts.next()?.trim().to_uppercase();
+    let params = parts
+        .filter_map(|param| {
+            let (key, value) = param.split_once('=')?;
+            Some((key.trim().to_uppercase(), value.trim_matches('"')))
+        })
+        .collect();
+
+    Some(Property {
+        name,
+        params,
+        value,
+    })
+}
+
+fn parse_event(properties: &[Property], triggers: &[Property]) -> Result<ParsedEvent, ParseError> {
+    let find = |name: &str| properties.iter().find(|property| property.name == name);
+
+    let uid = find("UID").map(|uid| unescape_text(uid.value));
+    let error = |error: &'static str| ParseError {
+        uid: uid.clone(),
+        error,
+    };
+
+    let dtstart = find("DTSTART").ok_or_else(|| error("missing-start"))?;
+    let start = parse_datetime_property(dtstart).map_err(error)?;
+
+    let end = match (find("DTEND"), find("DURATION")) {
+        (Some(dtend), _) => Some(parse_datetime_property(dtend).map_err(error)?),
+        (None, Some(duration)) => {
+            Some(start + parse_duration(duration.value).ok_or_else(|| error("invalid-duration"))?)
+        }
+        (None, None) => None,
+    };
+
+    let name = find("SUMMARY")
+        .map(|summary| unescape_text(summary.value))
+        .filter(|name| !name.trim().is_empty())
+        .ok_or_else(|| error("missing-summary"))?;
+    let name = name
+        .trim()
+        .chars()
+        .take(MAX_NAME_LENGTH)
+        .collect::<String>();
+
+    let description = find("DESCRIPTION")
+        .map(|description| unescape_text(description.value))
+        .unwrap_or_default()
+        .chars()
+        .take(MAX_DESCRIPTION_LENGTH)
+        .collect();
+
+    let categories = properties
+        .iter()
+        .filter(|property| property.name == "CATEGORIES")
+        .flat_map(|property| property.value.split(','))
+        .map(unescape_text)
+        .collect::<Vec<_>>();
+
+    let mut event = ParsedEvent {
+        // events without an uid can still be recognized on a re-import
+        uid: uid
+            .clone()
+            .unwrap_or_else(|| format!("{}-{}", start.timestamp(), name)),
+        e_type: guess_event_type(&categories, &name),
+        categories,
+        name,
+        start,
+        end: end.filter(|end| *end > start),
+        description,
+        notifications: vec![],
+        rec_frequency: None,
+        rec_interval: 1,
+        rec_until: None,
+        rec_count: None,
+        rec_exceptions: vec![],
+        recurrence_id: None,
+    };
+
+    if let Some(recurrence_id) = find("RECURRENCE-ID") {
+        let occurrence = parse_datetime_property(recurrence_id).map_err(error)?;
+        event.uid = format!("{}/{}", event.uid, occurrence.timestamp());
+        event.recurrence_id = Some(occurrence);
+    }
+
+    if let Some(rrule) = find("RRULE") {
+        parse_rrule(&mut event, rrule.value).map_err(error)?;
+
+        for exdate in properties.iter().filter(|p| p.name == "EXDATE") {
+            for value in exdate.value.split(',') {
+                let exception = parse_datetime(value, exdate.param("TZID")).map_err(error)?;
+                event.rec_exceptions.push(exception);
+            }
+        }
+    }
+
+    for trigger in triggers {
+        let notification = match trigger.param("VALUE") {
+            Some(value) if value.eq_ignore_ascii_case("DATE-TIME") => {
+                parse_datetime(trigger.value, None).map_err(error)?
+            }
+            _ => {
+                let offset =
+                    parse_duration(trigger.value).ok_or_else(|| error("invalid-duration"))?;
+                match trigger.param("RELATED") {
+                    Some(related) if related.eq_ignore_ascii_case("END") => {
+                        event.end.unwrap_or(event.start) + offset
+                    }
+                    _ => event.start + offset,
+                }
+            }
+        };
+        event.notifications.push(notification);
+    }
+
+    Ok(event)
+}
+
+fn parse_rrule(event: &mut ParsedEvent, rrule: &str) -> Result<(), &'static str> {
+    for part in rrule.split(';').filter(|part| !part.is_empty()) {
+        let (key, value) = part.split_once('=').ok_or("invalid-recurrence")?;
+        match &*key.to_uppercase() {
+            "FREQ" => {
+                event.rec_frequency = Some(
+                    Frequency::from_name(&value.to_lowercase()).ok_or("unsupported-recurrence")?,
+                )
+            }
+            "INTERVAL" => event.rec_interval = value.parse().map_err(|_| "invalid-recurrence")?,
+            "COUNT" => event.rec_count = Some(value.parse().map_err(|_| "invalid-recurrence")?),
+            "UNTIL" => event.rec_until = Some(parse_datetime(value, None)?),
+            "WKST" => {}
+            // a weekly event on the weekday it starts is the same as no BYDAY
+            "BYDAY"
+                if event.rec_frequency == Some(Frequency::Weekly)
+                    && weekday_code(event.start) == value.to_uppercase() => {}
+            _ => return Err("unsupported-recurrence"),
+        }
+    }
+
+    match event.rec_frequency {
+        Some(_) if event.rec_interval > 0 && event.rec_count.is_none_or(|count| count > 0) => {
+            Ok(())
+        }
+        _ => Err("invalid-recurrence"),
+    }
+}
+
+fn weekday_code(datetime: NaiveDateTime) -> &'static str {
+    ["MO", "TU", "WE", "TH", "FR", "SA", "SU"][datetime.weekday().num_days_from_monday() as usize]
+}
+
+fn parse_datetime_property(property: &Property) -> Result<NaiveDateTime, &'static str> {
+    parse_datetime(property.value, property.param("TZID"))
+}
+
+/// Parses a date or date time value and converts it to UTC.
+/// Date times without a timezone and dates are treated as UTC.
+fn parse_datetime(value: &str, tzid: Option<&str>) -> Result<NaiveDateTime, &'static str> {
+    let value = value.trim();
+
+    if value.len() == 8 {
+        return NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .ok_or("invalid-date");
+    }
+
+    if let Some(utc) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").map_err(|_| "invalid-date");
+    }
+
+    let local =
+        NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").map_err(|_| "invalid-date")?;
+
+    match tzid {
+        Some(tzid) => {
+            let tz = tzid.parse::<Tz>().map_err(|_| "unknown-timezone")?;
+            tz.from_local_datetime(&local)
+                .earliest()
+                .map(|datetime| datetime.naive_utc())
+                .ok_or("invalid-date")
+        }
+        None => Ok(local),
+    }
+}
+
+/// Parses a duration like `-P1D` or `PT1H30M`
+fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(value) => (true, value),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+
+    let mut seconds = 0;
+    let mut number = None;
+    let mut in_time = false;
+
+    for char in value.strip_prefix('P')?.chars() {
+        if let Some(digit) = char.to_digit(10) {
+            number = Some(number.unwrap_or(0i64).checked_mul(10)? + digit as i64);
+            continue;
+        }
+
+        let unit = match (char, in_time) {
+            ('T', false) => {
+                in_time = true;
+                continue;
+            }
+            ('W', false) => 7 * 24 * 3600,
+            ('D', false) => 24 * 3600,
+            ('H', true) => 3600,
+            ('M', true) => 60,
+            ('S', true) => 1,
+            _ => return None,
+        };
+        seconds += number.take()?.checked_mul(unit)?;
+    }
+
+    if number.is_some() {
+        return None;
+    }
+
+    Some(Duration::seconds(if negative { -seconds } else { seconds }))
+}
+
+fn unescape_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(char) = chars.next() {
+        match char {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => result.push('\n'),
+                Some(other) => result.push(other),
+                None => {}
+            },
+            char => result.push(char),
+        }
+    }
+
+    result
+}
+
+/// Guesses the type of an event from its categories, or else from the words in its name
+fn guess_event_type(categories: &[String], name: &str) -> i32 {
+    // german compounds like `Herbstferien` end with the keyword
+    let matches = |words: &[String], keywords: &[&str]| {
+        words.iter().any(|word| {
+            let word = word.trim().to_lowercase();
+            keywords.iter().any(|keyword| {
+                word == *keyword || (keyword.chars().count() > 3 && word.ends_with(keyword))
+            })
+        })
+    };
+
+    let words = name
+        .split(|char: char| !char.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+
+    for words in [categories, &words] {
+        if matches(words, EXAM_KEYWORDS) {
+            return EventType::EXAM;
+        }
+        if matches(words, HOMEWORK_KEYWORDS) {
+            return EventType::HOMEWORK;
+        }
+        if matches(words, HOLIDAYS_KEYWORDS) {
+            return EventType::HOLIDAYS;
+        }
+    }
+
+    EventType::OTHER
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::datetime;
+
+    const CALENDAR: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VTIMEZONE\r
+TZID:Europe/Zurich\r
+BEGIN:STANDARD\r
+DTSTART:19701025T030000\r
+END:STANDARD\r
+END:VTIMEZONE\r
+BEGIN:VEVENT\r
+UID:exam-1@school\r
+DTSTART;TZID=Europe/Zurich:20210920T080000\r
+DTEND;TZID=Europe/Zurich:20210920T093000\r
+SUMMARY:Mathe\\, Kapitel 3\r
+DESCRIPTION:Alles über Funktionen\\nund Gleichungen, mit einer sehr langen Beschrei\r
+ bung\r
+CATEGORIES:Prüfung\r
+BEGIN:VALARM\r
+ACTION:DISPLAY\r
+TRIGGER:-P1W\r
+END:VALARM\r
+BEGIN:VALARM\r
+ACTION:DISPLAY\r
+TRIGGER:-P1D\r
+END:VALARM\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:voci@school\r
+DTSTART:20210903T060000Z\r
+DURATION:PT15M\r
+SUMMARY:Voci Test\r
+RRULE:FREQ=WEEKLY;BYDAY=FR;COUNT=10\r
+EXDATE:20210910T060000Z\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:voci@school\r
+RECURRENCE-ID:20210917T060000Z\r
+DTSTART:20210916T060000Z\r
+SUMMARY:Voci Test (verschoben)\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:herbst@school\r
+DTSTART;VALUE=DATE:20211009\r
+DTEND;VALUE=DATE:20211025\r
+SUMMARY:Herbstferien\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:broken@school\r
+SUMMARY:Kein Start\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    #[test]
+    fn parse_events() {
+        let (events, errors) = parse_calendar(CALENDAR).unwrap();
+
+        assert_eq!(
+            errors,
+            vec![ParseError {
+                uid: Some("broken@school".to_string()),
+                error: "missing-start"
+            }]
+        );
+        assert_eq!(events.len(), 4);
+
+        let exam = &events[0];
+        assert_eq!(exam.uid, "exam-1@school");
+        assert_eq!(exam.e_type, EventType::EXAM);
+        assert_eq!(exam.name, "Mathe, Kapitel 3");
+        assert!(exam
+            .description
+            .ends_with("Gleichungen, mit einer sehr langen Beschreibung"));
+        // CEST is UTC+2
+        assert_eq!(exam.start, datetime("2021-09-20 06:00"));
+        assert_eq!(exam.end, Some(datetime("2021-09-20 07:30")));
+        assert_eq!(
+            exam.notifications,
+            vec![datetime("2021-09-13 06:00"), datetime("2021-09-19 06:00")]
+        );
+
+        let voci = &events[1];
+        assert_eq!(voci.e_type, EventType::EXAM);
+        assert_eq!(voci.end, Some(datetime("2021-09-03 06:15")));
+        assert_eq!(voci.rec_frequency, Some(Frequency::Weekly));
+        assert_eq!(voci.rec_count, Some(10));
+        assert_eq!(
+            voci.rec_exceptions,
+            vec![datetime("2021-09-10 06:00"), datetime("2021-09-17 06:00")]
+        );
+
+        let moved = &events[2];
+        assert_eq!(moved.uid, "voci@school/1631858400");
+        assert_eq!(moved.start, datetime("2021-09-16 06:00"));
+        assert_eq!(moved.rec_frequency, None);
+
+        let holidays = &events[3];
+        assert_eq!(holidays.e_type, EventType::HOLIDAYS);
+        assert_eq!(holidays.start, datetime("2021-10-09 00:00"));
+    }
+
+    #[test]
+    fn invalid_calendar() {
+        assert_eq!(parse_calendar("hello"), Err("invalid-calendar"));
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("-PT15M"), Some(Duration::minutes(-15)));
+        assert_eq!(
+            parse_duration("P1W2DT3H"),
+            Some(Duration::hours(9 * 24 + 3))
+        );
+        assert_eq!(parse_duration("PT"), Some(Duration::zero()));
+        assert_eq!(parse_duration("P1H"), None);
+        assert_eq!(parse_duration("P12"), None);
+    }
+}
@@ -0,0 +1,460 @@
+//! Rendering of class events and the timetable as iCalendar text
+
+use crate::error::ServiceResult;
+use crate::models::{Event, EventNotification, EventType};
+use crate::timetable::RecurringLesson;
+use chrono::{Duration, FixedOffset, NaiveDateTime, Offset, TimeZone};
+use chrono_tz::{OffsetComponents, OffsetName, Tz};
+
+const PRODUCT_ID: &str = "-//CORS//davinci-cors//DE";
+
+/// Lines longer than this (in bytes) have to be folded
+const MAX_LINE_LENGTH: usize = 75;
+
+/// The `VTIMEZONE` of the event calendar covers the offset changes until this many years from now
+const TIMEZONE_YEARS: i64 = 5;
+
+/// Renders all events of a class as a calendar
+/// Recurring events are rendered as a single `VEVENT` with a `RRULE`, in local times of the timezone
+/// of the class, since they keep their local time
+pub fn render_calendar(
+    name: &str,
+    timezone: Tz,
+    events: Vec<(Event, Vec<EventNotification>)>,
+    types: &[EventType],
+    now: NaiveDateTime,
+) -> ServiceResult<String> {
+    let mut calendar = Calendar::default();
+
+    calendar.begin(name);
+
+    let first_series = events
+        .iter()
+        .filter(|(event, _)| event.rec_frequency.is_some())
+        .map(|(event, _)| event.start)
+        .min();
+    if let Some(first) = first_series {
+        render_timezone(
+            &mut calendar,
+            timezone,
+            first - Duration::days(1),
+            now + Duration::days(TIMEZONE_YEARS * 366),
+        );
+    }
+
+    for (event, notifications) in events {
+        let e_type = types.iter().find(|typ| typ.id == event.e_type);
+        render_event(&mut calendar, timezone, event, e_type, &notifications, now)?;
+    }
+
+    calendar.line("END:VCALENDAR");
+
+    Ok(calendar.content)
+}
+
+/// Renders the lessons of the timetable as weekly recurring events.
+/// The times are local times in the timezone of the class, it is included as a `VTIMEZONE`
+pub fn render_timetable(
+    name: &str,
+    timezone: Tz,
+    lessons: &[RecurringLesson],
+    now: NaiveDateTime,
+) -> String {
+    let mut calendar = Calendar::default();
+
+    calendar.begin(name);
+    calendar.line(&format!("X-WR-TIMEZONE:{}", timezone.name()));
+
+    // the local starts are at most a day away from UTC
+    let from = lessons.iter().map(|recurring| recurring.start).min();
+    let until = lessons.iter().map(|recurring| recurring.until).max();
+    render_timezone(
+        &mut calendar,
+        timezone,
+        from.map_or(now, |from| from - Duration::days(1)),
+        until.unwrap_or(now),
+    );
+
+    let timezone = timezone.name();
+    for recurring in lessons {
+        let lesson = recurring.lesson;
+        let local = |time: NaiveDateTime| format!("TZID={}:{}", timezone, format_local(time));
+
+        calendar.line("BEGIN:VEVENT");
+        calendar.line(&format!("UID:{}", lesson.id));
+        calendar.line(&format!("DTSTAMP:{}", format_datetime(now)));
+        calendar.line(&format!("DTSTART;{}", local(recurring.start)));
+        calendar.line(&format!("DTEND;{}", local(recurring.end)));
+        calendar.line(&format!(
+            "RRULE:FREQ=WEEKLY;INTERVAL={};UNTIL={}",
+            recurring.interval,
+            format_datetime(recurring.until)
+        ));
+        if !recurring.exceptions.is_empty() {
+            let exceptions = recurring
+                .exceptions
+                .iter()
+                .map(|exception| format_local(*exception))
+                .collect::<Vec<_>>();
+            calendar.line(&format!(
+                "EXDATE;TZID={}:{}",
+                timezone,
+                exceptions.join(",")
+            ));
+        }
+        calendar.line(&format!("SUMMARY:{}", escape_text(&lesson.subject)));
+        if !lesson.room.is_empty() {
+            calendar.line(&format!("LOCATION:{}", escape_text(&lesson.room)));
+        }
+        let description = [&*lesson.teacher, &*lesson.description]
+            .iter()
+            .filter(|text| !text.is_empty())
+            .copied()
+            .collect::<Vec<_>>()
+            .join("\n");
+        if !description.is_empty() {
+            calendar.line(&format!("DESCRIPTION:{}", escape_text(&description)));
+        }
+        calendar.line("END:VEVENT");
+    }
+
+    calendar.line("END:VCALENDAR");
+
+    calendar.content
+}
+
+/// Calendar apps only know the timezones that are in the calendar, so the offset changes between
+/// `from` and `until` are rendered as single observances
+fn render_timezone(
+    calendar: &mut Calendar,
+    timezone: Tz,
+    from: NaiveDateTime,
+    until: NaiveDateTime,
+) {
+    calendar.line("BEGIN:VTIMEZONE");
+    calendar.line(&format!("TZID:{}", timezone.name()));
+
+    let mut previous = timezone.offset_from_utc_datetime(&from).fix();
+    render_observance(calendar, timezone, from, previous);
+    for change in offset_changes(timezone, from, until) {
+        render_observance(calendar, timezone, change, previous);
+        previous = timezone.offset_from_utc_datetime(&change).fix();
+    }
+
+    calendar.line("END:VTIMEZONE");
+}
+
+/// `start` is in UTC, it is rendered in the offset `before` the change
+fn render_observance(
+    calendar: &mut Calendar,
+    timezone: Tz,
+    start: NaiveDateTime,
+    before: FixedOffset,
+) {
+    let after = timezone.offset_from_utc_datetime(&start);
+    let kind = if after.dst_offset() == Duration::zero() {
+        "STANDARD"
+    } else {
+        "DAYLIGHT"
+    };
+    calendar.line(&format!("BEGIN:{}", kind));
+    calendar.line(&format!("DTSTART:{}", format_local(start + before)));
+    calendar.line(&format!("TZOFFSETFROM:{}", format_offset(before)));
+    calendar.line(&format!("TZOFFSETTO:{}", format_offset(after.fix())));
+    calendar.line(&format!("TZNAME:{}", after.abbreviation()));
+    calendar.line(&format!("END:{}", kind));
+}
+
+/// The times (in UTC) at which the offset of the timezone changes, to the second
+fn offset_changes(timezone: Tz, from: NaiveDateTime, until: NaiveDateTime) -> Vec<NaiveDateTime> {
+    let offset = |time: NaiveDateTime| timezone.offset_from_utc_datetime(&time).fix();
+
+    let mut changes = vec![];
+    let mut day = from;
+    while day < until {
+        let next = day + Duration::days(1);
+        if offset(day) != offset(next) {
+            let (mut before, mut after) = (day, next);
+            while after - before > Duration::seconds(1) {
+                let middle = before + (after - before) / 2;
+                if offset(middle) == offset(before) {
+                    before = middle;
+                } else {
+                    after = middle;
+                }
+            }
+            changes.push(after);
+        }
+        day = next;
+    }
+    changes
+}
+
+fn render_event(
+    calendar: &mut Calendar,
+    timezone: Tz,
+    event: Event,
+    e_type: Option<&EventType>,
+    notifications: &[EventNotification],
+    now: NaiveDateTime,
+) -> ServiceResult<()> {
+    let time = |time: NaiveDateTime| match event.rec_frequency {
+        Some(_) => format!(
+            ";TZID={}:{}",
+            timezone.name(),
+            format_local(timezone.from_utc_datetime(&time).naive_local())
+        ),
+        None => format!(":{}", format_datetime(time)),
+    };
+
+    calendar.line("BEGIN:VEVENT");
+    calendar.line(&format!("UID:{}", event.id));
+    calendar.line(&format!("DTSTAMP:{}", format_datetime(now)));
+    calendar.line(&format!("DTSTART{}", time(event.start)));
+    if let Some(end) = event.end.filter(|end| *end > event.start) {
+        calendar.line(&format!("DTEND{}", time(end)));
+    }
+    calendar.line(&format!("SUMMARY:{}", escape_text(&event.name)));
+    if !event.description.is_empty() {
+        calendar.line(&format!("DESCRIPTION:{}", escape_text(&event.description)));
+    }
+    if let Some(e_type) = e_type {
+        calendar.line(&format!(
+            "CATEGORIES:{}",
+            escape_text(&e_type.display.to_uppercase())
+        ));
+    }
+
+    if let Some(frequency) = &event.rec_frequency {
+        let mut rule = format!(
+            "RRULE:FREQ={};INTERVAL={}",
+            frequency.to_uppercase(),
+            event.rec_interval
+        );
+        if let Some(until) = event.rec_until {
+            rule.push_str(&format!(";UNTIL={}", format_datetime(until)));
+        }
+        if let Some(count) = event.rec_count {
+            rule.push_str(&format!(";COUNT={}", count));
+        }
+        calendar.line(&rule);
+
+        if !event.rec_exceptions.is_empty() {
+            let exceptions = event
+                .rec_exceptions
+                .iter()
+                .map(|exception| format_local(timezone.from_utc_datetime(exception).naive_local()))
+                .collect::<Vec<_>>();
+            calendar.line(&format!(
+                "EXDATE;TZID={}:{}",
+                timezone.name(),
+                exceptions.join(",")
+            ));
+        }
+    }
+
+    for notification in notifications {
+        calendar.line("BEGIN:VALARM");
+        calendar.line("ACTION:DISPLAY");
+        calendar.line(&format!("DESCRIPTION:{}", escape_text(&event.name)));
+        calendar.line(&format!(
+            "TRIGGER:{}",
+            format_duration((notification.time - event.start).num_seconds())
+        ));
+        calendar.line("END:VALARM");
+    }
+
+    calendar.line("END:VEVENT");
+    Ok(())
+}
+
+#[derive(Default)]
+struct Calendar {
+    content: String,
+}
+
+impl Calendar {
+    fn begin(&mut self, name: &str) {
+        self.line("BEGIN:VCALENDAR");
+        self.line("VERSION:2.0");
+        self.line(&format!("PRODID:{}", PRODUCT_ID));
+        self.line("CALSCALE:GREGORIAN");
+        self.line("METHOD:PUBLISH");
+        self.line(&format!("X-WR-CALNAME:{}", escape_text(name)));
+    }
+
+    /// Adds a content line, folding it if it is too long
+    fn line(&mut self, line: &str) {
+        let mut length = 0;
+        for char in line.chars() {
+            if length + char.len_utf8() > MAX_LINE_LENGTH {
+                self.content.push_str("\r\n ");
+                length = 1;
+            }
+            self.content.push(char);
+            length += char.len_utf8();
+        }
+        self.content.push_str("\r\n");
+    }
+}
+
+fn format_datetime(datetime: NaiveDateTime) -> String {
+    datetime.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// A local time, used together with a `TZID`
+fn format_local(datetime: NaiveDateTime) -> String {
+    datetime.format("%Y%m%dT%H%M%S").to_string()
+}
+
+/// Formats a duration relative to the start of the event, eg. `-PT3600S`
+/// `+0100`
+fn format_offset(offset: FixedOffset) -> String {
+    let seconds = offset.local_minus_utc();
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let minutes = seconds.abs() / 60;
+    format!("{}{:02}{:02}", sign, minutes / 60, minutes % 60)
+}
+
+fn format_duration(seconds: i64) -> String {
+    if seconds < 0 {
+        format!("-PT{}S", -seconds)
+    } else {
+        format!("PT{}S", seconds)
+    }
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::datetime;
+
+    #[test]
+    fn escape_and_fold() {
+        assert_eq!(escape_text("a;b,c\\d\ne"), "a\\;b\\,c\\\\d\\ne");
+
+        let mut calendar = Calendar::default();
+        calendar.line(&"ä".repeat(40));
+        let lines = calendar.content.split("\r\n").collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert!(lines[1].starts_with(' '));
+    }
+
+    #[test]
+    fn render_recurring_event() {
+        let event = Event {
+            id: uuid::Uuid::nil(),
+            name: "Mathe, Prüfung".to_string(),
+            end: Some(datetime("2021-08-20 09:00")),
+            rec_frequency: Some("weekly".to_string()),
+            rec_interval: 2,
+            rec_count: Some(4),
+            rec_exceptions: vec![datetime("2021-09-03 08:00")],
+            ..Event::test(datetime("2021-08-20 08:00"))
+        };
+
+        let notifications = vec![
+            EventNotification {
+                event: event.id,
+                time: datetime("2021-08-13 08:00"),
+            },
+            EventNotification {
+                event: event.id,
+                time: datetime("2021-08-19 18:00"),
+            },
+        ];
+
+        let exam = EventType {
+            id: 2,
+            display: "exam".to_string(),
+            class: None,
+            colour: "#e53935".to_string(),
+            reminders: vec![],
+        };
+
+        let single = Event::test(datetime("2021-08-21 10:00"));
+
+        let calendar = render_calendar(
+            "Klasse",
+            chrono_tz::Europe::Zurich,
+            vec![(event, notifications), (single, vec![])],
+            &[exam],
+            datetime("2021-08-01 00:00"),
+        )
+        .unwrap();
+
+        assert!(calendar.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(calendar.ends_with("END:VCALENDAR\r\n"));
+        assert!(calendar.contains("BEGIN:VTIMEZONE\r\nTZID:Europe/Zurich\r\n"));
+        assert!(calendar.contains("DTSTART;TZID=Europe/Zurich:20210820T100000\r\n"));
+        assert!(calendar.contains("DTEND;TZID=Europe/Zurich:20210820T110000\r\n"));
+        assert!(calendar.contains("DTSTART:20210821T100000Z\r\n"));
+        assert!(calendar.contains("SUMMARY:Mathe\\, Prüfung\r\n"));
+        assert!(calendar.contains("CATEGORIES:EXAM\r\n"));
+        assert!(calendar.contains("RRULE:FREQ=WEEKLY;INTERVAL=2;COUNT=4\r\n"));
+        assert!(calendar.contains("EXDATE;TZID=Europe/Zurich:20210903T100000\r\n"));
+        assert!(calendar.contains("TRIGGER:-PT604800S\r\n"));
+        assert!(calendar.contains("TRIGGER:-PT50400S\r\n"));
+    }
+
+    #[test]
+    fn render_timetable_lessons() {
+        let lesson = crate::models::Lesson {
+            id: uuid::Uuid::nil(),
+            class: uuid::Uuid::nil(),
+            weekday: 0,
+            start: 8 * 60 * 60 * 1000,
+            end: 9 * 60 * 60 * 1000,
+            subject: "Mathe".to_string(),
+            room: "B204".to_string(),
+            teacher: "Meier".to_string(),
+            description: "Heft mitnehmen".to_string(),
+            cycle_week: Some(1),
+            version: uuid::Uuid::nil(),
+            subject_id: uuid::Uuid::nil(),
+        };
+        let recurring = RecurringLesson {
+            lesson: &lesson,
+            start: datetime("2021-08-30 08:00"),
+            end: datetime("2021-08-30 09:00"),
+            interval: 2,
+            until: datetime("2022-07-31 21:59") + chrono::Duration::seconds(59),
+            exceptions: vec![datetime("2021-10-11 08:00"), datetime("2021-10-25 08:00")],
+        };
+
+        let calendar = render_timetable(
+            "Klasse",
+            chrono_tz::Europe::Zurich,
+            &[recurring],
+            datetime("2021-08-01 00:00"),
+        );
+
+        assert!(calendar.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(calendar.contains("X-WR-TIMEZONE:Europe/Zurich\r\n"));
+        let timezone = calendar.find("BEGIN:VTIMEZONE\r\nTZID:Europe/Zurich\r\n");
+        assert!(timezone.is_some() && timezone < calendar.find("BEGIN:VEVENT"));
+        assert!(calendar.contains(
+            "BEGIN:STANDARD\r\nDTSTART:20211031T030000\r\nTZOFFSETFROM:+0200\r\nTZOFFSETTO:+0100\r\nTZNAME:CET\r\nEND:STANDARD\r\n"
+        ));
+        assert!(calendar.contains(
+            "BEGIN:DAYLIGHT\r\nDTSTART:20220327T020000\r\nTZOFFSETFROM:+0100\r\nTZOFFSETTO:+0200\r\nTZNAME:CEST\r\nEND:DAYLIGHT\r\n"
+        ));
+        assert!(calendar.contains("DTSTART;TZID=Europe/Zurich:20210830T080000\r\n"));
+        assert!(calendar.contains("DTEND;TZID=Europe/Zurich:20210830T090000\r\n"));
+        assert!(calendar.contains("RRULE:FREQ=WEEKLY;INTERVAL=2;UNTIL=20220731T215959Z\r\n"));
+        assert!(calendar.contains("EXDATE;TZID=Europe/Zurich:20211011T080000,20211025T080000\r\n"));
+        assert!(calendar.contains("SUMMARY:Mathe\r\n"));
+        assert!(calendar.contains("LOCATION:B204\r\n"));
+        assert!(calendar.contains("DESCRIPTION:Meier\\nHeft mitnehmen\r\n"));
+        assert!(calendar.ends_with("END:VCALENDAR\r\n"));
+    }
+}
@@ -11,7 +11,7 @@ use diesel::prelude::*;
 use diesel::r2d2;
 use diesel::r2d2::ConnectionManager;
 use jsonwebtoken::{DecodingKey, EncodingKey};
-use tracing::info;
+use tracing::{error, info};
 use tracing_subscriber::EnvFilter;
 
 use crate::actions::Pool;
@@ -19,9 +19,16 @@ use crate::handlers::config;
 
 pub mod actions;
 mod error;
+mod grades;
 mod handlers;
+mod history;
+mod ical;
 mod models;
+mod recurrence;
 mod schema;
+mod timetable;
+mod timetable_import;
+mod workload;
 
 #[actix_rt::main]
 async fn main() -> Result<(), Report> {
@@ -40,6 +47,11 @@ async fn main() -> Result<(), Report> {
     let encoding_key = EncodingKey::from_secret(secret.as_bytes());
     let decoding_key = DecodingKey::from_secret(secret.as_bytes());
 
+    let retention_days = env::var("TRASH_RETENTION_DAYS")
+        .map(|days| days.parse().expect("TRASH_RETENTION_DAYS must be a number"))
+        .unwrap_or(30);
+    start_trash_purge(pool.clone(), chrono::Duration::days(retention_days));
+
     info!("Starting Server");
 
     HttpServer::new(move || {
@@ -63,6 +75,18 @@ async fn main() -> Result<(), Report> {
     Ok(())
 }
 
+/// Deletes everything that has been in the trash for longer than `retention` for good, every hour
+fn start_trash_purge(pool: Pool, retention: chrono::Duration) {
+    std::thread::spawn(move || loop {
+        let before = chrono::Utc::now().naive_utc() - retention;
+        match actions::class::purge_trash(&pool, before) {
+            Ok(purged) => info!(%purged, "purged trash"),
+            Err(err) => error!(?err, "failed to purge trash"),
+        }
+        std::thread::sleep(std::time::Duration::from_secs(60 * 60));
+    });
+}
+
 fn setup() -> std::result::Result<(), Report> {
     if env::var("RUST_LIB_BACKTRACE").is_err() {
         env::set_var("RUST_LIB_BACKTRACE", "1")
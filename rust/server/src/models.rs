@@ -1,4 +1,6 @@
+use crate::error::{ServiceErr, ServiceResult};
 use crate::schema::*;
+use chrono_tz::Tz;
 use diesel::{Identifiable, Insertable, Queryable};
 use uuid::Uuid;
 
@@ -45,6 +47,23 @@ pub struct Class {
     pub name: String,
     pub description: String,
     pub discord_id: Option<String>,
+    /// Set if the class is in the trash
+    pub deleted_at: Option<chrono::NaiveDateTime>,
+    /// The maximum amount of exams in a week
+    pub exam_limit: i32,
+    /// Whether exams that exceed the limit are rejected instead of only warned about
+    pub strict_exam_limit: bool,
+    /// The IANA name of the timezone of the class
+    pub timezone: String,
+}
+
+impl Class {
+    /// The timezone is validated when the class is saved
+    pub fn tz(&self) -> ServiceResult<Tz> {
+        self.timezone
+            .parse()
+            .map_err(|_| ServiceErr::InternalServerError("invalid-timezone".to_string()))
+    }
 }
 
 #[derive(Debug, Insertable, Queryable, Identifiable)]
@@ -55,6 +74,9 @@ pub struct NewClass<'a> {
     pub name: &'a str,
     pub description: &'a str,
     pub discord_id: Option<&'a str>,
+    pub exam_limit: i32,
+    pub strict_exam_limit: bool,
+    pub timezone: &'a str,
 }
 
 #[derive(Debug, Clone, Queryable)]
@@ -63,6 +85,8 @@ pub struct Member {
     pub class: Uuid,
     pub display_name: String,
     pub role: i32,
+    /// Set if the member is in the trash
+    pub deleted_at: Option<chrono::NaiveDateTime>,
 }
 
 #[derive(Debug, Clone, Insertable, Identifiable, AsChangeset)]
@@ -75,7 +99,7 @@ pub struct NewMember<'a> {
     pub role: i32,
 }
 
-#[derive(Debug, Clone, Queryable)]
+#[derive(Debug, Clone, Queryable, Identifiable)]
 pub struct Event {
     pub id: Uuid,
     pub class: Uuid,
@@ -84,11 +108,25 @@ pub struct Event {
     pub start: chrono::NaiveDateTime,
     pub end: Option<chrono::NaiveDateTime>,
     pub description: String,
-    pub notification: Option<chrono::NaiveDateTime>,
+    pub rec_frequency: Option<String>,
+    pub rec_interval: i32,
+    pub rec_until: Option<chrono::NaiveDateTime>,
+    pub rec_count: Option<i32>,
+    pub rec_exceptions: Vec<chrono::NaiveDateTime>,
+    pub series: Option<Uuid>,
+    /// The `UID` of the iCalendar event this event was imported from
+    pub import_uid: Option<String>,
+    /// Set if the event is in the trash
+    pub deleted_at: Option<chrono::NaiveDateTime>,
+    /// The name of the subject, always the same as the one of `subject_id`
+    pub subject: Option<String>,
+    pub subject_id: Option<Uuid>,
 }
 
+/// `None` fields are written as `NULL` on update, since an event is always replaced as a whole
 #[derive(Debug, Insertable, Queryable, Identifiable, AsChangeset)]
 #[table_name = "events"]
+#[changeset_options(treat_none_as_null = "true")]
 pub struct NewEvent<'a> {
     pub id: Uuid,
     pub class: Uuid,
@@ -97,26 +135,345 @@ pub struct NewEvent<'a> {
     pub start: &'a chrono::NaiveDateTime,
     pub end: Option<&'a chrono::NaiveDateTime>,
     pub description: &'a str,
-    pub notification: Option<&'a chrono::NaiveDateTime>,
+    pub rec_frequency: Option<&'a str>,
+    pub rec_interval: i32,
+    pub rec_until: Option<&'a chrono::NaiveDateTime>,
+    pub rec_count: Option<i32>,
+    pub rec_exceptions: &'a [chrono::NaiveDateTime],
+    pub series: Option<Uuid>,
+    pub import_uid: Option<&'a str>,
+    pub subject: Option<&'a str>,
+    pub subject_id: Option<Uuid>,
+}
+
+impl NewEvent<'_> {
+    /// The event as it will be stored
+    pub fn to_event(&self) -> Event {
+        Event {
+            id: self.id,
+            class: self.class,
+            e_type: self.e_type,
+            name: self.name.to_string(),
+            start: *self.start,
+            end: self.end.copied(),
+            description: self.description.to_string(),
+            rec_frequency: self.rec_frequency.map(str::to_string),
+            rec_interval: self.rec_interval,
+            rec_until: self.rec_until.copied(),
+            rec_count: self.rec_count,
+            rec_exceptions: self.rec_exceptions.to_vec(),
+            series: self.series,
+            import_uid: self.import_uid.map(str::to_string),
+            deleted_at: None,
+            subject: self.subject.map(str::to_string),
+            subject_id: self.subject_id,
+        }
+    }
+}
+
+/// Parses `2021-08-20 08:00`
+#[cfg(test)]
+pub fn datetime(str: &str) -> chrono::NaiveDateTime {
+    chrono::NaiveDateTime::parse_from_str(str, "%Y-%m-%d %H:%M").unwrap()
+}
+
+#[cfg(test)]
+impl Event {
+    /// An exam without a class, for tests
+    pub fn test(start: chrono::NaiveDateTime) -> Self {
+        NewEvent::test(Uuid::nil(), &start).to_event()
+    }
+}
+
+#[cfg(test)]
+impl<'a> NewEvent<'a> {
+    /// An exam of the class, for tests
+    pub fn test(class: Uuid, start: &'a chrono::NaiveDateTime) -> Self {
+        NewEvent {
+            id: Uuid::new_v4(),
+            class,
+            e_type: dto::EventType::EXAM,
+            name: "Prüfung",
+            start,
+            end: None,
+            description: "",
+            rec_frequency: None,
+            rec_interval: 1,
+            rec_until: None,
+            rec_count: None,
+            rec_exceptions: &[],
+            series: None,
+            import_uid: None,
+            subject: None,
+            subject_id: None,
+        }
+    }
+}
+
+/// A reminder for an event, an event can have any amount of them.
+/// For recurring events, the time is relative to the first occurrence.
+#[derive(Debug, Clone, PartialEq, Queryable, Insertable, Identifiable, Associations)]
+#[belongs_to(Event, foreign_key = "event")]
+#[primary_key(event, time)]
+pub struct EventNotification {
+    pub event: Uuid,
+    pub time: chrono::NaiveDateTime,
+}
+
+/// A member has marked the event as done.
+/// For recurring events, the whole series is marked.
+#[derive(Debug, Clone, Queryable, Insertable)]
+#[table_name = "event_completions"]
+pub struct EventCompletion {
+    pub event: Uuid,
+    pub user: Uuid,
+    pub class: Uuid,
+    pub done_at: chrono::NaiveDateTime,
 }
 
+/// The grade of a member for an exam, only the member can see it
+#[derive(Debug, Clone, Queryable, Insertable, AsChangeset)]
+#[table_name = "grades"]
+pub struct Grade {
+    pub user: Uuid,
+    pub event: Uuid,
+    pub class: Uuid,
+    pub grade: f64,
+    pub weight: f64,
+    pub scale: String,
+}
+
+/// A change of an event, `changes` and `snapshot` are JSON
+#[derive(Debug, Clone, Queryable)]
+pub struct EventRevision {
+    pub id: i32,
+    pub event: Uuid,
+    pub class: Uuid,
+    pub actor: Option<Uuid>,
+    pub kind: String,
+    pub time: chrono::NaiveDateTime,
+    pub changes: String,
+    pub snapshot: Option<String>,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[table_name = "event_revisions"]
+pub struct NewEventRevision<'a> {
+    pub event: Uuid,
+    pub class: Uuid,
+    pub actor: Option<Uuid>,
+    pub kind: &'a str,
+    pub time: chrono::NaiveDateTime,
+    pub changes: &'a str,
+    pub snapshot: Option<&'a str>,
+}
+
+/// The built-in event types have no class
 #[derive(Debug, Clone, Queryable)]
 pub struct EventType {
     pub id: i32,
     pub display: String,
+    pub class: Option<Uuid>,
+    pub colour: String,
+    /// In seconds before the start of the event
+    pub reminders: Vec<i32>,
 }
 
+#[derive(Debug, Insertable, AsChangeset)]
+#[table_name = "event_types"]
+pub struct NewEventType<'a> {
+    pub display: &'a str,
+    pub class: Uuid,
+    pub colour: &'a str,
+    pub reminders: &'a [i32],
+}
+
+/// The lessons of a timetable are stored in `lessons`, the timetable only marks that the class has one
 #[derive(Debug, Clone, Queryable)]
 pub struct Timetable {
     pub class: Uuid,
-    pub timetable: String,
+    pub cycle_weeks: i32,
+    pub cycle_anchor: chrono::NaiveDate,
 }
 
-#[derive(Debug, Insertable)]
-#[table_name = "timetables"]
-pub struct NewTimetable<'a> {
-    pub class: &'a Uuid,
-    pub timetable: &'a str,
+#[derive(Debug, Clone, Queryable, Identifiable)]
+pub struct Lesson {
+    pub id: Uuid,
+    pub class: Uuid,
+    /// `0` is monday
+    pub weekday: i32,
+    pub start: i64,
+    pub end: i64,
+    pub subject: String,
+    pub room: String,
+    pub teacher: String,
+    pub description: String,
+    /// `None` if the lesson is in every week of the cycle
+    pub cycle_week: Option<i32>,
+    pub version: Uuid,
+    pub subject_id: Uuid,
+}
+
+/// The subject is written as in the lesson, the actions look it up by its name and set `subject_id`
+#[derive(Debug, Insertable, AsChangeset)]
+#[table_name = "lessons"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct NewLesson<'a> {
+    pub class: Uuid,
+    pub weekday: i32,
+    pub start: i64,
+    pub end: i64,
+    pub subject: &'a str,
+    pub room: &'a str,
+    pub teacher: &'a str,
+    pub description: &'a str,
+    pub cycle_week: Option<i32>,
+    pub version: Uuid,
+}
+
+impl<'a> NewLesson<'a> {
+    pub fn from_dto(class: Uuid, version: Uuid, weekday: u8, lesson: &'a dto::Lesson) -> Self {
+        Self {
+            class,
+            version,
+            weekday: weekday as i32,
+            start: lesson.start,
+            end: lesson.end,
+            subject: &lesson.subject,
+            room: &lesson.room,
+            teacher: &lesson.teacher,
+            description: &lesson.description,
+            cycle_week: lesson.week.map(i32::from),
+        }
+    }
+}
+
+/// The timetable that is valid between the dates, both are included and `None` is unbounded
+#[derive(Debug, Clone, Queryable, Identifiable)]
+pub struct TimetableVersion {
+    pub id: Uuid,
+    pub class: Uuid,
+    pub valid_from: Option<chrono::NaiveDate>,
+    pub valid_until: Option<chrono::NaiveDate>,
+}
+
+impl TimetableVersion {
+    pub fn is_valid_on(&self, date: chrono::NaiveDate) -> bool {
+        let started = !matches!(self.valid_from, Some(from) if date < from);
+        let ended = matches!(self.valid_until, Some(until) if until < date);
+        started && !ended
+    }
+}
+
+#[derive(Debug, Insertable, AsChangeset)]
+#[table_name = "timetable_versions"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct NewTimetableVersion {
+    pub class: Uuid,
+    pub valid_from: Option<chrono::NaiveDate>,
+    pub valid_until: Option<chrono::NaiveDate>,
+}
+
+impl NewTimetableVersion {
+    pub fn from_dto(class: Uuid, version: &dto::TimetableVersion) -> Self {
+        Self {
+            class,
+            valid_from: version.valid_from.map(|from| from.to_naive().date()),
+            valid_until: version.valid_until.map(|until| until.to_naive().date()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Queryable, Identifiable)]
+pub struct Subject {
+    pub id: Uuid,
+    pub class: Uuid,
+    pub name: String,
+    pub short: String,
+    pub colour: String,
+    pub teacher: String,
+    pub room: String,
+}
+
+#[derive(Debug, Insertable, AsChangeset)]
+#[table_name = "subjects"]
+pub struct NewSubject<'a> {
+    pub class: Uuid,
+    pub name: &'a str,
+    pub short: &'a str,
+    pub colour: &'a str,
+    pub teacher: &'a str,
+    pub room: &'a str,
+}
+
+impl<'a> NewSubject<'a> {
+    pub fn from_dto(class: Uuid, subject: &'a dto::Subject) -> Self {
+        Self {
+            class,
+            name: subject.name.trim(),
+            short: &subject.short,
+            colour: &subject.colour,
+            teacher: &subject.teacher,
+            room: &subject.room,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Queryable, Identifiable)]
+pub struct TimetableException {
+    pub id: Uuid,
+    pub class: Uuid,
+    pub date: chrono::NaiveDate,
+    /// `None` for an extra lesson
+    pub lesson: Option<Uuid>,
+    pub cancelled: bool,
+    pub subject: Option<String>,
+    pub room: Option<String>,
+    pub teacher: Option<String>,
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+    pub description: String,
+}
+
+#[derive(Debug, Insertable, AsChangeset)]
+#[table_name = "timetable_exceptions"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct NewTimetableException<'a> {
+    pub class: Uuid,
+    pub date: chrono::NaiveDate,
+    pub lesson: Option<Uuid>,
+    pub cancelled: bool,
+    pub subject: Option<&'a str>,
+    pub room: Option<&'a str>,
+    pub teacher: Option<&'a str>,
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+    pub description: &'a str,
+}
+
+impl<'a> NewTimetableException<'a> {
+    pub fn from_dto(class: Uuid, exception: &'a dto::TimetableException) -> Self {
+        Self {
+            class,
+            date: exception.date.to_naive().date(),
+            lesson: exception.lesson,
+            cancelled: exception.cancelled,
+            subject: exception.subject.as_deref(),
+            room: exception.room.as_deref(),
+            teacher: exception.teacher.as_deref(),
+            start: exception.start,
+            end: exception.end,
+            description: &exception.description,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Queryable, Insertable)]
+#[table_name = "feed_tokens"]
+pub struct FeedToken {
+    pub token: Uuid,
+    pub user: Uuid,
+    pub class: Uuid,
 }
 
 #[derive(Debug, Clone, Queryable)]
@@ -138,7 +495,10 @@ pub struct NewGuild<'a> {
 
 pub mod conversion {
     use crate::error::{ServiceErr, ServiceResult};
-    use crate::models::{Class, Event, Guild, Member, MemberRole, User};
+    use crate::models::{
+        Class, Event, EventNotification, EventRevision, EventType, FeedToken, Grade, Guild, Lesson,
+        Member, MemberRole, Subject, Timetable, TimetableException, TimetableVersion, User,
+    };
 
     pub trait IntoDto<T> {
         fn into_dto(self) -> ServiceResult<T>;
@@ -152,6 +512,9 @@ pub mod conversion {
                 name: self.name,
                 description: self.description,
                 discord_id: self.discord_id,
+                exam_limit: self.exam_limit,
+                strict_exam_limit: self.strict_exam_limit,
+                timezone: self.timezone,
             })
         }
     }
@@ -172,6 +535,9 @@ pub mod conversion {
                 name: class.name,
                 description: class.description,
                 discord_id: class.discord_id,
+                exam_limit: class.exam_limit,
+                strict_exam_limit: class.strict_exam_limit,
+                timezone: class.timezone,
             })
         }
     }
@@ -234,19 +600,43 @@ pub mod conversion {
         }
     }
 
-    impl IntoDto<dto::EventType> for i32 {
+    impl IntoDto<dto::EventRevision> for EventRevision {
+        fn into_dto(self) -> ServiceResult<dto::EventRevision> {
+            let invalid_json = |err: serde_json::Error| {
+                ServiceErr::IntoDTOError(format!("Invalid revision {}", err))
+            };
+
+            Ok(dto::EventRevision {
+                id: self.id,
+                event: self.event,
+                actor: self.actor,
+                kind: dto::RevisionKind::from_name(&self.kind).ok_or_else(|| {
+                    ServiceErr::IntoDTOError(format!("Invalid revision kind {}", self.kind))
+                })?,
+                time: self.time.into(),
+                changes: serde_json::from_str(&self.changes).map_err(invalid_json)?,
+                snapshot: self
+                    .snapshot
+                    .as_deref()
+                    .map(serde_json::from_str)
+                    .transpose()
+                    .map_err(invalid_json)?,
+            })
+        }
+    }
+
+    impl IntoDto<dto::EventType> for EventType {
         fn into_dto(self) -> ServiceResult<dto::EventType> {
-            Ok(match self {
-                1 => dto::EventType::Homework,
-                2 => dto::EventType::Exam,
-                3 => dto::EventType::Holidays,
-                4 => dto::EventType::Other,
-                role => {
-                    return Err(ServiceErr::IntoDTOError(format!(
-                        "Invalid member role {}",
-                        role
-                    )))
-                }
+            Ok(dto::EventType {
+                id: self.id,
+                name: self.display,
+                colour: self.colour,
+                reminders: self
+                    .reminders
+                    .iter()
+                    .map(|seconds| *seconds as i64 * 1000)
+                    .collect(),
+                built_in: self.class.is_none(),
             })
         }
     }
@@ -274,28 +664,71 @@ pub mod conversion {
         }
     }
 
-    impl IntoDto<dto::Event> for Event {
+    impl IntoDto<dto::Event> for (Event, Vec<EventNotification>) {
         fn into_dto(self) -> ServiceResult<dto::Event> {
-            let end = self.end.map(|dt| dt.timestamp_millis());
-            let end = if let Some(0) = end { None } else { end };
+            let (event, notifications) = self;
+            let end = event.end.map(dto::Timestamp::from);
+            let end = if let Some(dto::Timestamp(0)) = end {
+                None
+            } else {
+                end
+            };
+
+            let recurrence = match &event.rec_frequency {
+                Some(frequency) => Some(dto::Recurrence {
+                    frequency: dto::Frequency::from_name(frequency).ok_or_else(|| {
+                        ServiceErr::IntoDTOError(format!("Invalid frequency {}", frequency))
+                    })?,
+                    interval: event.rec_interval,
+                    until: event.rec_until.map(dto::Timestamp::from),
+                    count: event.rec_count,
+                    exceptions: event
+                        .rec_exceptions
+                        .iter()
+                        .map(|ts| dto::Timestamp::from(*ts))
+                        .collect(),
+                }),
+                None => None,
+            };
+            let occurrence = recurrence
+                .as_ref()
+                .map(|_| dto::Timestamp::from(event.start));
 
             Ok(dto::Event {
-                id: self.id,
-                r#type: self.e_type.into_dto()?,
-                name: self.name,
-                start: self.start.timestamp_millis(),
+                id: event.id,
+                r#type: event.e_type,
+                name: event.name,
+                start: event.start.into(),
                 end,
-                description: self.description,
-                notification: self.notification.map(|ts| ts.timestamp_millis()),
+                description: event.description,
+                subject: event.subject,
+                subject_id: event.subject_id,
+                notifications: notifications
+                    .iter()
+                    .map(|notif| notif.time.into())
+                    .collect(),
+                recurrence,
+                occurrence,
+                done: false,
+                completions: None,
+                warnings: vec![],
             })
         }
     }
 
-    impl IntoDto<dto::Notification> for (Event, (Class, Guild)) {
+    impl IntoDto<dto::Notification>
+        for (
+            chrono::NaiveDateTime,
+            (Event, Vec<EventNotification>),
+            (Class, Guild),
+        )
+    {
         fn into_dto(self) -> ServiceResult<dto::Notification> {
-            let (event, (_, guild)) = self;
+            let (time, event, (class, guild)) = self;
             Ok(dto::Notification {
+                time: time.into(),
                 event: event.into_dto()?,
+                timezone: class.timezone,
                 guild: guild.id,
                 channel: guild.notif_channel.expect("Notif channel"),
                 role_ping: guild.notif_ping_role,
@@ -304,6 +737,12 @@ pub mod conversion {
         }
     }
 
+    impl IntoDto<dto::FeedToken> for FeedToken {
+        fn into_dto(self) -> ServiceResult<dto::FeedToken> {
+            Ok(dto::FeedToken { token: self.token })
+        }
+    }
+
     impl IntoDto<dto::Guild> for Guild {
         fn into_dto(self) -> ServiceResult<dto::Guild> {
             Ok(dto::Guild {
@@ -315,6 +754,109 @@ pub mod conversion {
         }
     }
 
+    impl IntoDto<dto::Grade> for (Grade, Event) {
+        fn into_dto(self) -> ServiceResult<dto::Grade> {
+            let (grade, event) = self;
+            Ok(dto::Grade {
+                event: grade.event,
+                class: grade.class,
+                grade: grade.grade,
+                weight: grade.weight,
+                scale: dto::GradeScale::from_name(&grade.scale).ok_or_else(|| {
+                    ServiceErr::IntoDTOError(format!("Invalid grade scale: {}", grade.scale))
+                })?,
+                name: event.name,
+                subject: event.subject,
+                start: event.start.into(),
+            })
+        }
+    }
+
+    impl IntoDto<dto::TimetableLesson> for Lesson {
+        fn into_dto(self) -> ServiceResult<dto::TimetableLesson> {
+            Ok(dto::TimetableLesson {
+                weekday: self.weekday as u8,
+                version: Some(self.version),
+                lesson: dto::Lesson {
+                    id: Some(self.id),
+                    subject: self.subject,
+                    subject_id: Some(self.subject_id),
+                    description: self.description,
+                    start: self.start,
+                    end: self.end,
+                    room: self.room,
+                    teacher: self.teacher,
+                    week: self.cycle_week.map(|week| week as u8),
+                },
+            })
+        }
+    }
+
+    impl IntoDto<dto::TimetableCycle> for Timetable {
+        fn into_dto(self) -> ServiceResult<dto::TimetableCycle> {
+            Ok(dto::TimetableCycle {
+                weeks: self.cycle_weeks as u8,
+                anchor: self.cycle_anchor.and_hms_opt(0, 0, 0).unwrap().into(),
+            })
+        }
+    }
+
+    impl IntoDto<dto::TimetableException> for TimetableException {
+        fn into_dto(self) -> ServiceResult<dto::TimetableException> {
+            Ok(dto::TimetableException {
+                id: Some(self.id),
+                date: self.date.and_hms_opt(0, 0, 0).unwrap().into(),
+                lesson: self.lesson,
+                cancelled: self.cancelled,
+                subject: self.subject,
+                room: self.room,
+                teacher: self.teacher,
+                start: self.start,
+                end: self.end,
+                description: self.description,
+            })
+        }
+    }
+
+    impl IntoDto<dto::Subject> for Subject {
+        fn into_dto(self) -> ServiceResult<dto::Subject> {
+            Ok(dto::Subject {
+                id: Some(self.id),
+                name: self.name,
+                short: self.short,
+                colour: self.colour,
+                teacher: self.teacher,
+                room: self.room,
+            })
+        }
+    }
+
+    impl IntoDto<dto::TimetableVersion> for TimetableVersion {
+        fn into_dto(self) -> ServiceResult<dto::TimetableVersion> {
+            let timestamp = |date: chrono::NaiveDate| date.and_hms_opt(0, 0, 0).unwrap().into();
+            Ok(dto::TimetableVersion {
+                id: Some(self.id),
+                valid_from: self.valid_from.map(timestamp),
+                valid_until: self.valid_until.map(timestamp),
+            })
+        }
+    }
+
+    /// Sorts the lessons into the days of the timetable, keeping their order
+    pub fn into_timetable(lessons: Vec<Lesson>) -> ServiceResult<dto::Timetable> {
+        let mut timetable = dto::Timetable::default();
+        for lesson in lessons {
+            let dto::TimetableLesson {
+                weekday, lesson, ..
+            } = lesson.into_dto()?;
+            timetable
+                .get_mut(weekday as usize)
+                .ok_or_else(|| ServiceErr::IntoDTOError(format!("Invalid weekday: {}", weekday)))?
+                .push(lesson);
+        }
+        Ok(timetable)
+    }
+
     impl<T, Dto> IntoDto<Vec<Dto>> for Vec<T>
     where
         T: IntoDto<Dto>,
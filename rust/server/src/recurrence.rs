@@ -0,0 +1,373 @@
+//! Expands recurring events into their single occurrences.
+//! Recurring events are stored once as a series, with the start of the first occurrence as `start`.
+//! The occurrences are stepped in the timezone of the class, so they keep their local time when
+//! daylight saving time starts or ends.
+
+use crate::models::{Event, EventNotification};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use dto::Frequency;
+
+/// The maximum amount of occurrences a single series is expanded to
+pub const MAX_OCCURRENCES: usize = 500;
+
+/// The last year an occurrence can be in, the iteration stops after that
+const MAX_YEAR: i32 = 9999;
+
+/// Iterates over the starts of all occurrences of a recurring event, in order.
+/// Excluded occurrences are skipped.
+pub struct Occurrences<'a> {
+    event: &'a Event,
+    /// The start of the first occurrence in the timezone
+    local_start: NaiveDateTime,
+    timezone: Tz,
+    frequency: Frequency,
+    step: i32,
+    generated: i32,
+}
+
+enum Step {
+    Start(NaiveDateTime),
+    /// The date does not exist, eg. the 31st of a month with 30 days
+    Invalid,
+    End,
+}
+
+impl<'a> Occurrences<'a> {
+    /// Returns `None` if the event is not recurring
+    pub fn of(event: &'a Event, timezone: Tz) -> Option<Self> {
+        let frequency = Frequency::from_name(event.rec_frequency.as_deref()?)?;
+        Some(Self {
+            event,
+            local_start: timezone.from_utc_datetime(&event.start).naive_local(),
+            timezone,
+            frequency,
+            step: 0,
+            generated: 0,
+        })
+    }
+
+    fn nth_start(&self, n: i32) -> Step {
+        match self.nth_local_start(n) {
+            Step::Start(local) => match self.to_utc(local) {
+                Some(start) => Step::Start(start),
+                None => Step::Invalid,
+            },
+            step => step,
+        }
+    }
+
+    /// A local time that is skipped when daylight saving time starts is moved by an hour,
+    /// the earlier one of a repeated local time is used
+    fn to_utc(&self, local: NaiveDateTime) -> Option<NaiveDateTime> {
+        self.timezone
+            .from_local_datetime(&local)
+            .earliest()
+            .or_else(|| {
+                self.timezone
+                    .from_local_datetime(&(local + Duration::hours(1)))
+                    .earliest()
+            })
+            .map(|start| start.naive_utc())
+    }
+
+    fn nth_local_start(&self, n: i32) -> Step {
+        let start = self.local_start;
+        let amount = n.checked_mul(self.event.rec_interval);
+
+        let months = match (self.frequency, amount) {
+            (_, None) => return Step::End,
+            (Frequency::Daily, Some(days)) => return add_days(start, days),
+            (Frequency::Weekly, Some(weeks)) => return add_days(start, weeks.saturating_mul(7)),
+            (Frequency::Monthly, Some(months)) => months,
+            (Frequency::Yearly, Some(years)) => years.saturating_mul(12),
+        };
+
+        let month0 = match (start.month0() as i32).checked_add(months) {
+            Some(month0) => month0,
+            None => return Step::End,
+        };
+        let year = start.year() + month0 / 12;
+        if year > MAX_YEAR {
+            return Step::End;
+        }
+
+        match NaiveDate::from_ymd_opt(year, (month0 % 12) as u32 + 1, start.day()) {
+            Some(date) => Step::Start(date.and_time(start.time())),
+            None => Step::Invalid,
+        }
+    }
+}
+
+fn add_days(start: NaiveDateTime, days: i32) -> Step {
+    match start.checked_add_signed(Duration::days(days as i64)) {
+        Some(date) if date.year() <= MAX_YEAR => Step::Start(date),
+        _ => Step::End,
+    }
+}
+
+impl Iterator for Occurrences<'_> {
+    type Item = NaiveDateTime;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(count) = self.event.rec_count {
+                if self.generated >= count {
+                    return None;
+                }
+            }
+
+            let start = match self.nth_start(self.step) {
+                Step::Start(start) => start,
+                Step::Invalid => {
+                    self.step += 1;
+                    continue;
+                }
+                Step::End => return None,
+            };
+            self.step += 1;
+
+            if let Some(until) = self.event.rec_until {
+                if start > until {
+                    return None;
+                }
+            }
+
+            self.generated += 1;
+            if !self.event.rec_exceptions.contains(&start) {
+                return Some(start);
+            }
+        }
+    }
+}
+
+/// Moves the event to the start of an occurrence, end and notifications are moved with it
+pub fn at_occurrence(
+    event: &Event,
+    notifications: &[EventNotification],
+    start: NaiveDateTime,
+) -> (Event, Vec<EventNotification>) {
+    let offset = start - event.start;
+    let occurrence = Event {
+        start,
+        end: event
+            .end
+            .filter(|end| *end >= event.start)
+            .map(|end| end + offset),
+        ..event.clone()
+    };
+    let notifications = notifications
+        .iter()
+        .map(|notif| EventNotification {
+            event: notif.event,
+            time: notif.time + offset,
+        })
+        .collect();
+    (occurrence, notifications)
+}
+
+/// Checks whether `start` is the start of a (not excluded) occurrence of the event
+pub fn is_occurrence(event: &Event, timezone: Tz, start: NaiveDateTime) -> bool {
+    match Occurrences::of(event, timezone) {
+        Some(occurrences) => occurrences
+            .take_while(|occurrence| *occurrence <= start)
+            .any(|occurrence| occurrence == start),
+        None => false,
+    }
+}
+
+/// Replaces every recurring event with its occurrences that overlap with the window.
+/// Events that do not recur are kept as they are.
+pub fn expand(
+    events: Vec<(Event, Vec<EventNotification>)>,
+    timezone: Tz,
+    after: Option<NaiveDateTime>,
+    before: Option<NaiveDateTime>,
+) -> Vec<(Event, Vec<EventNotification>)> {
+    events
+        .into_iter()
+        .flat_map(
+            |(event, notifications)| match Occurrences::of(&event, timezone) {
+                Some(occurrences) => occurrences
+                    .take_while(|start| before.is_none_or(|before| *start < before))
+                    .map(|start| at_occurrence(&event, &notifications, start))
+                    .filter(|(occurrence, _)| {
+                        after.is_none_or(|after| occurrence.end.unwrap_or(occurrence.start) > after)
+                    })
+                    .take(MAX_OCCURRENCES)
+                    .collect(),
+                None => vec![(event, notifications)],
+            },
+        )
+        .collect()
+}
+
+/// Returns the notifications of the occurrences of a recurring event that are due in the time
+/// between `since` and `until`, together with their occurrence
+pub fn due_notifications(
+    event: &Event,
+    timezone: Tz,
+    notifications: &[EventNotification],
+    since: NaiveDateTime,
+    until: NaiveDateTime,
+) -> Vec<(NaiveDateTime, (Event, Vec<EventNotification>))> {
+    let occurrences = match Occurrences::of(event, timezone) {
+        Some(occurrences) if !notifications.is_empty() => occurrences,
+        _ => return vec![],
+    };
+
+    occurrences
+        .map(|start| at_occurrence(event, notifications, start))
+        .take_while(|(_, notifications)| notifications.iter().any(|notif| notif.time < until))
+        .flat_map(|occurrence| {
+            occurrence
+                .1
+                .iter()
+                .map(|notif| notif.time)
+                .filter(|time| *time > since && *time < until)
+                .map(|time| (time, occurrence.clone()))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::datetime;
+
+    fn recurring(start: &str, frequency: &str, interval: i32) -> Event {
+        Event {
+            rec_frequency: Some(frequency.to_string()),
+            rec_interval: interval,
+            ..Event::test(datetime(start))
+        }
+    }
+
+    #[test]
+    fn weekly_with_count_and_exception() {
+        let mut event = recurring("2021-08-20 08:00", "weekly", 1);
+        event.rec_count = Some(3);
+        event.rec_exceptions = vec![datetime("2021-08-27 08:00")];
+
+        let starts = Occurrences::of(&event, Tz::UTC)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            starts,
+            vec![datetime("2021-08-20 08:00"), datetime("2021-09-03 08:00")]
+        );
+    }
+
+    #[test]
+    fn monthly_skips_invalid_dates() {
+        let mut event = recurring("2021-01-31 10:00", "monthly", 1);
+        event.rec_until = Some(datetime("2021-05-31 10:00"));
+
+        let starts = Occurrences::of(&event, Tz::UTC)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            starts,
+            vec![
+                datetime("2021-01-31 10:00"),
+                datetime("2021-03-31 10:00"),
+                datetime("2021-05-31 10:00"),
+            ]
+        );
+    }
+
+    #[test]
+    fn expand_in_window() {
+        let mut event = recurring("2021-08-02 08:00", "daily", 2);
+        event.end = Some(datetime("2021-08-02 09:00"));
+        let notification = EventNotification {
+            event: event.id,
+            time: datetime("2021-08-01 18:00"),
+        };
+
+        let occurrences = expand(
+            vec![(event, vec![notification])],
+            Tz::UTC,
+            Some(datetime("2021-08-04 08:30")),
+            Some(datetime("2021-08-08 08:00")),
+        );
+
+        let starts = occurrences.iter().map(|(e, _)| e.start).collect::<Vec<_>>();
+        assert_eq!(
+            starts,
+            vec![datetime("2021-08-04 08:00"), datetime("2021-08-06 08:00")]
+        );
+        assert_eq!(occurrences[1].0.end, Some(datetime("2021-08-06 09:00")));
+        assert_eq!(occurrences[1].1[0].time, datetime("2021-08-05 18:00"));
+    }
+
+    #[test]
+    fn notifications_of_occurrences() {
+        let event = recurring("2021-08-02 08:00", "weekly", 1);
+        // a week and a day before
+        let notifications = [
+            EventNotification {
+                event: event.id,
+                time: datetime("2021-07-26 08:00"),
+            },
+            EventNotification {
+                event: event.id,
+                time: datetime("2021-08-01 08:00"),
+            },
+        ];
+
+        let due = due_notifications(
+            &event,
+            Tz::UTC,
+            &notifications,
+            datetime("2021-08-08 07:59"),
+            datetime("2021-08-09 08:01"),
+        );
+        let starts = due
+            .iter()
+            .map(|(time, (occurrence, _))| (*time, occurrence.start))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            starts,
+            vec![
+                (datetime("2021-08-08 08:00"), datetime("2021-08-09 08:00")),
+                (datetime("2021-08-09 08:00"), datetime("2021-08-16 08:00")),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_local_time_over_dst() {
+        // 08:00 in Zurich, daylight saving time starts on the 28th of March
+        let mut event = recurring("2021-03-22 07:00", "weekly", 1);
+        event.rec_count = Some(2);
+
+        let starts = Occurrences::of(&event, chrono_tz::Europe::Zurich)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            starts,
+            vec![datetime("2021-03-22 07:00"), datetime("2021-03-29 06:00")]
+        );
+    }
+
+    #[test]
+    fn skipped_local_time_is_moved() {
+        // 02:30 in Zurich does not exist on the 28th of March
+        let mut event = recurring("2021-03-27 01:30", "daily", 1);
+        event.rec_count = Some(3);
+
+        let starts = Occurrences::of(&event, chrono_tz::Europe::Zurich)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            starts,
+            vec![
+                datetime("2021-03-27 01:30"),
+                datetime("2021-03-28 01:30"),
+                datetime("2021-03-29 00:30"),
+            ]
+        );
+    }
+}
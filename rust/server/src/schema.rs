@@ -5,6 +5,39 @@ table! {
         name -> Varchar,
         description -> Varchar,
         discord_id -> Nullable<Varchar>,
+        deleted_at -> Nullable<Timestamp>,
+        exam_limit -> Int4,
+        strict_exam_limit -> Bool,
+        timezone -> Varchar,
+    }
+}
+
+table! {
+    event_completions (event, user) {
+        event -> Uuid,
+        user -> Uuid,
+        class -> Uuid,
+        done_at -> Timestamp,
+    }
+}
+
+table! {
+    event_notifications (event, time) {
+        event -> Uuid,
+        time -> Timestamp,
+    }
+}
+
+table! {
+    event_revisions (id) {
+        id -> Int4,
+        event -> Uuid,
+        class -> Uuid,
+        actor -> Nullable<Uuid>,
+        kind -> Varchar,
+        time -> Timestamp,
+        changes -> Text,
+        snapshot -> Nullable<Text>,
     }
 }
 
@@ -12,6 +45,9 @@ table! {
     event_types (id) {
         id -> Int4,
         display -> Varchar,
+        class -> Nullable<Uuid>,
+        colour -> Varchar,
+        reminders -> Array<Int4>,
     }
 }
 
@@ -24,7 +60,35 @@ table! {
         start -> Timestamp,
         end -> Nullable<Timestamp>,
         description -> Varchar,
-        notification -> Nullable<Timestamp>,
+        rec_frequency -> Nullable<Varchar>,
+        rec_interval -> Int4,
+        rec_until -> Nullable<Timestamp>,
+        rec_count -> Nullable<Int4>,
+        rec_exceptions -> Array<Timestamp>,
+        series -> Nullable<Uuid>,
+        import_uid -> Nullable<Varchar>,
+        deleted_at -> Nullable<Timestamp>,
+        subject -> Nullable<Varchar>,
+        subject_id -> Nullable<Uuid>,
+    }
+}
+
+table! {
+    feed_tokens (token) {
+        token -> Uuid,
+        user -> Uuid,
+        class -> Uuid,
+    }
+}
+
+table! {
+    grades (user, event) {
+        user -> Uuid,
+        event -> Uuid,
+        class -> Uuid,
+        grade -> Float8,
+        weight -> Float8,
+        scale -> Varchar,
     }
 }
 
@@ -37,6 +101,23 @@ table! {
     }
 }
 
+table! {
+    lessons (id) {
+        id -> Uuid,
+        class -> Uuid,
+        weekday -> Int4,
+        start -> Int8,
+        end -> Int8,
+        subject -> Varchar,
+        room -> Varchar,
+        teacher -> Varchar,
+        description -> Varchar,
+        cycle_week -> Nullable<Int4>,
+        version -> Uuid,
+        subject_id -> Uuid,
+    }
+}
+
 table! {
     member_roles (id) {
         id -> Int4,
@@ -50,13 +131,52 @@ table! {
         class -> Uuid,
         display_name -> Varchar,
         role -> Int4,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    subjects (id) {
+        id -> Uuid,
+        class -> Uuid,
+        name -> Varchar,
+        short -> Varchar,
+        colour -> Varchar,
+        teacher -> Varchar,
+        room -> Varchar,
+    }
+}
+
+table! {
+    timetable_exceptions (id) {
+        id -> Uuid,
+        class -> Uuid,
+        date -> Date,
+        lesson -> Nullable<Uuid>,
+        cancelled -> Bool,
+        subject -> Nullable<Varchar>,
+        room -> Nullable<Varchar>,
+        teacher -> Nullable<Varchar>,
+        start -> Nullable<Int8>,
+        end -> Nullable<Int8>,
+        description -> Varchar,
+    }
+}
+
+table! {
+    timetable_versions (id) {
+        id -> Uuid,
+        class -> Uuid,
+        valid_from -> Nullable<Date>,
+        valid_until -> Nullable<Date>,
     }
 }
 
 table! {
     timetables (class) {
         class -> Uuid,
-        timetable -> Text,
+        cycle_weeks -> Int4,
+        cycle_anchor -> Date,
     }
 }
 
@@ -72,20 +192,43 @@ table! {
 }
 
 joinable!(classes -> users (owner));
+joinable!(event_completions -> events (event));
+joinable!(event_notifications -> events (event));
+joinable!(event_revisions -> classes (class));
+joinable!(event_revisions -> users (actor));
+joinable!(event_types -> classes (class));
+joinable!(grades -> events (event));
 joinable!(events -> classes (class));
 joinable!(events -> event_types (e_type));
+joinable!(events -> subjects (subject_id));
+joinable!(lessons -> subjects (subject_id));
+joinable!(lessons -> timetable_versions (version));
+joinable!(lessons -> timetables (class));
 joinable!(members -> classes (class));
 joinable!(members -> member_roles (role));
 joinable!(members -> users (user));
+joinable!(subjects -> classes (class));
+joinable!(timetable_exceptions -> lessons (lesson));
+joinable!(timetable_exceptions -> timetables (class));
+joinable!(timetable_versions -> timetables (class));
 joinable!(timetables -> classes (class));
 
 allow_tables_to_appear_in_same_query!(
     classes,
+    event_completions,
+    event_notifications,
+    event_revisions,
     event_types,
     events,
+    feed_tokens,
+    grades,
     guilds,
+    lessons,
     member_roles,
     members,
+    subjects,
+    timetable_exceptions,
+    timetable_versions,
     timetables,
     users,
 );
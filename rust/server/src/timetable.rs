@@ -0,0 +1,579 @@
+//! Applies the exceptions of a date to the timetable, to get the lessons that actually take place.
+//! Also turns the lessons into weekly recurring events for the calendar export.
+
+use crate::models::{self, Event, TimetableVersion};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use dto::{
+    EffectiveDay, EffectiveHoliday, EffectiveLesson, Lesson, Timetable, TimetableCycle,
+    TimetableException,
+};
+use std::collections::BTreeSet;
+
+/// School years start on the first of august
+const SCHOOL_YEAR_START_MONTH: u32 = 8;
+
+/// A range of dates, including both ends. `None` means unbounded
+pub type DateRange = (Option<NaiveDate>, Option<NaiveDate>);
+
+pub fn ranges_overlap((a_from, a_until): DateRange, (b_from, b_until): DateRange) -> bool {
+    let a_before_b = matches!((a_until, b_from), (Some(until), Some(from)) if until < from);
+    let b_before_a = matches!((b_until, a_from), (Some(until), Some(from)) if until < from);
+    !a_before_b && !b_before_a
+}
+
+/// The version of the timetable that is valid on the date. Versions never overlap
+pub fn active_version(versions: &[TimetableVersion], date: NaiveDate) -> Option<&TimetableVersion> {
+    versions.iter().find(|version| version.is_valid_on(date))
+}
+
+/// The first and the last day of the school year that the date is in
+pub fn school_year(date: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let year = if date.month() >= SCHOOL_YEAR_START_MONTH {
+        date.year()
+    } else {
+        date.year() - 1
+    };
+    let start = |year| NaiveDate::from_ymd_opt(year, SCHOOL_YEAR_START_MONTH, 1).unwrap();
+    (start(year), start(year + 1).pred_opt().unwrap())
+}
+
+/// The first and the last day of the version. An unbounded version is bounded by the school year
+/// that its other end is in, or the school year of today if both are unbounded
+pub fn version_range(version: &TimetableVersion, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let from = version
+        .valid_from
+        .unwrap_or_else(|| school_year(version.valid_until.unwrap_or(today)).0);
+    let until = version.valid_until.unwrap_or_else(|| school_year(from).1);
+    (from, until)
+}
+
+/// The first and the last day in the timezone that are covered by the holidays.
+/// Holidays that end at 00:00 do not cover the day they end on
+fn holiday_days(holiday: &Event, timezone: Tz) -> (NaiveDate, NaiveDate) {
+    let local = |time: NaiveDateTime| timezone.from_utc_datetime(&time).naive_local();
+    let start = local(holiday.start);
+    let last = match holiday.end.map(local).filter(|end| *end > start) {
+        Some(end) if end.time() == chrono::NaiveTime::MIN => end.date().pred_opt().unwrap(),
+        Some(end) => end.date(),
+        None => start.date(),
+    };
+    (start.date(), last)
+}
+
+/// The dates in the timezone that are covered by the holidays
+pub fn holiday_dates(holidays: &[Event], timezone: Tz) -> BTreeSet<NaiveDate> {
+    holidays
+        .iter()
+        .flat_map(|holiday| {
+            let (first, last) = holiday_days(holiday, timezone);
+            first.iter_days().take_while(move |date| *date <= last)
+        })
+        .collect()
+}
+
+/// The holidays that cover the date, the one that lasts the longest if there are several
+pub fn holiday_on(holidays: &[Event], timezone: Tz, date: NaiveDate) -> Option<EffectiveHoliday> {
+    holidays
+        .iter()
+        .map(|holiday| (holiday, holiday_days(holiday, timezone)))
+        .filter(|(_, (first, last))| (*first..=*last).contains(&date))
+        .max_by_key(|(_, (_, last))| *last)
+        .map(|(holiday, (_, last))| EffectiveHoliday {
+            event: holiday.id,
+            name: holiday.name.clone(),
+            until: last.and_hms_opt(0, 0, 0).unwrap().into(),
+        })
+}
+
+/// A lesson as an event that repeats every `interval` weeks
+#[derive(Debug, Clone)]
+pub struct RecurringLesson<'a> {
+    pub lesson: &'a models::Lesson,
+    /// The start and end of the first occurrence, in the timezone of the class
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub interval: u8,
+    /// The end of the last day of the version, in UTC
+    pub until: NaiveDateTime,
+    /// The starts of the occurrences that are on holidays, in the timezone of the class
+    pub exceptions: Vec<NaiveDateTime>,
+}
+
+/// The lessons of the version as recurring events from the first to the last day of the version.
+/// Lessons of a week of the cycle repeat once per cycle, lessons without an occurrence are skipped
+pub fn recurring_lessons<'a>(
+    lessons: impl Iterator<Item = &'a models::Lesson>,
+    cycle: &TimetableCycle,
+    (from, until): (NaiveDate, NaiveDate),
+    holidays: &BTreeSet<NaiveDate>,
+    timezone: Tz,
+) -> Vec<RecurringLesson<'a>> {
+    let at = |date: NaiveDate, time: i64| {
+        date.and_hms_opt(0, 0, 0).unwrap() + Duration::milliseconds(time)
+    };
+    let until_utc = timezone
+        .from_local_datetime(&at(until.succ_opt().unwrap(), 0))
+        .earliest()
+        .map_or_else(
+            || at(until.succ_opt().unwrap(), 0),
+            |until| until.naive_utc(),
+        )
+        - Duration::seconds(1);
+
+    lessons
+        .filter_map(|lesson| {
+            let first = from
+                .iter_days()
+                .take(7 * cycle.weeks.max(1) as usize)
+                .find(|date| {
+                    date.weekday().num_days_from_monday() as i32 == lesson.weekday
+                        && (lesson.cycle_week.is_none()
+                            || lesson.cycle_week == Some(cycle.week_of(*date) as i32))
+                })
+                .filter(|first| *first <= until)?;
+            let interval = if lesson.cycle_week.is_some() {
+                cycle.weeks.max(1)
+            } else {
+                1
+            };
+
+            let exceptions = std::iter::successors(Some(first), |date| {
+                Some(*date + Duration::weeks(interval as i64))
+            })
+            .take_while(|date| *date <= until)
+            .filter(|date| holidays.contains(date))
+            .map(|date| at(date, lesson.start))
+            .collect();
+
+            Some(RecurringLesson {
+                lesson,
+                start: at(first, lesson.start),
+                end: at(first, lesson.end),
+                interval,
+                until: until_utc,
+                exceptions,
+            })
+        })
+        .collect()
+}
+
+/// The lessons on the date, sorted by their start. Exceptions of other dates are ignored.
+/// There are no lessons on holidays
+pub fn effective_day(
+    timetable: &Timetable,
+    cycle: &TimetableCycle,
+    exceptions: &[TimetableException],
+    holiday: Option<EffectiveHoliday>,
+    date: NaiveDate,
+) -> EffectiveDay {
+    let week = cycle.week_of(date);
+    let exceptions = exceptions
+        .iter()
+        .filter(|exception| exception.date.to_naive().date() == date)
+        .collect::<Vec<_>>();
+
+    let mut lessons = timetable[date.weekday().num_days_from_monday() as usize]
+        .iter()
+        .filter(|lesson| lesson.is_in_week(week))
+        .map(|lesson| {
+            match exceptions
+                .iter()
+                .find(|exception| exception.lesson.is_some() && exception.lesson == lesson.id)
+            {
+                Some(exception) => changed_lesson(lesson.clone(), exception),
+                None => EffectiveLesson {
+                    lesson: lesson.clone(),
+                    cancelled: false,
+                    exception: None,
+                },
+            }
+        })
+        .chain(
+            exceptions
+                .iter()
+                .filter(|exception| exception.lesson.is_none())
+                .map(|exception| extra_lesson(exception)),
+        )
+        .collect::<Vec<_>>();
+    lessons.sort_by_key(|lesson| lesson.lesson.start);
+    if holiday.is_some() {
+        lessons.clear();
+    }
+
+    EffectiveDay {
+        date: date.and_hms_opt(0, 0, 0).unwrap().into(),
+        week: if cycle.weeks > 1 { Some(week) } else { None },
+        lessons,
+        holiday,
+    }
+}
+
+fn changed_lesson(lesson: Lesson, exception: &TimetableException) -> EffectiveLesson {
+    EffectiveLesson {
+        lesson: Lesson {
+            id: lesson.id,
+            subject: exception.subject.clone().unwrap_or(lesson.subject),
+            // the exception only has the name of its subject
+            subject_id: match exception.subject {
+                Some(_) => None,
+                None => lesson.subject_id,
+            },
+            description: if exception.description.is_empty() {
+                lesson.description
+            } else {
+                exception.description.clone()
+            },
+            start: exception.start.unwrap_or(lesson.start),
+            end: exception.end.unwrap_or(lesson.end),
+            room: exception.room.clone().unwrap_or(lesson.room),
+            teacher: exception.teacher.clone().unwrap_or(lesson.teacher),
+            week: lesson.week,
+        },
+        cancelled: exception.cancelled,
+        exception: exception.id,
+    }
+}
+
+/// Extra lessons always have a subject, start and end, they are checked when they are created
+fn extra_lesson(exception: &TimetableException) -> EffectiveLesson {
+    EffectiveLesson {
+        lesson: Lesson {
+            id: None,
+            subject: exception.subject.clone().unwrap_or_default(),
+            subject_id: None,
+            description: exception.description.clone(),
+            start: exception.start.unwrap_or_default(),
+            end: exception.end.unwrap_or_default(),
+            room: exception.room.clone().unwrap_or_default(),
+            teacher: exception.teacher.clone().unwrap_or_default(),
+            week: None,
+        },
+        cancelled: exception.cancelled,
+        exception: exception.id,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::models::{datetime, Event};
+    use chrono::{Datelike, NaiveDate};
+    use dto::{Lesson, Timetable, TimetableCycle, TimetableException};
+    use uuid::Uuid;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2021, 8, day).unwrap()
+    }
+
+    fn lesson(subject: &str, start: i64, week: Option<u8>) -> Lesson {
+        Lesson {
+            id: Some(Uuid::new_v4()),
+            subject: subject.to_string(),
+            subject_id: None,
+            description: "".to_string(),
+            start,
+            end: start + 45,
+            room: "B204".to_string(),
+            teacher: "".to_string(),
+            week,
+        }
+    }
+
+    fn exception(day: u32, lesson: Option<&Lesson>) -> TimetableException {
+        TimetableException {
+            id: Some(Uuid::new_v4()),
+            date: date(day).and_hms_opt(0, 0, 0).unwrap().into(),
+            lesson: lesson.and_then(|lesson| lesson.id),
+            cancelled: false,
+            subject: None,
+            room: None,
+            teacher: None,
+            start: None,
+            end: None,
+            description: "".to_string(),
+        }
+    }
+
+    fn timetable(monday: Vec<Lesson>) -> Timetable {
+        [monday, vec![], vec![], vec![], vec![], vec![], vec![]]
+    }
+
+    const EVERY_WEEK: TimetableCycle = TimetableCycle {
+        weeks: 1,
+        anchor: dto::Timestamp(0),
+    };
+
+    #[test]
+    fn changed_and_cancelled_lessons() {
+        let (math, german) = (lesson("Mathe", 100, None), lesson("Deutsch", 200, None));
+        let table = timetable(vec![math.clone(), german.clone()]);
+        let exceptions = vec![
+            TimetableException {
+                room: Some("A101".to_string()),
+                teacher: Some("Stellvertretung".to_string()),
+                ..exception(23, Some(&math))
+            },
+            TimetableException {
+                cancelled: true,
+                ..exception(23, Some(&german))
+            },
+            // another monday
+            TimetableException {
+                cancelled: true,
+                ..exception(30, Some(&math))
+            },
+        ];
+
+        let day = super::effective_day(&table, &EVERY_WEEK, &exceptions, None, date(23));
+        assert_eq!(day.date, date(23).and_hms_opt(0, 0, 0).unwrap().into());
+        assert_eq!(day.week, None);
+        assert_eq!(day.lessons.len(), 2);
+        assert_eq!(day.lessons[0].lesson.subject, "Mathe");
+        assert_eq!(day.lessons[0].lesson.room, "A101");
+        assert_eq!(day.lessons[0].lesson.teacher, "Stellvertretung");
+        assert!(!day.lessons[0].cancelled);
+        assert_eq!(day.lessons[0].exception, exceptions[0].id);
+        assert!(day.lessons[1].cancelled);
+
+        let unchanged = super::effective_day(&table, &EVERY_WEEK, &[], None, date(23));
+        assert_eq!(unchanged.lessons[0].lesson, math);
+        assert_eq!(unchanged.lessons[0].exception, None);
+    }
+
+    #[test]
+    fn moved_and_extra_lessons() {
+        let (math, german) = (lesson("Mathe", 100, None), lesson("Deutsch", 200, None));
+        let table = timetable(vec![math.clone(), german]);
+        let exceptions = vec![
+            TimetableException {
+                start: Some(300),
+                end: Some(345),
+                ..exception(23, Some(&math))
+            },
+            TimetableException {
+                subject: Some("Physik".to_string()),
+                start: Some(150),
+                end: Some(195),
+                ..exception(23, None)
+            },
+        ];
+
+        let day = super::effective_day(&table, &EVERY_WEEK, &exceptions, None, date(23));
+        let subjects = day
+            .lessons
+            .iter()
+            .map(|lesson| (&*lesson.lesson.subject, lesson.lesson.start))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            subjects,
+            vec![("Physik", 150), ("Deutsch", 200), ("Mathe", 300)]
+        );
+        assert_eq!(day.lessons[0].lesson.id, None);
+        assert_eq!(day.lessons[0].exception, exceptions[1].id);
+    }
+
+    #[test]
+    fn versions_by_date() {
+        let version = |from: Option<u32>, until: Option<u32>| crate::models::TimetableVersion {
+            id: Uuid::new_v4(),
+            class: Uuid::nil(),
+            valid_from: from.map(date),
+            valid_until: until.map(date),
+        };
+        let versions = vec![version(None, Some(15)), version(Some(16), None)];
+
+        assert_eq!(
+            super::active_version(&versions, date(1)).unwrap().id,
+            versions[0].id
+        );
+        assert_eq!(
+            super::active_version(&versions, date(15)).unwrap().id,
+            versions[0].id
+        );
+        assert_eq!(
+            super::active_version(&versions, date(16)).unwrap().id,
+            versions[1].id
+        );
+        assert!(super::active_version(&versions[..1], date(16)).is_none());
+
+        let range = |from: Option<u32>, until: Option<u32>| (from.map(date), until.map(date));
+        assert!(!super::ranges_overlap(
+            range(None, Some(15)),
+            range(Some(16), None)
+        ));
+        assert!(super::ranges_overlap(
+            range(None, Some(16)),
+            range(Some(16), None)
+        ));
+        assert!(super::ranges_overlap(
+            range(None, None),
+            range(Some(1), Some(2))
+        ));
+        assert!(!super::ranges_overlap(
+            range(Some(3), Some(4)),
+            range(Some(1), Some(2))
+        ));
+    }
+
+    fn stored_lesson(weekday: i32, cycle_week: Option<i32>) -> crate::models::Lesson {
+        crate::models::Lesson {
+            id: Uuid::new_v4(),
+            class: Uuid::nil(),
+            weekday,
+            start: 8 * 60 * 60 * 1000,
+            end: 9 * 60 * 60 * 1000,
+            subject: "Mathe".to_string(),
+            room: "".to_string(),
+            teacher: "".to_string(),
+            description: "".to_string(),
+            cycle_week,
+            version: Uuid::nil(),
+            subject_id: Uuid::nil(),
+        }
+    }
+
+    fn holiday(start: &str, end: Option<&str>) -> Event {
+        Event {
+            e_type: dto::EventType::HOLIDAYS,
+            name: "Herbstferien".to_string(),
+            end: end.map(datetime),
+            ..Event::test(datetime(start))
+        }
+    }
+
+    #[test]
+    fn school_years_and_holidays() {
+        let september = NaiveDate::from_ymd_opt(2021, 9, 1).unwrap();
+        let year = (
+            NaiveDate::from_ymd_opt(2021, 8, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2022, 7, 31).unwrap(),
+        );
+        assert_eq!(super::school_year(september), year);
+        assert_eq!(super::school_year(year.1), year);
+
+        let version = |from, until| crate::models::TimetableVersion {
+            id: Uuid::nil(),
+            class: Uuid::nil(),
+            valid_from: from,
+            valid_until: until,
+        };
+        assert_eq!(super::version_range(&version(None, None), september), year);
+        assert_eq!(
+            super::version_range(&version(Some(date(16)), None), september),
+            (date(16), year.1)
+        );
+        assert_eq!(
+            super::version_range(&version(None, Some(date(15))), september),
+            (year.0, date(15))
+        );
+
+        // times are UTC, the holidays are in Europe/Zurich
+        let holidays = vec![
+            holiday("2021-08-23 22:00", Some("2021-08-25 22:00")),
+            holiday("2021-08-30 10:00", None),
+        ];
+        let dates = super::holiday_dates(&holidays, chrono_tz::Europe::Zurich);
+        assert_eq!(
+            dates.into_iter().collect::<Vec<_>>(),
+            vec![date(24), date(25), date(30)]
+        );
+    }
+
+    #[test]
+    fn no_lessons_on_holidays() {
+        let table = timetable(vec![lesson("Mathe", 100, None)]);
+        let holidays = vec![
+            holiday("2021-08-22 22:00", Some("2021-08-24 22:00")),
+            holiday("2021-08-22 22:00", Some("2021-09-05 22:00")),
+        ];
+
+        let holiday = super::holiday_on(&holidays, chrono_tz::Europe::Zurich, date(23)).unwrap();
+        assert_eq!(holiday.event, holidays[1].id);
+        assert_eq!(holiday.name, "Herbstferien");
+        assert_eq!(
+            holiday.until,
+            date(5)
+                .with_month(9)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+                .into()
+        );
+        assert_eq!(
+            super::holiday_on(&holidays, chrono_tz::Europe::Zurich, date(22)),
+            None
+        );
+
+        let day = super::effective_day(&table, &EVERY_WEEK, &[], Some(holiday.clone()), date(23));
+        assert!(day.lessons.is_empty());
+        assert_eq!(day.holiday, Some(holiday));
+    }
+
+    #[test]
+    fn recurring_lessons() {
+        let lessons = [stored_lesson(0, None), stored_lesson(1, Some(1))];
+        let cycle = TimetableCycle {
+            weeks: 2,
+            anchor: date(23).and_hms_opt(0, 0, 0).unwrap().into(),
+        };
+        let holidays = vec![date(30), date(31)].into_iter().collect();
+
+        let recurring = super::recurring_lessons(
+            lessons.iter(),
+            &cycle,
+            (date(18), date(31)),
+            &holidays,
+            chrono_tz::Europe::Zurich,
+        );
+        assert_eq!(recurring.len(), 2);
+        assert_eq!(recurring[0].start, date(23).and_hms_opt(8, 0, 0).unwrap());
+        assert_eq!(recurring[0].end, date(23).and_hms_opt(9, 0, 0).unwrap());
+        assert_eq!(recurring[0].interval, 1);
+        assert_eq!(
+            recurring[0].until,
+            date(31).and_hms_opt(21, 59, 59).unwrap()
+        );
+        assert_eq!(
+            recurring[0].exceptions,
+            vec![date(30).and_hms_opt(8, 0, 0).unwrap()]
+        );
+        // the B week starts on the 30th
+        assert_eq!(recurring[1].start, date(31).and_hms_opt(8, 0, 0).unwrap());
+        assert_eq!(recurring[1].interval, 2);
+        assert_eq!(recurring[1].exceptions.len(), 1);
+
+        let too_short = super::recurring_lessons(
+            lessons.iter(),
+            &cycle,
+            (date(24), date(29)),
+            &holidays,
+            chrono_tz::Europe::Zurich,
+        );
+        assert!(too_short.is_empty());
+    }
+
+    #[test]
+    fn lessons_of_the_cycle_week() {
+        let table = timetable(vec![
+            lesson("A", 100, Some(0)),
+            lesson("B", 100, Some(1)),
+            lesson("Mathe", 200, None),
+        ]);
+        let cycle = TimetableCycle {
+            weeks: 2,
+            anchor: date(23).and_hms_opt(0, 0, 0).unwrap().into(),
+        };
+
+        let a_week = super::effective_day(&table, &cycle, &[], None, date(23));
+        assert_eq!(a_week.week, Some(0));
+        assert_eq!(a_week.lessons[0].lesson.subject, "A");
+        assert_eq!(a_week.lessons.len(), 2);
+
+        let b_week = super::effective_day(&table, &cycle, &[], None, date(30));
+        assert_eq!(b_week.week, Some(1));
+        assert_eq!(b_week.lessons[0].lesson.subject, "B");
+
+        let tuesday = super::effective_day(&table, &cycle, &[], None, date(24));
+        assert!(tuesday.lessons.is_empty());
+    }
+}
@@ -0,0 +1,362 @@
+//! Parsing of timetables that are exported as CSV, from a spreadsheet or from WebUntis
+
+use chrono::{Datelike, NaiveDate};
+use dto::{Lesson, Timetable};
+
+const WEEKDAYS: [[&str; 2]; 7] = [
+    ["montag", "monday"],
+    ["dienstag", "tuesday"],
+    ["mittwoch", "wednesday"],
+    ["donnerstag", "thursday"],
+    ["freitag", "friday"],
+    ["samstag", "saturday"],
+    ["sonntag", "sunday"],
+];
+
+const DAY_COLUMNS: &[&str] = &["tag", "wochentag", "day", "weekday"];
+const DATE_COLUMNS: &[&str] = &["datum", "date"];
+const START_COLUMNS: &[&str] = &["von", "beginn", "start", "startzeit"];
+const END_COLUMNS: &[&str] = &["bis", "ende", "end", "endzeit"];
+const SUBJECT_COLUMNS: &[&str] = &["fach", "fächer", "subject"];
+const ROOM_COLUMNS: &[&str] = &["raum", "räume", "room"];
+const TEACHER_COLUMNS: &[&str] = &["lehrer", "lehrkraft", "lehrkräfte", "teacher"];
+const DESCRIPTION_COLUMNS: &[&str] = &["text", "info", "bemerkung", "description"];
+
+/// A row that could not be parsed, the other rows can still be imported
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowError {
+    /// The line of the row, starting at 1
+    pub row: usize,
+    pub error: &'static str,
+}
+
+/// Parses a CSV file with the columns weekday, start, end, subject and optionally room, description and teacher.
+/// The columns are separated by `,`, `;` or tabs, a header row is skipped.
+/// The lessons of every day are sorted by their start.
+pub fn parse_csv(text: &str) -> (Timetable, Vec<RowError>) {
+    let rows = split_rows(text);
+    let mut timetable = Timetable::default();
+    let mut errors = vec![];
+
+    for (index, (row, fields)) in rows.iter().enumerate() {
+        let field = |column: usize| fields.get(column).map_or("", String::as_str);
+        // a header does not have a time in the start column
+        if index == 0 && parse_time(field(1)).is_err() {
+            continue;
+        }
+        if fields.len() < 4 {
+            errors.push(RowError {
+                row: *row,
+                error: "missing-columns",
+            });
+            continue;
+        }
+
+        let lesson = parse_weekday(field(0)).and_then(|weekday| {
+            Ok((
+                weekday,
+                new_lesson(field(1), field(2), field(3), field(4), field(6), field(5))?,
+            ))
+        });
+        match lesson {
+            Ok((weekday, lesson)) => timetable[weekday].push(lesson),
+            Err(error) => errors.push(RowError { row: *row, error }),
+        }
+    }
+
+    sort_days(&mut timetable);
+    (timetable, errors)
+}
+
+/// Parses the CSV export of a timetable in WebUntis. The columns are found by the names in the header,
+/// the day is taken from a weekday or a date column. An export of several weeks contains every lesson once per week,
+/// the same lesson is only imported once.
+/// Returns an error if the header is missing a column.
+pub fn parse_untis(text: &str) -> Result<(Timetable, Vec<RowError>), &'static str> {
+    let rows = split_rows(text);
+    let (_, header) = rows.first().ok_or("invalid-untis-export")?;
+    let column = |names: &[&str]| {
+        header
+            .iter()
+            .position(|name| names.contains(&name.trim().to_lowercase().as_str()))
+    };
+
+    let day = match (column(DAY_COLUMNS), column(DATE_COLUMNS)) {
+        (Some(day), _) => Ok(day),
+        (None, Some(date)) => Err(date),
+        (None, None) => return Err("invalid-untis-export"),
+    };
+    let start = column(START_COLUMNS).ok_or("invalid-untis-export")?;
+    let end = column(END_COLUMNS).ok_or("invalid-untis-export")?;
+    let subject = column(SUBJECT_COLUMNS).ok_or("invalid-untis-export")?;
+    let (room, teacher, description) = (
+        column(ROOM_COLUMNS),
+        column(TEACHER_COLUMNS),
+        column(DESCRIPTION_COLUMNS),
+    );
+
+    let mut timetable = Timetable::default();
+    let mut errors = vec![];
+
+    for (row, fields) in rows.iter().skip(1) {
+        let field = |column: Option<usize>| {
+            column
+                .and_then(|column| fields.get(column))
+                .map_or("", String::as_str)
+        };
+        let weekday = match day {
+            Ok(day) => parse_weekday(field(Some(day))),
+            Err(date) => parse_date(field(Some(date)))
+                .map(|date| date.weekday().num_days_from_monday() as usize),
+        };
+        let lesson = weekday.and_then(|weekday| {
+            Ok((
+                weekday,
+                new_lesson(
+                    field(Some(start)),
+                    field(Some(end)),
+                    field(Some(subject)),
+                    field(room),
+                    field(teacher),
+                    field(description),
+                )?,
+            ))
+        });
+        match lesson {
+            Ok((weekday, lesson)) => {
+                if !timetable[weekday].contains(&lesson) {
+                    timetable[weekday].push(lesson)
+                }
+            }
+            Err(error) => errors.push(RowError { row: *row, error }),
+        }
+    }
+
+    sort_days(&mut timetable);
+    Ok((timetable, errors))
+}
+
+fn new_lesson(
+    start: &str,
+    end: &str,
+    subject: &str,
+    room: &str,
+    teacher: &str,
+    description: &str,
+) -> Result<Lesson, &'static str> {
+    let subject = subject.trim();
+    if subject.is_empty() {
+        return Err("missing-subject");
+    }
+    Ok(Lesson {
+        id: None,
+        subject: subject.to_string(),
+        subject_id: None,
+        description: description.trim().to_string(),
+        start: parse_time(start)?,
+        end: parse_time(end)?,
+        room: room.trim().to_string(),
+        teacher: teacher.trim().to_string(),
+        week: None,
+    })
+}
+
+fn sort_days(timetable: &mut Timetable) {
+    for day in timetable.iter_mut() {
+        day.sort_by_key(|lesson| lesson.start);
+    }
+}
+
+/// The non-empty rows with their line, starting at 1, split into their fields.
+/// The separator is the one of `,`, `;` and tab that is used most in the first row
+fn split_rows(text: &str) -> Vec<(usize, Vec<String>)> {
+    let text = text.trim_start_matches('\u{feff}');
+    let first = text
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or("");
+    let separator = [',', ';', '\t']
+        .iter()
+        .copied()
+        .max_by_key(|separator| first.matches(*separator).count())
+        .unwrap_or(',');
+
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| (index + 1, split_fields(line, separator)))
+        .collect()
+}
+
+/// Splits a row at the separator. Fields can be quoted with `"`, a `""` in a quoted field is a single `"`
+fn split_fields(line: &str, separator: char) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(char) = chars.next() {
+        match char {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            char if char == separator && !quoted => fields.push(std::mem::take(&mut field)),
+            char => field.push(char),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// `0` is monday. Days can be numbers, or german or english names that can be shortened to two letters
+fn parse_weekday(day: &str) -> Result<usize, &'static str> {
+    let day = day.trim().trim_end_matches('.').to_lowercase();
+    if let Ok(number) = day.parse::<usize>() {
+        return if number < 7 {
+            Ok(number)
+        } else {
+            Err("invalid-weekday")
+        };
+    }
+    if day.chars().count() < 2 {
+        return Err("invalid-weekday");
+    }
+    WEEKDAYS
+        .iter()
+        .position(|names| names.iter().any(|name| name.starts_with(&day)))
+        .ok_or("invalid-weekday")
+}
+
+/// `dd.mm.yyyy` or `yyyy-mm-dd`
+fn parse_date(date: &str) -> Result<NaiveDate, &'static str> {
+    let date = date.trim();
+    NaiveDate::parse_from_str(date, "%d.%m.%Y")
+        .or_else(|_| NaiveDate::parse_from_str(date, "%Y-%m-%d"))
+        .map_err(|_| "invalid-date")
+}
+
+/// `8:00`, `08.00` or `0800`, as the milliseconds since 00:00
+fn parse_time(time: &str) -> Result<i64, &'static str> {
+    let time = time.trim();
+    // only ASCII is valid, so the time can be split at any byte
+    if !time.is_ascii() {
+        return Err("invalid-time");
+    }
+    let (hours, minutes) = match time.find([':', '.']) {
+        Some(index) => (&time[..index], &time[index + 1..]),
+        None if time.len() >= 3 => time.split_at(time.len() - 2),
+        None => return Err("invalid-time"),
+    };
+    if minutes.len() != 2 {
+        return Err("invalid-time");
+    }
+    let hours = hours.parse::<i64>().map_err(|_| "invalid-time")?;
+    let minutes = minutes.parse::<i64>().map_err(|_| "invalid-time")?;
+    if !(0..=24).contains(&hours) || !(0..60).contains(&minutes) {
+        return Err("invalid-time");
+    }
+    Ok((hours * 60 + minutes) * 60 * 1000)
+}
+
+#[cfg(test)]
+mod test {
+    use super::RowError;
+
+    const HOUR: i64 = 60 * 60 * 1000;
+
+    #[test]
+    fn csv() {
+        let csv = "Wochentag;Beginn;Ende;Fach;Raum;Beschreibung\n\
+                   Mo;08:00;08:45;Mathe;B204;\n\
+                   Montag;7:15;8:00;\"Deutsch; Aufsatz\";A101;\"Heft \"\"blau\"\"\"\n\
+                   \n\
+                   Fr.;1300;1345;Sport;Halle;;Meier\n\
+                   Xy;08:00;08:45;Mathe;B204;\n\
+                   4;08:00;;Mathe;B204;\n\
+                   4;08:00\n";
+
+        let (timetable, errors) = super::parse_csv(csv);
+        assert_eq!(timetable[0].len(), 2);
+        assert_eq!(timetable[0][0].subject, "Deutsch; Aufsatz");
+        assert_eq!(timetable[0][0].start, 7 * HOUR + 15 * 60 * 1000);
+        assert_eq!(timetable[0][0].description, "Heft \"blau\"");
+        assert_eq!(timetable[0][1].subject, "Mathe");
+        assert_eq!(timetable[0][1].room, "B204");
+        assert_eq!(timetable[4][0].start, 13 * HOUR);
+        assert_eq!(timetable[4][0].teacher, "Meier");
+        assert_eq!(
+            errors,
+            vec![
+                RowError {
+                    row: 6,
+                    error: "invalid-weekday"
+                },
+                RowError {
+                    row: 7,
+                    error: "invalid-time"
+                },
+                RowError {
+                    row: 8,
+                    error: "missing-columns"
+                },
+            ]
+        );
+
+        // without a header, separated by commas
+        let (timetable, errors) = super::parse_csv("0,08:00,08:45,Mathe\n");
+        assert!(errors.is_empty());
+        assert_eq!(timetable[0][0].end, 8 * HOUR + 45 * 60 * 1000);
+    }
+
+    #[test]
+    fn untis() {
+        let export = "\"Datum\"\t\"Von\"\t\"Bis\"\t\"Klasse\"\t\"Fach\"\t\"Lehrer\"\t\"Raum\"\n\
+                      23.08.2021\t08:00\t08:45\tI3a\tMathe\tMEI\tB204\n\
+                      23.08.2021\t08:50\t09:35\tI3a\tDeutsch\tHUB\tA101\n\
+                      30.08.2021\t08:00\t08:45\tI3a\tMathe\tMEI\tB204\n\
+                      2021-08-24\t10:00\t10:45\tI3a\tPhysik\tMEI\tL1\n\
+                      24.13.2021\t10:00\t10:45\tI3a\tPhysik\tMEI\tL1\n";
+
+        let (timetable, errors) = super::parse_untis(export).unwrap();
+        assert_eq!(timetable[0].len(), 2);
+        assert_eq!(timetable[0][0].subject, "Mathe");
+        assert_eq!(timetable[0][0].teacher, "MEI");
+        assert_eq!(timetable[0][1].room, "A101");
+        assert_eq!(timetable[1][0].subject, "Physik");
+        assert_eq!(
+            errors,
+            vec![RowError {
+                row: 6,
+                error: "invalid-date"
+            }]
+        );
+
+        let by_day = "Tag;Von;Bis;Fach\nDi;10:00;10:45;Physik\n";
+        assert_eq!(super::parse_untis(by_day).unwrap().0[1].len(), 1);
+        assert_eq!(
+            super::parse_untis("Datum;Von;Bis\n"),
+            Err("invalid-untis-export")
+        );
+    }
+
+    #[test]
+    fn weekdays_and_times() {
+        assert_eq!(super::parse_weekday("Mi"), Ok(2));
+        assert_eq!(super::parse_weekday("thu"), Ok(3));
+        assert_eq!(super::parse_weekday("Sonntag"), Ok(6));
+        assert_eq!(super::parse_weekday("6"), Ok(6));
+        assert!(super::parse_weekday("7").is_err());
+        assert!(super::parse_weekday("m").is_err());
+
+        assert_eq!(super::parse_time("8:05"), Ok(8 * HOUR + 5 * 60 * 1000));
+        assert_eq!(super::parse_time("08.05"), super::parse_time("0805"));
+        assert_eq!(super::parse_time("24:00"), Ok(24 * HOUR));
+        assert!(super::parse_time("8:5").is_err());
+        assert!(super::parse_time("8:60").is_err());
+        assert!(super::parse_time("800x").is_err());
+        assert!(super::parse_time("8ü0").is_err());
+        assert!(super::parse_time("ü:00").is_err());
+    }
+}
@@ -0,0 +1,217 @@
+//! Counts the events of a class per week, to find the weeks with too many exams.
+//! Weeks start on monday in the timezone of the class, events count for the week they start in.
+
+use crate::models::Event;
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use dto::{EventType, EventTypeCount, Timestamp, WeekWorkload, WorkloadWarning};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The monday of the week of the time in the timezone
+pub fn week_of(time: NaiveDateTime, timezone: Tz) -> NaiveDate {
+    let date = timezone.from_utc_datetime(&time).date_naive();
+    date - Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+/// Monday 00:00 of the week in the timezone, as UTC.
+/// If midnight is skipped by daylight saving time, the week starts an hour later.
+pub fn week_start(week: NaiveDate, timezone: Tz) -> NaiveDateTime {
+    let midnight = week.and_hms_opt(0, 0, 0).unwrap();
+    timezone
+        .from_local_datetime(&midnight)
+        .earliest()
+        .or_else(|| {
+            timezone
+                .from_local_datetime(&(midnight + Duration::hours(1)))
+                .earliest()
+        })
+        .map_or(midnight, |start| start.naive_utc())
+}
+
+/// The timestamp of monday 00:00 of the week in the timezone
+pub fn week_timestamp(week: NaiveDate, timezone: Tz) -> Timestamp {
+    week_start(week, timezone).into()
+}
+
+/// The amount of events of each type per week
+fn weekly_counts(events: &[Event], timezone: Tz) -> BTreeMap<NaiveDate, BTreeMap<i32, usize>> {
+    let mut weeks = BTreeMap::<NaiveDate, BTreeMap<i32, usize>>::new();
+    for event in events {
+        *weeks
+            .entry(week_of(event.start, timezone))
+            .or_default()
+            .entry(event.e_type)
+            .or_default() += 1;
+    }
+    weeks
+}
+
+fn exams(counts: &BTreeMap<i32, usize>) -> usize {
+    counts.get(&EventType::EXAM).copied().unwrap_or_default()
+}
+
+/// The weeks of `weeks` that have more exams than the limit
+pub fn exam_warnings(
+    events: &[Event],
+    weeks: &BTreeSet<NaiveDate>,
+    timezone: Tz,
+    limit: i32,
+) -> Vec<WorkloadWarning> {
+    weekly_counts(events, timezone)
+        .into_iter()
+        .filter(|(week, _)| weeks.contains(week))
+        .map(|(week, counts)| (week, exams(&counts)))
+        .filter(|(_, exams)| *exams as i64 > limit as i64)
+        .map(|(week, exams)| WorkloadWarning {
+            week: week_timestamp(week, timezone),
+            exams,
+            limit,
+        })
+        .collect()
+}
+
+/// The workload of every week from the week of `after` until `before`, including empty weeks
+pub fn workload(
+    events: &[Event],
+    after: NaiveDateTime,
+    before: NaiveDateTime,
+    timezone: Tz,
+    limit: i32,
+) -> Vec<WeekWorkload> {
+    let counts = weekly_counts(events, timezone);
+
+    std::iter::successors(Some(week_of(after, timezone)), |week| {
+        Some(*week + Duration::weeks(1))
+    })
+    .take_while(|week| week_start(*week, timezone) < before)
+    .map(|week| {
+        let types = counts.get(&week).cloned().unwrap_or_default();
+        WeekWorkload {
+            week: week_timestamp(week, timezone),
+            overloaded: exams(&types) as i64 > limit as i64,
+            types: types
+                .into_iter()
+                .map(|(r#type, count)| EventTypeCount { r#type, count })
+                .collect(),
+        }
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::models::{datetime, Event};
+    use chrono::NaiveDate;
+    use chrono_tz::Tz;
+    use dto::EventType;
+    use std::collections::BTreeSet;
+
+    fn event(e_type: i32, start: &str) -> Event {
+        Event {
+            e_type,
+            ..Event::test(datetime(start))
+        }
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn weeks_start_on_monday() {
+        let monday = date(2021, 8, 23);
+        assert_eq!(
+            super::week_of(event(1, "2021-08-23 00:00").start, Tz::UTC),
+            monday
+        );
+        assert_eq!(
+            super::week_of(event(1, "2021-08-27 10:00").start, Tz::UTC),
+            monday
+        );
+        assert_eq!(
+            super::week_of(event(1, "2021-08-29 23:59").start, Tz::UTC),
+            monday
+        );
+        assert_eq!(
+            super::week_of(event(1, "2021-08-30 08:00").start, Tz::UTC),
+            date(2021, 8, 30)
+        );
+    }
+
+    #[test]
+    fn weeks_in_timezone() {
+        let zurich = chrono_tz::Europe::Zurich;
+        // monday 00:30 in Zurich
+        let monday = event(1, "2021-08-22 22:30").start;
+        assert_eq!(super::week_of(monday, zurich), date(2021, 8, 23));
+        assert_eq!(super::week_of(monday, Tz::UTC), date(2021, 8, 16));
+        assert_eq!(
+            super::week_start(date(2021, 8, 23), zurich),
+            datetime("2021-08-22 22:00")
+        );
+    }
+
+    #[test]
+    fn too_many_exams() {
+        let events = vec![
+            event(EventType::EXAM, "2021-08-23 08:00"),
+            event(EventType::EXAM, "2021-08-24 08:00"),
+            event(EventType::EXAM, "2021-08-26 08:00"),
+            event(EventType::HOMEWORK, "2021-08-26 08:00"),
+            event(EventType::EXAM, "2021-08-27 08:00"),
+            event(EventType::EXAM, "2021-08-30 08:00"),
+        ];
+        let weeks = vec![date(2021, 8, 23), date(2021, 8, 30)]
+            .into_iter()
+            .collect::<BTreeSet<_>>();
+
+        let warnings = super::exam_warnings(&events, &weeks, Tz::UTC, 3);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].week,
+            super::week_timestamp(date(2021, 8, 23), Tz::UTC)
+        );
+        assert_eq!(warnings[0].exams, 4);
+        assert_eq!(warnings[0].limit, 3);
+
+        assert!(super::exam_warnings(&events, &weeks, Tz::UTC, 4).is_empty());
+        // only the weeks of the new exam are checked
+        let later = vec![date(2021, 8, 30)].into_iter().collect::<BTreeSet<_>>();
+        assert!(super::exam_warnings(&events, &later, Tz::UTC, 3).is_empty());
+    }
+
+    #[test]
+    fn workload_of_weeks() {
+        let events = vec![
+            event(EventType::EXAM, "2021-08-23 08:00"),
+            event(EventType::EXAM, "2021-08-24 08:00"),
+            event(EventType::HOMEWORK, "2021-08-26 08:00"),
+            event(EventType::HOMEWORK, "2021-09-07 08:00"),
+        ];
+        let after = datetime("2021-08-25 12:00");
+        let before = datetime("2021-09-07 00:00");
+
+        let workload = super::workload(&events, after, before, Tz::UTC, 1);
+        assert_eq!(workload.len(), 3);
+        assert_eq!(
+            workload[0].week,
+            super::week_timestamp(date(2021, 8, 23), Tz::UTC)
+        );
+        assert!(workload[0].overloaded);
+        assert_eq!(
+            workload[0]
+                .types
+                .iter()
+                .map(|count| (count.r#type, count.count))
+                .collect::<Vec<_>>(),
+            vec![(EventType::HOMEWORK, 1), (EventType::EXAM, 2)]
+        );
+        assert!(workload[1].types.is_empty());
+        assert!(!workload[1].overloaded);
+        assert_eq!(
+            workload[2].week,
+            super::week_timestamp(date(2021, 9, 6), Tz::UTC)
+        );
+        assert_eq!(workload[2].types.len(), 1);
+    }
+}